@@ -36,17 +36,22 @@ fn print_vorbis_comments(vorbis_comment: &VorbisComment, args: &Arguments) {
 
     println!("Number of Comments: {}", vorbis_comment.comments.len());
 
-    for (name, value) in &vorbis_comment.comments {
+    for &(ref name, ref value) in &vorbis_comment.comments {
       println!("  {}: \"{}\" = {}", index, name, value);
 
       index += 1;
     }
   } else {
     if let Some(ref name) = args.flag_name {
-      let error_str = format!("Couldn't find tag name: \"{}\"", name);
-      let result    = vorbis_comment.comments.get(name).unwrap_or(&error_str);
+      let values = vorbis_comment.get_all(name);
 
-      println!("{}", result)
+      if values.is_empty() {
+        println!("Couldn't find tag name: \"{}\"", name);
+      }
+
+      for value in values {
+        println!("{}", value);
+      }
     }
   }
 }
@@ -55,7 +60,7 @@ fn export_vorbis_comments(vorbis_comment: &VorbisComment, filename: &str)
                           -> io::Result<()> {
   let mut file = try!(File::create(filename));
 
-  for (name, value) in &vorbis_comment.comments {
+  for &(ref name, ref value) in &vorbis_comment.comments {
     try!(write!(file, "{}={}\n", name, value));
   }
 
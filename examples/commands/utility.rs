@@ -35,7 +35,7 @@ pub fn list_block_names(filename: &str) {
   println!("stream info");
 
   for meta in stream.metadata() {
-    println!("{}", match meta.data {
+    let name = match meta.data {
       metadata::Data::StreamInfo(_)    => "stream info",
       metadata::Data::Padding(_)       => "padding",
       metadata::Data::Application(_)   => "application",
@@ -43,7 +43,13 @@ pub fn list_block_names(filename: &str) {
       metadata::Data::VorbisComment(_) => "vorbis comment",
       metadata::Data::CueSheet(_)      => "cuesheet",
       metadata::Data::Picture(_)       => "picture",
-      metadata::Data::Unknown(_)       => "unknown",
-    });
+      metadata::Data::Unknown(_, _)    => "unknown",
+    };
+
+    if let metadata::Data::Padding(length) = meta.data {
+      println!("{} ({} bytes)", name, length);
+    } else {
+      println!("{}", name);
+    }
   }
 }
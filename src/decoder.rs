@@ -0,0 +1,278 @@
+use std::marker::PhantomData;
+
+use frame::{Scratch, frame_parser_into};
+use metadata::{self, Metadata, StreamInfo};
+use stream::{Block, DecodeError, decode_frame};
+use utility::{
+  Buffer, ErrorKind, ParserState, Sample,
+  from_iresult, parser,
+};
+
+/// Outcome of `Decoder::next_block`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+  /// A frame was decoded into the block.
+  Block,
+  /// The bytes fed in so far end before the next metadata block or frame
+  /// does.
+  NeedMoreData,
+}
+
+/// FLAC decoder that gets fed bytes as they arrive, instead of reading
+/// them from a source.
+///
+/// Bytes that don't make up a whole metadata block or frame yet are kept
+/// until the next call to `Decoder::feed`, so the stream can be split at
+/// any point. Nothing ever blocks, running out of bytes is reported as
+/// `Status::NeedMoreData`.
+///
+/// # Examples
+///
+/// ```
+/// use flac::{Block, Decoder};
+///
+/// let mut decoder = Decoder::new();
+/// let chunks: Vec<Vec<u8>> = Vec::new();
+///
+/// for chunk in &chunks {
+///   for block in decoder.feed(chunk) {
+///     let block: Block<i32> = match block {
+///       Ok(block)  => block,
+///       Err(error) => panic!("{:?}", error),
+///     };
+///
+///     for channel in 0..block.channels() {
+///       let samples = block.channel(channel);
+///
+///       // Process the samples of a single channel
+///     }
+///   }
+/// }
+/// ```
+pub struct Decoder {
+  buffer: Buffer,
+  state: ParserState,
+  info: Option<StreamInfo>,
+  metadata: Vec<Metadata>,
+  is_metadata_done: bool,
+  // Byte offset and number of the next frame to decode.
+  byte_offset: u64,
+  frame_number: u64,
+  // Frame that every frame gets parsed into, so it's only allocated once.
+  scratch: Scratch,
+}
+
+impl Decoder {
+  /// Constructs a `Decoder` that hasn't been fed any bytes.
+  pub fn new() -> Self {
+    Decoder {
+      buffer: Buffer::new(),
+      state: ParserState::Header,
+      info: None,
+      metadata: Vec::new(),
+      is_metadata_done: false,
+      byte_offset: 0,
+      frame_number: 0,
+      scratch: Scratch::new(0, 0),
+    }
+  }
+
+  /// Returns information for the stream, once `StreamInfo` has been
+  /// parsed.
+  #[inline]
+  pub fn info(&self) -> Option<StreamInfo> {
+    self.info
+  }
+
+  /// Returns a slice of `Metadata`, excluding `StreamInfo`, parsed so far.
+  #[inline]
+  pub fn metadata(&self) -> &[Metadata] {
+    &self.metadata
+  }
+
+  /// Returns true once every metadata block has been parsed and the next
+  /// bytes are frames.
+  #[inline]
+  pub fn is_metadata_done(&self) -> bool {
+    self.is_metadata_done
+  }
+
+  /// Adds `bytes` to the end of the stream and returns an iterator over
+  /// every block that can be decoded with them.
+  ///
+  /// The iterator ends once more bytes are needed, or right after the
+  /// first error. Blocks left over, from not iterating until the end, are
+  /// returned by the next call.
+  pub fn feed<'a, S>(&'a mut self, bytes: &[u8]) -> Blocks<'a, S>
+   where S: Sample {
+    let mut input = bytes;
+    let needed    = self.buffer.len() + bytes.len();
+
+    self.buffer.resize(needed);
+
+    while !input.is_empty() {
+      match self.buffer.fill(&mut input) {
+        Ok(0) | Err(_) => break,
+        Ok(_)          => continue,
+      }
+    }
+
+    Blocks {
+      decoder: self,
+      is_finished: false,
+      phantom: PhantomData,
+    }
+  }
+
+  /// Decodes the next frame, from the bytes fed in so far, into `block`.
+  ///
+  /// Any metadata blocks before the first frame get parsed first. The
+  /// buffer inside of `block` gets reused, the same way as
+  /// `Stream::next_block`.
+  ///
+  /// # Failures
+  ///
+  /// * `DecodeError` is returned when a metadata block or frame fails to
+  ///   parse. The bytes are kept as is, so the same error is returned
+  ///   until the decoder is replaced.
+  /// * `ErrorKind::InvalidSampleSize` is the kind of error when `S` can't
+  ///   hold one more bit than the stream's bits per sample.
+  pub fn next_block<S>(&mut self, block: &mut Block<S>)
+                       -> Result<Status, DecodeError>
+   where S: Sample {
+    if !self.is_metadata_done {
+      try!(self.next_metadata());
+
+      if !self.is_metadata_done {
+        return Ok(Status::NeedMoreData);
+      }
+    }
+
+    let info        = self.info.unwrap_or_default();
+    let channels    = info.channels as usize;
+    let buffer_size = info.max_block_size as usize * channels;
+
+    if info.bits_per_sample as usize >= S::size_extended() {
+      return Err(self.decode_error(ErrorKind::InvalidSampleSize));
+    }
+
+    if self.buffer.is_empty() {
+      return Ok(Status::NeedMoreData);
+    }
+
+    if block.buffer.len() < buffer_size {
+      block.buffer.resize(buffer_size, S::from_i8(0));
+    }
+
+    let result = {
+      let iresult = frame_parser_into(self.buffer.as_slice(), &info,
+                                      &mut self.scratch, &mut block.buffer);
+
+      from_iresult(&self.buffer, iresult)
+    };
+
+    match result {
+      Ok((consumed, _))             => {
+        let frame = &self.scratch.frame;
+
+        decode_frame(frame, &mut block.buffer);
+
+        self.buffer.consume(consumed);
+
+        self.byte_offset  += consumed as u64;
+        self.frame_number += 1;
+
+        block.header = frame.header;
+        block.offset = 0;
+
+        Ok(Status::Block)
+      }
+      Err(ErrorKind::Incomplete(_)) => Ok(Status::NeedMoreData),
+      Err(kind)                     => Err(self.decode_error(kind)),
+    }
+  }
+
+  // Parse as many metadata blocks as the bytes fed in so far allow.
+  fn next_metadata(&mut self) -> Result<(), DecodeError> {
+    while !self.is_metadata_done {
+      let result = {
+        let iresult = parser(self.buffer.as_slice(), &mut self.state);
+
+        from_iresult(&self.buffer, iresult)
+      };
+
+      match result {
+        Ok((consumed, block))         => {
+          self.buffer.consume(consumed);
+
+          self.byte_offset      += consumed as u64;
+          self.is_metadata_done  = block.is_last();
+
+          if let metadata::Data::StreamInfo(info) = block.data {
+            self.info    = Some(info);
+            self.scratch = Scratch::new(info.channels as usize,
+                                        info.max_block_size as usize);
+          } else {
+            self.metadata.push(block);
+          }
+        }
+        Err(ErrorKind::Incomplete(_)) => break,
+        Err(kind)                     => return Err(self.decode_error(kind)),
+      }
+    }
+
+    Ok(())
+  }
+
+  fn decode_error(&self, kind: ErrorKind) -> DecodeError {
+    DecodeError {
+      kind: kind,
+      frame_number: self.frame_number,
+      byte_offset: self.byte_offset,
+    }
+  }
+}
+
+impl Default for Decoder {
+  /// Constructs a `Decoder` that hasn't been fed any bytes, same as
+  /// `Decoder::new`.
+  fn default() -> Self {
+    Decoder::new()
+  }
+}
+
+/// An iterator over the blocks that can be decoded from the bytes fed into
+/// a `Decoder`.
+pub struct Blocks<'a, S>
+ where S: Sample {
+  decoder: &'a mut Decoder,
+  is_finished: bool,
+  phantom: PhantomData<S>,
+}
+
+impl<'a, S> Iterator for Blocks<'a, S>
+ where S: Sample {
+  type Item = Result<Block<S>, DecodeError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.is_finished {
+      return None;
+    }
+
+    let mut block = Block::new();
+
+    match self.decoder.next_block(&mut block) {
+      Ok(Status::Block)        => Some(Ok(block)),
+      Ok(Status::NeedMoreData) => {
+        self.is_finished = true;
+
+        None
+      }
+      Err(error)               => {
+        self.is_finished = true;
+
+        Some(Err(error))
+      }
+    }
+  }
+}
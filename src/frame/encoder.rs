@@ -0,0 +1,486 @@
+use frame::{ChannelAssignment, NumberType, Frame, Header};
+use subframe::{self, adjust_bits_per_sample, Subframe};
+use utility::{BitWriter, crc8, crc16};
+
+/// How the two channels of a stereo stream get decorrelated.
+///
+/// Besides coding both channels as they are, a frame can code one of them
+/// along with their difference, the side channel, or code their average,
+/// the mid channel, along with the side channel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StereoMode {
+  /// Code the left and right channels as they are.
+  Independent,
+  /// Pick the channel assignment from an estimate of the size of each
+  /// channel, so only two of them get encoded.
+  LooseMidSide,
+  /// Encode every channel and pick the channel assignment that results in
+  /// the smallest frame.
+  MidSide,
+}
+
+// Return the four bit block size code and, when the block size doesn't have
+// its own code, the number of bits used for the secondary block size.
+fn block_size_code(block_size: u32) -> (u32, usize) {
+  match block_size {
+    192                            => (0b0001, 0),
+    576 | 1152 | 2304 | 4608        => {
+      (0b0010 + (block_size / 576).trailing_zeros(), 0)
+    }
+    256 | 512 | 1024 | 2048 | 4096 |
+    8192 | 16384 | 32768           => {
+      (0b1000 + (block_size / 256).trailing_zeros(), 0)
+    }
+    _                              => {
+      if block_size <= 256 { (0b0110, 8) } else { (0b0111, 16) }
+    }
+  }
+}
+
+// Return the four bit sample rate code and, when the sample rate doesn't
+// have its own code, the number of bits used for the secondary sample rate.
+//
+// Sample rates that can't be written inside the frame header at all use
+// the code for getting the value from `StreamInfo`.
+fn sample_rate_code(sample_rate: u32) -> (u32, usize) {
+  match sample_rate {
+    88200  => (0b0001, 0),
+    176400 => (0b0010, 0),
+    192000 => (0b0011, 0),
+    8000   => (0b0100, 0),
+    16000  => (0b0101, 0),
+    22050  => (0b0110, 0),
+    24000  => (0b0111, 0),
+    32000  => (0b1000, 0),
+    44100  => (0b1001, 0),
+    48000  => (0b1010, 0),
+    96000  => (0b1011, 0),
+    _      => {
+      if sample_rate % 1000 == 0 && sample_rate <= 255000 {
+        (0b1100, 8)
+      } else if sample_rate % 10 == 0 && sample_rate <= 655350 {
+        (0b1110, 16)
+      } else if sample_rate <= 65535 {
+        (0b1101, 16)
+      } else {
+        (0b0000, 0)
+      }
+    }
+  }
+}
+
+// Return the three bit sample size code, sample sizes without their own
+// code use the code for getting the value from `StreamInfo`.
+fn sample_size_code(bits_per_sample: usize) -> u32 {
+  match bits_per_sample {
+    8  => 0b001,
+    12 => 0b010,
+    16 => 0b100,
+    20 => 0b101,
+    24 => 0b110,
+    32 => 0b111,
+    _  => 0b000,
+  }
+}
+
+// Return true when the frame header can hold both `sample_rate` and
+// `bits_per_sample` without referring to `StreamInfo`.
+pub fn is_header_complete(sample_rate: u32, bits_per_sample: usize) -> bool {
+  sample_rate_code(sample_rate).0 != 0b0000 &&
+  sample_size_code(bits_per_sample) != 0b000
+}
+
+// Writes a number with the same variable length scheme that UTF-8 uses,
+// extended up to seven bytes for 36 bit values.
+pub fn write_utf8(value: u64, writer: &mut BitWriter) {
+  if value < 0x80 {
+    writer.write_bits(value as u32, 8);
+
+    return;
+  }
+
+  let length = if value < 0x800 {
+    2
+  } else if value < 0x10000 {
+    3
+  } else if value < 0x200000 {
+    4
+  } else if value < 0x4000000 {
+    5
+  } else if value < 0x80000000 {
+    6
+  } else {
+    7
+  };
+
+  let continuation = length - 1;
+  let leading      = (0xff00 >> length) as u32 & 0xff;
+  let first        = (value >> (continuation * 6)) as u32;
+
+  writer.write_bits(leading | first, 8);
+
+  for i in (0..continuation).rev() {
+    let bits = ((value >> (i * 6)) & 0b00111111) as u32;
+
+    writer.write_bits(0b10000000 | bits, 8);
+  }
+}
+
+/// Writes a frame header, including the CRC-8.
+///
+/// The CRC-8 stored within `header` is ignored and gets calculated from the
+/// bytes that are written.
+pub fn write_header(header: &Header, writer: &mut BitWriter) {
+  let start = writer.as_slice().len();
+
+  let (block_code, block_bits)   = block_size_code(header.block_size);
+  let (sample_code, sample_bits) = sample_rate_code(header.sample_rate);
+  let channel_code               = match header.channel_assignment {
+    ChannelAssignment::Independent  => header.channels as u32 - 1,
+    ChannelAssignment::LeftSide     => 0b1000,
+    ChannelAssignment::RightSide    => 0b1001,
+    ChannelAssignment::MidpointSide => 0b1010,
+  };
+
+  let is_variable_block_size = match header.number {
+    NumberType::Frame(_)  => 0,
+    NumberType::Sample(_) => 1,
+  };
+
+  writer.write_bits(0b11111111111110, 14);
+  writer.write_bits(0, 1);
+  writer.write_bits(is_variable_block_size, 1);
+  writer.write_bits(block_code, 4);
+  writer.write_bits(sample_code, 4);
+  writer.write_bits(channel_code, 4);
+  writer.write_bits(sample_size_code(header.bits_per_sample), 3);
+  writer.write_bits(0, 1);
+
+  match header.number {
+    NumberType::Frame(number)  => write_utf8(number as u64, writer),
+    NumberType::Sample(number) => write_utf8(number, writer),
+  }
+
+  writer.write_bits(header.block_size - 1, block_bits);
+
+  match sample_code {
+    0b1100 => writer.write_bits(header.sample_rate / 1000, sample_bits),
+    0b1110 => writer.write_bits(header.sample_rate / 10, sample_bits),
+    _      => writer.write_bits(header.sample_rate, sample_bits),
+  }
+
+  let crc = crc8(&writer.as_slice()[start..]);
+
+  writer.write_bits(crc as u32, 8);
+}
+
+// Pad the frame to a byte boundary and write the CRC-16 of every byte
+// starting from `start`.
+fn write_footer(start: usize, writer: &mut BitWriter) {
+  writer.align();
+
+  let crc = crc16(&writer.as_slice()[start..]);
+
+  writer.write_bits(crc as u32, 16);
+}
+
+/// Writes a complete frame with subframes that are already encoded.
+pub fn write_frame(frame: &Frame, writer: &mut BitWriter) {
+  let start    = writer.as_slice().len();
+  let header   = &frame.header;
+  let channels = header.channels as usize;
+
+  write_header(header, writer);
+
+  for (channel, subframe) in frame.subframes[0..channels].iter()
+                                                         .enumerate() {
+    let bits_per_sample = adjust_bits_per_sample(header, channel);
+
+    subframe::write(subframe, bits_per_sample, writer);
+  }
+
+  write_footer(start, writer);
+}
+
+// Encodes the two channels of a stereo frame into subframes, returning the
+// channel assignment used along with the subframes in the order they get
+// written.
+fn encode_stereo(header: &Header, left: &[i32], right: &[i32],
+                 stereo: StereoMode, config: &subframe::Config)
+                 -> (ChannelAssignment, [Subframe; 2]) {
+  let mid: Vec<i32>  = left.iter().zip(right)
+                           .map(|(left, right)| (left + right) >> 1)
+                           .collect();
+  let side: Vec<i32> = left.iter().zip(right)
+                           .map(|(left, right)| left - right)
+                           .collect();
+
+  let channels            = [left, right, &mid[..], &side[..]];
+  let mut bits_per_sample = [header.bits_per_sample; 4];
+
+  bits_per_sample[3] += 1;
+
+  // Channels of each assignment, as indices into `channels`, in the same
+  // order as the subframes.
+  let assignments = [
+    (ChannelAssignment::Independent, [0, 1]),
+    (ChannelAssignment::LeftSide, [0, 3]),
+    (ChannelAssignment::RightSide, [3, 1]),
+    (ChannelAssignment::MidpointSide, [2, 3]),
+  ];
+
+  let mut subframes: [Option<Subframe>; 4] = [None, None, None, None];
+  let mut bits                             = [0; 4];
+
+  match stereo {
+    StereoMode::MidSide => {
+      let mut writer = BitWriter::new();
+
+      for (channel, samples) in channels.iter().enumerate() {
+        let subframe = subframe::encode(samples, bits_per_sample[channel],
+                                        config);
+
+        writer.clear();
+        subframe::write(&subframe, bits_per_sample[channel], &mut writer);
+
+        bits[channel]      = writer.bits_len() as u64;
+        subframes[channel] = Some(subframe);
+      }
+    }
+    _                   => {
+      for (channel, samples) in channels.iter().enumerate() {
+        bits[channel] = subframe::estimate_bits(samples);
+      }
+    }
+  }
+
+  let total    = |indices: &[usize; 2]| bits[indices[0]] + bits[indices[1]];
+  let mut best = assignments[0];
+
+  for assignment in &assignments[1..] {
+    if total(&assignment.1) < total(&best.1) {
+      best = *assignment;
+    }
+  }
+
+  let (channel_assignment, indices) = best;
+  let mut encode = |index: usize| {
+    subframes[index].take().unwrap_or_else(|| {
+      subframe::encode(channels[index], bits_per_sample[index], config)
+    })
+  };
+
+  (channel_assignment, [encode(indices[0]), encode(indices[1])])
+}
+
+/// Encodes a block of samples into a complete frame.
+///
+/// The `buffer` holds the samples for each channel one after the other,
+/// the same layout the decoder uses, and each subframe gets encoded with
+/// the settings in `config`.
+///
+/// Two channel frames get decorrelated according to `stereo`, replacing
+/// the channel assignment in `header`, in which case the channels in
+/// `buffer` are expected to be left and right. Otherwise the channels are
+/// expected to already match the channel assignment in `header`. Side
+/// channels of 32 bit samples don't fit inside of an `i32`, so those
+/// frames always use independent channels.
+pub fn encode(header: &Header, buffer: &[i32], stereo: StereoMode,
+              config: &subframe::Config, writer: &mut BitWriter) {
+  let start      = writer.as_slice().len();
+  let block_size = header.block_size as usize;
+  let channels   = header.channels as usize;
+
+  if channels == 2 && stereo != StereoMode::Independent &&
+     header.bits_per_sample < 32 {
+    let (left, right) = buffer[0..(2 * block_size)].split_at(block_size);
+    let (channel_assignment, subframes) = encode_stereo(header, left, right,
+                                                        stereo, config);
+    let mut header = *header;
+
+    header.channel_assignment = channel_assignment;
+
+    write_header(&header, writer);
+
+    for (channel, subframe) in subframes.iter().enumerate() {
+      let bits_per_sample = adjust_bits_per_sample(&header, channel);
+
+      subframe::write(subframe, bits_per_sample, writer);
+    }
+
+    return write_footer(start, writer);
+  }
+
+  write_header(header, writer);
+
+  for channel in 0..channels {
+    let samples         = &buffer[(channel * block_size)..
+                                  ((channel + 1) * block_size)];
+    let bits_per_sample = adjust_bits_per_sample(header, channel);
+    let subframe        = subframe::encode(samples, bits_per_sample,
+                                           config);
+
+    subframe::write(&subframe, bits_per_sample, writer);
+  }
+
+  write_footer(start, writer);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use frame::{frame_parser, ChannelAssignment, NumberType, Header};
+  use metadata::StreamInfo;
+  use stream::decode_frame;
+  use subframe;
+  use utility::BitWriter;
+
+  #[test]
+  fn test_block_size_code() {
+    assert_eq!(block_size_code(192), (0b0001, 0));
+    assert_eq!(block_size_code(4608), (0b0101, 0));
+    assert_eq!(block_size_code(4096), (0b1100, 0));
+    assert_eq!(block_size_code(75), (0b0110, 8));
+    assert_eq!(block_size_code(1000), (0b0111, 16));
+  }
+
+  #[test]
+  fn test_sample_rate_code() {
+    assert_eq!(sample_rate_code(44100), (0b1001, 0));
+    assert_eq!(sample_rate_code(26000), (0b1100, 8));
+    assert_eq!(sample_rate_code(41000), (0b1100, 8));
+    assert_eq!(sample_rate_code(4100), (0b1110, 16));
+    assert_eq!(sample_rate_code(1001), (0b1101, 16));
+    assert_eq!(sample_rate_code(700001), (0b0000, 0));
+  }
+
+  #[test]
+  fn test_write_utf8() {
+    let mut writer = BitWriter::new();
+
+    write_utf8(116, &mut writer);
+    assert_eq!(writer.as_slice(), &[0x74]);
+
+    writer.clear();
+    write_utf8(65536, &mut writer);
+    assert_eq!(writer.as_slice(), &[0xf0, 0x90, 0x80, 0x80]);
+
+    writer.clear();
+    write_utf8(68719476732, &mut writer);
+    assert_eq!(writer.as_slice(), &[0xfe, 0xbf, 0xbf, 0xbf, 0xbf, 0xbf,
+                                    0xbc]);
+  }
+
+  #[test]
+  fn test_write_header() {
+    let inputs  = [ Header {
+                      block_size: 4608,
+                      sample_rate: 192000,
+                      channels: 2,
+                      channel_assignment: ChannelAssignment::Independent,
+                      bits_per_sample: 24,
+                      number: NumberType::Frame(65536),
+                      crc: 0,
+                    }
+                  , Header {
+                      block_size: 4096,
+                      sample_rate: 32000,
+                      channels: 8,
+                      channel_assignment: ChannelAssignment::Independent,
+                      bits_per_sample: 8,
+                      number: NumberType::Frame(64),
+                      crc: 0,
+                    }
+                  ];
+    let results = [ &b"\xff\xf8\x53\x1c\xf0\x90\x80\x80\x2e"[..]
+                  , &b"\xff\xf8\xc8\x72\x40\x19"[..]
+                  ];
+
+    let mut writer = BitWriter::new();
+
+    write_header(&inputs[0], &mut writer);
+    assert_eq!(writer.as_slice(), results[0]);
+
+    writer.clear();
+    write_header(&inputs[1], &mut writer);
+    assert_eq!(writer.as_slice(), results[1]);
+  }
+
+  #[test]
+  fn test_encode_stereo() {
+    let mut info: StreamInfo = Default::default();
+
+    info.sample_rate     = 44100;
+    info.channels        = 2;
+    info.bits_per_sample = 16;
+    info.max_block_size  = 256;
+
+    // Right channel that is close to the left one, so both side channels
+    // are a lot smaller than either channel.
+    let left: Vec<i32>  = (0..256).map(|i| {
+      ((i as f64 * 0.05).sin() * 10000.0) as i32
+    }).collect();
+    let right: Vec<i32> = left.iter().enumerate()
+                              .map(|(i, sample)| sample + (i % 7) as i32 - 3)
+                              .collect();
+
+    let mut input = left.clone();
+
+    input.extend_from_slice(&right);
+
+    let mut header = Header {
+      block_size: 256,
+      sample_rate: 44100,
+      channels: 2,
+      channel_assignment: ChannelAssignment::Independent,
+      bits_per_sample: 16,
+      number: NumberType::Frame(0),
+      crc: 0,
+    };
+
+    let config     = subframe::Config::default();
+    let modes      = [StereoMode::Independent, StereoMode::LooseMidSide,
+                      StereoMode::MidSide];
+    let mut writer = BitWriter::new();
+    let mut sizes  = Vec::new();
+
+    for mode in modes.iter() {
+      let mut buffer = vec![0i64; 512];
+
+      writer.clear();
+      encode(&header, &input, *mode, &config, &mut writer);
+
+      let (_, frame) = frame_parser(writer.as_slice(), &info, &mut buffer)
+                         .unwrap();
+      let assignment = frame.header.channel_assignment;
+
+      decode_frame(&frame, &mut buffer);
+
+      assert_eq!(assignment == ChannelAssignment::Independent,
+                 *mode == StereoMode::Independent);
+      assert!(buffer.iter().zip(&input).all(|(a, b)| *a == *b as i64));
+
+      sizes.push(writer.as_slice().len());
+    }
+
+    assert!(sizes[1] < sizes[0]);
+    assert!(sizes[2] <= sizes[1]);
+
+    // The side channel of 32 bit samples would need 33 bits.
+    let input: Vec<i32> = input.iter().map(|sample| sample << 16).collect();
+
+    info.bits_per_sample   = 32;
+    header.bits_per_sample = 32;
+
+    let mut buffer = vec![0i64; 512];
+
+    writer.clear();
+    encode(&header, &input, StereoMode::MidSide, &config, &mut writer);
+
+    let (_, frame) = frame_parser(writer.as_slice(), &info, &mut buffer)
+                       .unwrap();
+
+    assert_eq!(frame.header.channel_assignment,
+               ChannelAssignment::Independent);
+  }
+}
@@ -1,6 +1,8 @@
 mod types;
 mod parser;
 mod decoder;
+mod encoder;
+mod subset;
 
 pub use self::types::{
   MAX_CHANNELS,
@@ -8,6 +10,14 @@ pub use self::types::{
   Frame,
   Header, Footer,
 };
+pub(crate) use self::types::Scratch;
 
-pub use self::parser::frame_parser;
-pub use self::decoder::decode;
+pub use self::parser::{frame_parser, frame_sync};
+pub(crate) use self::parser::{
+  frame_parser_into, frame_parser_unchecked, frame_span,
+};
+pub(crate) use self::decoder::decode;
+pub use self::encoder::StereoMode;
+pub use self::subset::SubsetRule;
+pub(crate) use self::encoder::{encode, is_header_complete};
+pub(crate) use self::subset::{check_subset, subset_limits};
@@ -2,21 +2,25 @@ use nom::{
   self,
   be_u8, be_u16,
   IResult,
-  Err,
+  Err, Needed,
 };
 
-use std::mem;
-
 use frame::{
-  MAX_CHANNELS,
   ChannelAssignment, NumberType,
-  Frame,
+  Frame, Scratch,
   Header, Footer,
 };
-use subframe::{subframe_parser, Subframe};
+use subframe::{self, subframe_parser_into, Subframe};
 
 use metadata::StreamInfo;
-use utility::{ErrorKind, Sample, crc8, crc16, to_u32, power_of_two};
+use utility::{
+  BitReader, ErrorKind, Sample,
+  crc8, crc16, to_u32, power_of_two, to_iresult,
+};
+
+// Largest possible frame header, in bytes, which has a seven byte sample
+// number along with a two byte block size and sample rate.
+const MAX_HEADER_SIZE: usize = 16;
 
 /// Parses an audio frame
 pub fn frame_parser<'a, S>(input: &'a [u8],
@@ -24,44 +28,28 @@ pub fn frame_parser<'a, S>(input: &'a [u8],
                            buffer: &mut [S])
                            -> IResult<&'a [u8], Frame, ErrorKind>
  where S: Sample {
-  // Unsafe way to initialize subframe data, but I would rather do this
-  // than have `Subframe` derive `Copy` to do something like:
-  //
-  // ```
-  // let mut subframe = [Subframe {
-  //                       data: subframe::Constant(0),
-  //                       wasted_bits: 0,
-  //                     }; MAX_CHANNELS];
-  // ```
-  let mut subframes: [Subframe; MAX_CHANNELS] = unsafe { mem::zeroed() };
-  let mut channel = 0;
+  let mut scratch = Scratch::new(0, 0);
 
-  let result = chain!(input,
-    frame_header: apply!(header, stream_info) ~
-    bits!(
-      count_slice!(
-        apply!(subframe_parser, &frame_header, &mut channel, buffer),
-        &mut subframes[0..(frame_header.channels as usize)]
-      )
-    ) ~
-    frame_footer: footer,
-    || {
-      Frame {
-        header: frame_header,
-        subframes: subframes,
-        footer: frame_footer,
-      }
-    }
-  );
+  frame_parser_into(input, stream_info, &mut scratch, buffer)
+    .map(|_| scratch.frame)
+}
 
-  match result {
-    IResult::Done(i, frame)   => {
+// Parses an audio frame the same way as `frame_parser`, into the frame of
+// `scratch` so its buffers get reused.
+pub fn frame_parser_into<'a, S>(input: &'a [u8],
+                                stream_info: &StreamInfo,
+                                scratch: &mut Scratch,
+                                buffer: &mut [S])
+                                -> IResult<&'a [u8], (), ErrorKind>
+ where S: Sample {
+  match frame_parser_unchecked(input, stream_info, scratch, buffer) {
+    IResult::Done(i, _)       => {
       // All frame bytes before the crc-16
       let end         = (input.len() - i.len()) - 2;
-      let Footer(crc) = frame.footer;
+      let Footer(crc) = scratch.frame.footer;
 
       if crc16(&input[0..end]) == crc {
-        IResult::Done(i, frame)
+        IResult::Done(i, ())
       } else {
         IResult::Error(Err::Position(
           nom::ErrorKind::Custom(ErrorKind::InvalidCRC16), input))
@@ -72,6 +60,71 @@ pub fn frame_parser<'a, S>(input: &'a [u8],
   }
 }
 
+// Parses an audio frame the same way as `frame_parser_into`, without
+// checking the CRC-16 in the footer.
+pub fn frame_parser_unchecked<'a, S>(input: &'a [u8],
+                                     stream_info: &StreamInfo,
+                                     scratch: &mut Scratch,
+                                     buffer: &mut [S])
+                                     -> IResult<&'a [u8], (), ErrorKind>
+ where S: Sample {
+  let frame   = &mut scratch.frame;
+  let buffers = &mut scratch.buffers;
+
+  let (i, frame_header) = try_parser!(header(input, stream_info));
+
+  let channels   = frame_header.channels as usize;
+  let block_size = frame_header.block_size as usize;
+
+  // Every subframe gets decoded into its own part of `buffer`.
+  if channels > stream_info.channels as usize ||
+     block_size > buffer.len() / channels {
+    return IResult::Error(Err::Position(
+      nom::ErrorKind::Custom(ErrorKind::InvalidFrameSize), input));
+  }
+
+  // The subframes aren't aligned to a byte boundary, only the footer after
+  // them is.
+  let iresult = {
+    let mut reader = BitReader::new(i);
+    let result     = subframes(&mut reader, &frame_header,
+                               &mut frame.subframes, buffers, buffer);
+
+    reader.align();
+
+    to_iresult(input, reader.remaining().0, result)
+  };
+
+  let (i, _) = try_parser!(iresult);
+
+  let (i, frame_footer) = try_parser!(footer(i));
+
+  frame.header = frame_header;
+  frame.footer = frame_footer;
+
+  IResult::Done(i, ())
+}
+
+// Parses the subframe of each channel, with the buffers of the same
+// channel.
+fn subframes<S>(reader: &mut BitReader,
+                frame_header: &Header,
+                subframes: &mut [Subframe],
+                buffers: &mut [subframe::Buffers],
+                buffer: &mut [S])
+                -> Result<(), ErrorKind>
+ where S: Sample {
+  let channels = frame_header.channels as usize;
+
+  for channel in 0..channels {
+    try!(subframe_parser_into(reader, frame_header, channel,
+                              &mut subframes[channel],
+                              &mut buffers[channel], buffer));
+  }
+
+  Ok(())
+}
+
 // Parses the first two bytes of a frame header. There are two things that
 // need to be valid inside these two bytes, the 14 bit sync code and the
 // following bit must be zero. The last bit is whether or not the block size
@@ -119,9 +172,8 @@ pub fn block_sample(input: &[u8]) -> IResult<&[u8], (u8, u8), ErrorKind> {
 
 // Parses the fourth byte of a frame header. There are three values that
 // need validation within the byte. First is the channel assignment bits
-// which can't be more than 0b1010. Second is the sample size bits that have
-// two values it can not equal, 0b011 and 0b111. Last is the final bit must
-// be a zero.
+// which can't be more than 0b1010. Second is the sample size bits that
+// can't be the reserved value 0b011. Last is the final bit must be a zero.
 pub fn channel_bits(input: &[u8])
                     -> IResult<&[u8], (ChannelAssignment, u8, u8),
                                ErrorKind> {
@@ -144,7 +196,7 @@ pub fn channel_bits(input: &[u8])
   };
   let size_byte = (byte >> 1) & 0b0111;
   let is_valid  = channel_byte < 0b1011 &&
-                  (size_byte != 0b0011 && size_byte != 0b0111) &&
+                  size_byte != 0b0011 &&
                   (byte & 0b01) == 0;
 
   if is_valid {
@@ -235,6 +287,59 @@ pub fn secondary_sample_rate(input: &[u8], sample_byte: u8)
   }
 }
 
+// Block size in samples for the four bits from the third byte of the frame
+// header, `None` when it's the reserved value.
+fn block_size(block_byte: u8, alt_block_size: Option<u32>) -> Option<u32> {
+  match block_byte {
+    0b0001          => Some(192),
+    0b0010...0b0101 => Some(576 * power_of_two(block_byte as u32 - 2)),
+    0b0110 | 0b0111 => alt_block_size.map(|size| size + 1),
+    0b1000...0b1111 => Some(256 * power_of_two(block_byte as u32 - 8)),
+    _               => None,
+  }
+}
+
+// Sample rate in hertz for the four bits from the third byte of the frame
+// header, `None` when it's the invalid value.
+fn sample_rate(sample_byte: u8, alt_sample_rate: Option<u32>,
+               stream_info: &StreamInfo)
+               -> Option<u32> {
+  match sample_byte {
+    0b0000 => Some(stream_info.sample_rate),
+    0b0001 => Some(88200),
+    0b0010 => Some(176400),
+    0b0011 => Some(192000),
+    0b0100 => Some(8000),
+    0b0101 => Some(16000),
+    0b0110 => Some(22050),
+    0b0111 => Some(24000),
+    0b1000 => Some(32000),
+    0b1001 => Some(44100),
+    0b1010 => Some(48000),
+    0b1011 => Some(96000),
+    0b1100 => alt_sample_rate.map(|rate| rate * 1000),
+    0b1101 => alt_sample_rate,
+    0b1110 => alt_sample_rate.map(|rate| rate * 10),
+    _      => None,
+  }
+}
+
+// Bits per sample for the three bits from the fourth byte of the frame
+// header, `None` when it's the reserved value.
+fn bits_per_sample(size_byte: u8, stream_info: &StreamInfo)
+                   -> Option<usize> {
+  match size_byte {
+    0b0000 => Some(stream_info.bits_per_sample as usize),
+    0b0001 => Some(8),
+    0b0010 => Some(12),
+    0b0100 => Some(16),
+    0b0101 => Some(20),
+    0b0110 => Some(24),
+    0b0111 => Some(32),
+    _      => None,
+  }
+}
+
 #[inline]
 pub fn header<'a>(input: &'a [u8], stream_info: &StreamInfo)
                   -> IResult<&'a [u8], Header, ErrorKind> {
@@ -252,57 +357,36 @@ pub fn header<'a>(input: &'a [u8], stream_info: &StreamInfo)
       let (block_byte, sample_byte)                 = tuple0;
       let (channel_assignment, channels, size_byte) = tuple1;
 
-      let block_size = match block_byte {
-        0b0001          => 192,
-        0b0010...0b0101 => 576 * power_of_two(block_byte as u32 - 2),
-        0b0110 | 0b0111 => alt_block_size.unwrap() + 1,
-        0b1000...0b1111 => 256 * power_of_two(block_byte as u32 - 8),
-        _               => unreachable!(),
-      };
-
-      let sample_rate = match sample_byte {
-        0b0000 => stream_info.sample_rate,
-        0b0001 => 88200,
-        0b0010 => 176400,
-        0b0011 => 192000,
-        0b0100 => 8000,
-        0b0101 => 16000,
-        0b0110 => 22050,
-        0b0111 => 24000,
-        0b1000 => 32000,
-        0b1001 => 44100,
-        0b1010 => 48000,
-        0b1011 => 96000,
-        0b1100 => alt_sample_rate.unwrap() * 1000,
-        0b1101 => alt_sample_rate.unwrap(),
-        0b1110 => alt_sample_rate.unwrap() * 10,
-        _      => unreachable!(),
-      };
-
-      let bits_per_sample = match size_byte {
-        0b0000 => stream_info.bits_per_sample as usize,
-        0b0001 => 8,
-        0b0010 => 12,
-        0b0100 => 16,
-        0b0101 => 20,
-        0b0110 => 24,
-        _      => unreachable!(),
-      };
-
-      Header {
-        block_size: block_size,
-        sample_rate: sample_rate,
-        channels: channels,
-        channel_assignment: channel_assignment,
-        bits_per_sample: bits_per_sample,
-        number: number,
-        crc: crc,
+      let block_size      = block_size(block_byte, alt_block_size);
+      let sample_rate     = sample_rate(sample_byte, alt_sample_rate,
+                                        stream_info);
+      let bits_per_sample = bits_per_sample(size_byte, stream_info);
+
+      // The reserved values already fail to parse, this only keeps a
+      // header from ever being built out of one.
+      match (block_size, sample_rate, bits_per_sample) {
+        (Some(block_size), Some(sample_rate), Some(bits_per_sample)) => {
+          Ok(Header {
+            block_size: block_size,
+            sample_rate: sample_rate,
+            channels: channels,
+            channel_assignment: channel_assignment,
+            bits_per_sample: bits_per_sample,
+            number: number,
+            crc: crc,
+          })
+        }
+        (_, _, None) => Err(ErrorKind::InvalidChannelBits),
+        _            => Err(ErrorKind::InvalidBlockSample),
       }
     }
   );
 
   match result {
-    IResult::Done(i, frame_header) => {
+    IResult::Done(_, Err(kind))        => {
+      IResult::Error(Err::Position(nom::ErrorKind::Custom(kind), input))
+    }
+    IResult::Done(i, Ok(frame_header)) => {
       // All header bytes before the crc-8
       let end = (input.len() - i.len()) - 1;
 
@@ -313,9 +397,89 @@ pub fn header<'a>(input: &'a [u8], stream_info: &StreamInfo)
           nom::ErrorKind::Custom(ErrorKind::InvalidCRC8), input))
       }
     }
-    IResult::Error(error)          => IResult::Error(error),
-    IResult::Incomplete(need)      => IResult::Incomplete(need),
+    IResult::Error(error)              => IResult::Error(error),
+    IResult::Incomplete(need)          => IResult::Incomplete(need),
+  }
+}
+
+/// Scans for the next frame header, validated by its CRC-8.
+///
+/// Every byte before the header gets consumed, while the header itself is
+/// left so the frame can be parsed as usual. When there isn't a header
+/// within `input` the bytes that can't be the start of one are consumed and
+/// `None` is returned.
+pub fn frame_sync<'a>(input: &'a [u8], stream_info: &StreamInfo)
+                      -> IResult<&'a [u8], Option<Header>, ErrorKind> {
+  let length = input.len();
+
+  if length < 2 {
+    return IResult::Incomplete(Needed::Size(2));
+  }
+
+  for i in 0..(length - 1) {
+    if input[i] != 0b11111111 || (input[i + 1] >> 1) != 0b1111100 {
+      continue;
+    }
+
+    match header(&input[i..], stream_info) {
+      IResult::Done(_, frame_header) => {
+        return IResult::Done(&input[i..], Some(frame_header));
+      }
+      IResult::Incomplete(_)         => {
+        // Drop what was scanned so far, unless there is nothing to drop.
+        return if i > 0 {
+          IResult::Done(&input[i..], None)
+        } else {
+          IResult::Incomplete(Needed::Size(length + MAX_HEADER_SIZE))
+        };
+      }
+      IResult::Error(_)              => continue,
+    }
+  }
+
+  // The last byte could still be the start of a sync code.
+  if input[length - 1] == 0b11111111 {
+    IResult::Done(&input[(length - 1)..], None)
+  } else {
+    IResult::Done(&input[length..], None)
+  }
+}
+
+/// Splits off the bytes of the frame at the start of `input` without
+/// parsing the frame itself.
+///
+/// The frame ends where the next valid frame header, that continues the
+/// numbering of this one, begins. When there isn't one within `input`,
+/// `Incomplete` is returned, which at the end of the stream means that the
+/// rest of `input` is the last frame.
+pub fn frame_span<'a>(input: &'a [u8], stream_info: &StreamInfo)
+                      -> IResult<&'a [u8], &'a [u8], ErrorKind> {
+  let (_, frame_header) = try_parser!(header(input, stream_info));
+
+  let length = input.len();
+  let next   = frame_header.first_sample(stream_info) +
+               frame_header.block_size as u64;
+
+  // A frame is always longer than the two bytes of its sync code.
+  for i in 2..(length - 1) {
+    if input[i] != 0b11111111 || (input[i + 1] >> 1) != 0b1111100 {
+      continue;
+    }
+
+    match header(&input[i..], stream_info) {
+      IResult::Done(_, next_header)  => {
+        if next_header.first_sample(stream_info) == next {
+          return IResult::Done(&input[i..], &input[0..i]);
+        }
+      }
+      IResult::Incomplete(_)         => {
+        return IResult::Incomplete(Needed::Size(length + MAX_HEADER_SIZE));
+      }
+      IResult::Error(_)              => continue,
+    }
   }
+
+  IResult::Incomplete(Needed::Unknown)
 }
 
 pub fn footer(input: &[u8]) -> IResult<&[u8], Footer, ErrorKind> {
@@ -332,7 +496,7 @@ mod tests {
   use metadata::StreamInfo;
   use utility::ErrorKind;
 
-  use nom::{self, IResult, Err};
+  use nom::{self, IResult, Err, Needed};
 
   fn error<O>(input: &[u8], kind: ErrorKind) -> IResult<&[u8], O, ErrorKind> {
     IResult::Error(Err::Position(nom::ErrorKind::Custom(kind), input))
@@ -364,13 +528,17 @@ mod tests {
 
   #[test]
   fn test_channel_bits() {
-    let inputs  = [b"\x58", b"\x80", b"\xac", b"\xf2", b"\xae", b"\x91"];
+    let inputs  = [ b"\x58", b"\x80", b"\xac", b"\xae"
+                  , b"\xf2", b"\x16", b"\x91"
+                  ];
     let slice   = &[][..];
     let results = [ IResult::Done(slice, (ChannelAssignment::Independent,
                                           6, 4))
                   , IResult::Done(slice, (ChannelAssignment::LeftSide, 2, 0))
                   , IResult::Done(slice, (ChannelAssignment::MidpointSide,
                                           2, 6))
+                  , IResult::Done(slice, (ChannelAssignment::MidpointSide,
+                                          2, 7))
                   ];
 
     let kind = ErrorKind::InvalidChannelBits;
@@ -378,9 +546,10 @@ mod tests {
     assert_eq!(channel_bits(inputs[0]), results[0]);
     assert_eq!(channel_bits(inputs[1]), results[1]);
     assert_eq!(channel_bits(inputs[2]), results[2]);
-    assert_eq!(channel_bits(inputs[3]), error(inputs[3], kind));
+    assert_eq!(channel_bits(inputs[3]), results[3]);
     assert_eq!(channel_bits(inputs[4]), error(inputs[4], kind));
     assert_eq!(channel_bits(inputs[5]), error(inputs[5], kind));
+    assert_eq!(channel_bits(inputs[6]), error(inputs[6], kind));
   }
 
   #[test]
@@ -456,6 +625,7 @@ mod tests {
                    , &b"\xff\xf9\x7c\xa0\xfe\xbf\xbf\xbf\xbf\xbf\xbc\x01\xff\
                         \x01\x88"[..]
                    , &b"\xff\xf8\xc8\x72\x40\x19"[..]
+                   , &b"\xff\xf8\xc9\x1e\x00\xbc"[..]
                    ];
     let mut info: StreamInfo = Default::default();
     let results  = [ IResult::Done(&[][..], Header {
@@ -485,6 +655,15 @@ mod tests {
                        number: NumberType::Frame(64),
                        crc: 0x19,
                      })
+                   , IResult::Done(&[][..], Header {
+                       block_size: 4096,
+                       sample_rate: 44100,
+                       channels: 2,
+                       channel_assignment: ChannelAssignment::Independent,
+                       bits_per_sample: 32,
+                       number: NumberType::Frame(0),
+                       crc: 0xbc,
+                     })
                   ];
 
     info.bits_per_sample = 16;
@@ -492,6 +671,7 @@ mod tests {
     assert_eq!(header(inputs[0], &info), results[0]);
     assert_eq!(header(inputs[1], &info), results[1]);
     assert_eq!(header(inputs[2], &info), results[2]);
+    assert_eq!(header(inputs[3], &info), results[3]);
   }
 
   #[test]
@@ -501,4 +681,75 @@ mod tests {
 
     assert_eq!(footer(input), result);
   }
+
+  #[test]
+  fn test_frame_sync() {
+    let inputs  = [ &b"\x00\xff\xf8\x53\x1c\xf0\x90\x80\x80\x2e\x00"[..]
+                  , &b"\xff\xf8\x53\x1c\xf0\x90\x80\x80\x2f\x00\xff"[..]
+                  , &b"\x12\x34\xff\xf8\xc8"[..]
+                  , &b"\xff"[..]
+                  ];
+    let header  = Header {
+                    block_size: 4608,
+                    sample_rate: 192000,
+                    channels: 2,
+                    channel_assignment: ChannelAssignment::Independent,
+                    bits_per_sample: 24,
+                    number: NumberType::Frame(65536),
+                    crc: 0x2e,
+                  };
+    let results = [ IResult::Done(&inputs[0][1..], Some(header))
+                  , IResult::Done(&inputs[1][10..], None)
+                  , IResult::Done(&inputs[2][2..], None)
+                  , IResult::Incomplete(Needed::Size(2))
+                  ];
+
+    let info: StreamInfo = Default::default();
+
+    assert_eq!(frame_sync(inputs[0], &info), results[0]);
+    assert_eq!(frame_sync(inputs[1], &info), results[1]);
+    assert_eq!(frame_sync(inputs[2], &info), results[2]);
+    assert_eq!(frame_sync(inputs[3], &info), results[3]);
+  }
+
+  #[test]
+  fn test_frame_span() {
+    let header = |number| {
+      let mut bytes = Vec::new();
+
+      Header {
+        block_size: 192,
+        sample_rate: 44100,
+        channels: 2,
+        channel_assignment: ChannelAssignment::Independent,
+        bits_per_sample: 16,
+        number: NumberType::Frame(number),
+        crc: 0,
+      }.to_bytes(&mut bytes).unwrap();
+
+      bytes
+    };
+
+    let mut info: StreamInfo = Default::default();
+    let mut bytes            = header(0);
+
+    info.max_block_size = 192;
+
+    // A valid header that doesn't continue the numbering isn't the end.
+    bytes.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+    bytes.extend(header(5));
+    bytes.extend_from_slice(&[0x00, 0x00, 0x00]);
+
+    let end = bytes.len();
+
+    bytes.extend(header(1));
+
+    assert_eq!(frame_span(&bytes, &info),
+               IResult::Done(&bytes[end..], &bytes[0..end]));
+    assert_eq!(frame_span(&bytes[0..(end + 2)], &info),
+               IResult::Incomplete(Needed::Size(end + 2 + MAX_HEADER_SIZE)));
+    assert_eq!(frame_span(&bytes[0..end], &info),
+               IResult::Incomplete(Needed::Unknown));
+    assert!(frame_span(&bytes[1..], &info).is_err());
+  }
 }
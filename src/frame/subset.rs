@@ -0,0 +1,173 @@
+use frame::Frame;
+use subframe::{self, Data};
+
+// Limits of the streamable subset, the part of the format that a decoder
+// can start playing from any frame, without `StreamInfo`. Streams with a
+// sample rate of 48kHz or less have lower limits on the block size and
+// `LPC` order.
+pub const MAX_BLOCK_SIZE: u32        = 16384;
+pub const MAX_BLOCK_SIZE_48KHZ: u32  = 4608;
+pub const MAX_LPC_ORDER_48KHZ: usize = 12;
+pub const MAX_PARTITION_ORDER: u32   = 8;
+
+/// Rule of the streamable subset that a frame breaks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubsetRule {
+  /// The frame header refers to `StreamInfo` for the sample rate.
+  SampleRate,
+  /// The frame header refers to `StreamInfo` for the bits per sample.
+  BitsPerSample,
+  /// The block size is above 16384 samples, or above 4608 samples for a
+  /// sample rate of 48kHz or less.
+  BlockSize(u32),
+  /// The `LPC` subframe of a channel has an order above 12, with a sample
+  /// rate of 48kHz or less.
+  LPCOrder {
+    /// Channel of the subframe.
+    channel: usize,
+    /// Order of the predictor.
+    order: u8,
+  },
+  /// The residual of a channel has a Rice partition order above 8.
+  PartitionOrder {
+    /// Channel of the subframe.
+    channel: usize,
+    /// Partition order of the residual.
+    order: u32,
+  },
+}
+
+// Largest block size, `LPC` order and Rice partition order the subset
+// allows at `sample_rate`.
+pub fn subset_limits(sample_rate: u32) -> (u32, usize, u32) {
+  if sample_rate <= 48000 {
+    (MAX_BLOCK_SIZE_48KHZ, MAX_LPC_ORDER_48KHZ, MAX_PARTITION_ORDER)
+  } else {
+    (MAX_BLOCK_SIZE, subframe::MAX_LPC_ORDER, MAX_PARTITION_ORDER)
+  }
+}
+
+// Checks `frame`, along with the `bytes` it was parsed from, against every
+// rule of the streamable subset, calling `report` for each one it breaks.
+pub fn check_subset<F>(bytes: &[u8], frame: &Frame, mut report: F)
+ where F: FnMut(SubsetRule) {
+  let header   = &frame.header;
+  let channels = header.channels as usize;
+
+  let (max_block_size, max_lpc_order, max_partition_order) =
+    subset_limits(header.sample_rate);
+
+  // Codes of zero leave the value up to `StreamInfo`.
+  if bytes[2] & 0x0f == 0 {
+    report(SubsetRule::SampleRate);
+  }
+
+  if (bytes[3] >> 1) & 0b0111 == 0 {
+    report(SubsetRule::BitsPerSample);
+  }
+
+  if header.block_size > max_block_size {
+    report(SubsetRule::BlockSize(header.block_size));
+  }
+
+  for (channel, subframe) in frame.subframes[0..channels].iter()
+                                                         .enumerate() {
+    let method = match subframe.data {
+      Data::Fixed(ref fixed) => &fixed.entropy_coding_method,
+      Data::LPC(ref lpc)     => {
+        if lpc.order as usize > max_lpc_order {
+          report(SubsetRule::LPCOrder {
+            channel: channel,
+            order: lpc.order,
+          });
+        }
+
+        &lpc.entropy_coding_method
+      }
+      _                      => continue,
+    };
+
+    if method.data.order > max_partition_order {
+      report(SubsetRule::PartitionOrder {
+        channel: channel,
+        order: method.data.order,
+      });
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use frame::{ChannelAssignment, NumberType, Frame, Header};
+  use frame::encoder::write_header;
+  use subframe::{
+    Data, LPC, CodingMethod, EntropyCodingMethod, PartitionedRice,
+    PartitionedRiceContents, MAX_LPC_ORDER,
+  };
+  use utility::BitWriter;
+
+  fn violations(frame: &Frame) -> Vec<SubsetRule> {
+    let mut writer = BitWriter::new();
+    let mut rules  = Vec::new();
+
+    write_header(&frame.header, &mut writer);
+    check_subset(writer.as_slice(), frame, |rule| rules.push(rule));
+
+    rules
+  }
+
+  #[test]
+  fn test_check_subset() {
+    let mut frame = Frame::default();
+
+    frame.header = Header {
+      block_size: 4608,
+      sample_rate: 44100,
+      channels: 2,
+      channel_assignment: ChannelAssignment::Independent,
+      bits_per_sample: 16,
+      number: NumberType::Frame(0),
+      crc: 0,
+    };
+
+    assert_eq!(violations(&frame), vec![]);
+
+    frame.header.block_size = 8192;
+    frame.subframes[1].data = Data::LPC(LPC {
+      entropy_coding_method: EntropyCodingMethod {
+        method_type: CodingMethod::PartitionedRice,
+        data: PartitionedRice {
+          order: 9,
+          contents: PartitionedRiceContents::new(512),
+        },
+      },
+      order: 16,
+      qlp_coeff_precision: 12,
+      quantization_level: 10,
+      qlp_coefficients: [0; MAX_LPC_ORDER],
+      warmup: [0; MAX_LPC_ORDER],
+      residual: Vec::new(),
+    });
+
+    assert_eq!(violations(&frame), vec![
+      SubsetRule::BlockSize(8192),
+      SubsetRule::LPCOrder { channel: 1, order: 16 },
+      SubsetRule::PartitionOrder { channel: 1, order: 9 },
+    ]);
+
+    // Higher sample rates allow larger blocks and orders, but the header
+    // has to hold the values itself.
+    frame.header.sample_rate     = 700001;
+    frame.header.bits_per_sample = 17;
+
+    assert_eq!(violations(&frame), vec![
+      SubsetRule::SampleRate,
+      SubsetRule::BitsPerSample,
+      SubsetRule::PartitionOrder { channel: 1, order: 9 },
+    ]);
+
+    assert_eq!(subset_limits(48000), (4608, 12, 8));
+    assert_eq!(subset_limits(96000), (16384, 32, 8));
+  }
+}
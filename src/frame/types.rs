@@ -1,4 +1,9 @@
-use subframe::Subframe;
+use metadata::StreamInfo;
+use subframe::{self, Subframe};
+use frame::encoder;
+use utility::{BitWriter, WriteExtension};
+
+use std::io;
 
 /// Maximum number of channels supported in the FLAC format.
 pub const MAX_CHANNELS: usize = 8;
@@ -13,6 +18,59 @@ pub struct Frame {
   pub footer: Footer,
 }
 
+impl Frame {
+  /// Writes the entire frame, header, subframes, and footer.
+  ///
+  /// Both the CRC-8 of the header and the CRC-16 of the footer are
+  /// calculated from the bytes being written, so `header.crc` and `footer`
+  /// are ignored. For a frame returned by the parser the result is the
+  /// same bytes the frame was parsed from.
+  pub fn to_bytes<Write: io::Write>(&self, buffer: &mut Write)
+                                    -> io::Result<()> {
+    let mut writer = BitWriter::new();
+
+    encoder::write_frame(self, &mut writer);
+
+    buffer.write_all(writer.as_slice())
+  }
+}
+
+impl Default for Frame {
+  /// Constructs an empty frame, with a constant subframe of zero for every
+  /// channel, which doesn't allocate.
+  fn default() -> Self {
+    Frame {
+      header: Header::default(),
+      subframes: Default::default(),
+      footer: Footer(0),
+    }
+  }
+}
+
+// A frame that gets parsed into over and over, along with the buffers for
+// the residual and Rice parameters of each channel. Once they are large
+// enough for the biggest frame, parsing doesn't allocate anything.
+pub struct Scratch {
+  pub frame: Frame,
+  pub buffers: [subframe::Buffers; MAX_CHANNELS],
+}
+
+impl Scratch {
+  // Reserve room up front for `channels` subframes of `max_block_size`.
+  pub fn new(channels: usize, max_block_size: usize) -> Scratch {
+    let mut buffers: [subframe::Buffers; MAX_CHANNELS] = Default::default();
+
+    for buffer in buffers.iter_mut().take(channels) {
+      *buffer = subframe::Buffers::with_capacity(max_block_size);
+    }
+
+    Scratch {
+      frame: Frame::default(),
+      buffers: buffers,
+    }
+  }
+}
+
 /// Channel assignment order.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ChannelAssignment {
@@ -36,7 +94,7 @@ pub enum NumberType {
 }
 
 /// Information regarding the current audio frame.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Header {
   /// Number of samples per subframe.
   pub block_size: u32,
@@ -54,9 +112,59 @@ pub struct Header {
   pub crc: u8,
 }
 
+impl Default for Header {
+  fn default() -> Self {
+    Header {
+      block_size: 0,
+      sample_rate: 0,
+      channels: 0,
+      channel_assignment: ChannelAssignment::Independent,
+      bits_per_sample: 0,
+      number: NumberType::Frame(0),
+      crc: 0,
+    }
+  }
+}
+
+impl Header {
+  /// Returns the number of the first sample within the frame.
+  ///
+  /// Frame numbers get multiplied by the maximum block size of
+  /// `stream_info`, which is the block size of every frame but the last
+  /// when the blocking strategy is fixed.
+  pub fn first_sample(&self, stream_info: &StreamInfo) -> u64 {
+    match self.number {
+      NumberType::Frame(number)  => {
+        number as u64 * stream_info.max_block_size as u64
+      }
+      NumberType::Sample(number) => number,
+    }
+  }
+
+  /// Writes the frame header, including the CRC-8.
+  ///
+  /// The CRC-8 is calculated from the bytes being written rather than
+  /// using `crc`.
+  pub fn to_bytes<Write: io::Write>(&self, buffer: &mut Write)
+                                    -> io::Result<()> {
+    let mut writer = BitWriter::new();
+
+    encoder::write_header(self, &mut writer);
+
+    buffer.write_all(writer.as_slice())
+  }
+}
+
 /// End of the audio frame.
 ///
 /// Contains a value that represents the CRC-16 of everything inside the
 /// frame before the footer.
 #[derive(Debug, PartialEq, Eq)]
 pub struct Footer(pub u16);
+
+impl Footer {
+  pub fn to_bytes<Write: io::Write>(&self, buffer: &mut Write)
+                                    -> io::Result<()> {
+    buffer.write_be_u16(self.0)
+  }
+}
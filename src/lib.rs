@@ -32,15 +32,25 @@ extern crate nom;
 
 #[macro_use]
 mod utility;
-mod frame;
-mod subframe;
+pub mod frame;
+pub mod subframe;
 pub mod metadata;
+pub mod ogg;
 pub mod stream;
+pub mod decoder;
+pub mod writer;
 
 pub use metadata::Metadata;
-pub use stream::{Stream, StreamBuffer, StreamReader};
+pub use stream::{
+  Stream, StreamBuffer, StreamReader, OggStreamReader,
+  Block, Concealment, DecodeError, ParallelBlocks, SkippedRegion,
+  SubsetViolation, Verification,
+};
+pub use decoder::Decoder;
+pub use writer::{EncoderConfig, StreamWriter};
+pub use ogg::{OggReader, OggWriter};
 pub use utility::{
   Sample, SampleSize,
-  StreamProducer, ReadStream, ByteStream,
+  StreamProducer, SeekableProducer, ReadStream, ByteStream,
   ErrorKind
 };
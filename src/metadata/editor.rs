@@ -0,0 +1,430 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::mem;
+use std::process;
+
+use utility::{ErrorKind, ReadStream, many_metadata};
+
+use metadata::{Metadata, Data, StreamInfo};
+
+// Largest length a metadata block can have, the length field in the block
+// header is only 24 bits.
+const MAX_BLOCK_LENGTH: usize = 0xffffff;
+
+// Bytes of the "fLaC" marker before the first metadata block.
+const MARKER_LENGTH: u64 = 4;
+
+#[inline]
+fn to_io_error(error: io::Error) -> ErrorKind {
+  ErrorKind::IO(error.kind())
+}
+
+/// Loads, edits, and saves the metadata blocks of a FLAC file.
+///
+/// Every block, besides `StreamInfo`, is available in file order and can
+/// be added, removed, replaced or moved. When saving, the blocks are
+/// written over the old ones as long as they fit within the space the old
+/// blocks took up. Any space left over becomes a single `Padding` block at
+/// the end, merging the existing ones, and only when the blocks don't fit
+/// is the entire file, audio frames included, rewritten.
+///
+/// # Examples
+///
+/// ```
+/// use flac::metadata::Editor;
+///
+/// match Editor::from_file("path/to/file.flac") {
+///   Ok(mut editor) => {
+///     // Drop every picture from the file.
+///     editor.retain(|block| !block.is_picture());
+///
+///     if let Err(error) = editor.save() {
+///       println!("{:?}", error);
+///     }
+///   }
+///   Err(error)     => println!("{:?}", error),
+/// }
+/// ```
+pub struct Editor {
+  filename: String,
+  stream_info: StreamInfo,
+  blocks: Vec<Metadata>,
+  // Bytes, after the "fLaC" marker, of every block the file has right now.
+  metadata_len: u64,
+}
+
+impl Editor {
+  /// Loads every metadata block of the given FLAC file.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::IO(io::ErrorKind::NotFound)` is returned when the given
+  ///   filename isn't found.
+  /// * Several different parser specific errors that are structured as
+  ///   `ErrorKind::<parser_name>Parser`.
+  pub fn from_file(filename: &str) -> Result<Editor, ErrorKind> {
+    let file             = try!(File::open(filename).map_err(to_io_error));
+    let mut stream       = ReadStream::new(file);
+    let mut stream_info  = None;
+    let mut blocks       = Vec::new();
+    let mut metadata_len = 0;
+
+    try!(many_metadata(&mut stream, |block| {
+      metadata_len += 4 + block.length() as u64;
+
+      if let Data::StreamInfo(info) = block.data {
+        stream_info = Some(info);
+      } else {
+        blocks.push(block);
+      }
+    }));
+
+    match stream_info {
+      Some(stream_info) => {
+        Ok(Editor {
+          filename: filename.to_owned(),
+          stream_info: stream_info,
+          blocks: blocks,
+          metadata_len: metadata_len,
+        })
+      }
+      None              => Err(ErrorKind::StreamInfoParser),
+    }
+  }
+
+  /// Returns the `StreamInfo` of the file, which can't be edited.
+  #[inline]
+  pub fn stream_info(&self) -> &StreamInfo {
+    &self.stream_info
+  }
+
+  /// Returns every metadata block, besides `StreamInfo`, in the order they
+  /// get saved.
+  #[inline]
+  pub fn blocks(&self) -> &[Metadata] {
+    &self.blocks
+  }
+
+  /// Returns a mutable reference to the block at `index`, for changing
+  /// the block data in place.
+  #[inline]
+  pub fn block_mut(&mut self, index: usize) -> Option<&mut Metadata> {
+    self.blocks.get_mut(index)
+  }
+
+  /// Adds a block to the end.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::InvalidMetadata` is returned when `data` is a
+  ///   `StreamInfo`.
+  pub fn push(&mut self, data: Data) -> Result<(), ErrorKind> {
+    let index = self.blocks.len();
+
+    self.insert(index, data)
+  }
+
+  /// Adds a block at `index`, moving every block after it down by one.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::InvalidMetadata` is returned when `data` is a
+  ///   `StreamInfo`.
+  ///
+  /// # Panics
+  ///
+  /// Panics when `index` is greater than the number of blocks.
+  pub fn insert(&mut self, index: usize, data: Data)
+                -> Result<(), ErrorKind> {
+    let block = try!(new_block(data));
+
+    self.blocks.insert(index, block);
+
+    Ok(())
+  }
+
+  /// Removes and returns the block at `index`.
+  ///
+  /// # Panics
+  ///
+  /// Panics when `index` is out of bounds.
+  pub fn remove(&mut self, index: usize) -> Metadata {
+    self.blocks.remove(index)
+  }
+
+  /// Replaces the block at `index` and returns the old one.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::InvalidMetadata` is returned when `data` is a
+  ///   `StreamInfo`.
+  ///
+  /// # Panics
+  ///
+  /// Panics when `index` is out of bounds.
+  pub fn replace(&mut self, index: usize, data: Data)
+                 -> Result<Metadata, ErrorKind> {
+    let block = try!(new_block(data));
+
+    Ok(mem::replace(&mut self.blocks[index], block))
+  }
+
+  /// Moves the block at `from` so it ends up at `to`, shifting the blocks
+  /// in between.
+  ///
+  /// # Panics
+  ///
+  /// Panics when either `from` or `to` are out of bounds.
+  pub fn move_block(&mut self, from: usize, to: usize) {
+    let block = self.blocks.remove(from);
+
+    self.blocks.insert(to, block);
+  }
+
+  /// Keeps only the blocks where `f` returns true.
+  pub fn retain<F>(&mut self, f: F)
+   where F: FnMut(&Metadata) -> bool {
+    self.blocks.retain(f)
+  }
+
+  /// Writes the blocks back to the file.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::InvalidMetadata` is returned when one of the blocks is a
+  ///   `StreamInfo` or is too large to fit within a metadata block.
+  /// * `ErrorKind::IO(_)` is returned when reading or writing the file
+  ///   fails.
+  pub fn save(&mut self) -> Result<(), ErrorKind> {
+    let is_valid = self.blocks.iter().all(|block| {
+      !block.is_stream_info() && block.bytes_len() - 4 <= MAX_BLOCK_LENGTH
+    });
+
+    if !is_valid {
+      return Err(ErrorKind::InvalidMetadata);
+    }
+
+    let blocks                = mem::replace(&mut self.blocks, Vec::new());
+    let (blocks, is_in_place) = layout(blocks, self.metadata_len);
+    let blocks_len            = blocks.len();
+    let mut bytes             = Vec::new();
+
+    self.blocks = blocks.into_iter().enumerate().map(|(i, block)| {
+      let length = (block.bytes_len() - 4) as u32;
+
+      Metadata::new(i + 1 == blocks_len, length, block.data)
+    }).collect();
+
+    try!(write_blocks(&self.stream_info, &self.blocks, &mut bytes)
+           .map_err(to_io_error));
+
+    let result = if is_in_place {
+      self.write_in_place(&bytes)
+    } else {
+      self.rewrite(&bytes)
+    };
+
+    if result.is_ok() {
+      self.metadata_len = bytes.len() as u64;
+    }
+
+    result.map_err(to_io_error)
+  }
+
+  fn write_in_place(&self, bytes: &[u8]) -> io::Result<()> {
+    let mut file = try!(OpenOptions::new().write(true).open(&self.filename));
+
+    try!(file.seek(SeekFrom::Start(MARKER_LENGTH)));
+    try!(file.write_all(bytes));
+
+    file.flush()
+  }
+
+  // Writes the entire file to a temporary file next to it, and then moves
+  // it over the original so a failure midway leaves the original intact.
+  fn rewrite(&self, bytes: &[u8]) -> io::Result<()> {
+    let mut original          = try!(File::open(&self.filename));
+    let permissions           = try!(original.metadata()).permissions();
+    let (temporary, mut file) = try!(create_temporary(&self.filename));
+
+    try!(original.seek(SeekFrom::Start(MARKER_LENGTH + self.metadata_len)));
+
+    let result = write_file(&mut file, bytes, &mut original)
+                   .and_then(|_| file.set_permissions(permissions))
+                   .and_then(|_| file.sync_all())
+                   .and_then(|_| fs::rename(&temporary, &self.filename));
+
+    if result.is_err() {
+      let _ = fs::remove_file(&temporary);
+    }
+
+    result
+  }
+}
+
+// Creates a file next to `filename` that didn't exist before, so an
+// existing file never gets overwritten or removed.
+fn create_temporary(filename: &str) -> io::Result<(String, File)> {
+  let mut options = OpenOptions::new();
+  let mut count   = 0;
+
+  options.write(true).create_new(true);
+
+  loop {
+    let temporary = format!("{}.{}-{}.tmp", filename, process::id(), count);
+
+    match options.open(&temporary) {
+      Ok(file)   => return Ok((temporary, file)),
+      Err(error) => {
+        if error.kind() != io::ErrorKind::AlreadyExists {
+          return Err(error);
+        }
+      }
+    }
+
+    count += 1;
+  }
+}
+
+// Writes the "fLaC" marker and the metadata `bytes`, followed by the rest
+// of `original` from where it currently is.
+fn write_file(file: &mut File, bytes: &[u8], original: &mut File)
+              -> io::Result<()> {
+  try!(file.write_all(b"fLaC"));
+  try!(file.write_all(bytes));
+  try!(io::copy(original, file));
+
+  Ok(())
+}
+
+fn new_block(data: Data) -> Result<Metadata, ErrorKind> {
+  if let Data::StreamInfo(_) = data {
+    return Err(ErrorKind::InvalidMetadata);
+  }
+
+  let length = match data {
+    Data::Padding(length) => length,
+    _                     => 0,
+  };
+
+  // The length gets updated once the block gets written.
+  Ok(Metadata::new(false, length, data))
+}
+
+// Arranges the blocks to fit within `available` bytes, returning whether
+// they do. When they fit, every `Padding` block gets merged into one at
+// the end covering whatever space is left over. Otherwise the blocks are
+// left as is, since the file needs rewriting either way.
+fn layout(blocks: Vec<Metadata>, available: u64) -> (Vec<Metadata>, bool) {
+  let stream_info_len = 4 + 34;
+  let padding_len     = blocks.iter().filter(|b| b.is_padding())
+                                     .fold(0, |len, b| len + b.bytes_len());
+  let total_len       = blocks.iter().fold(stream_info_len, |len, b| {
+                          len + b.bytes_len()
+                        }) as u64;
+  let needed          = total_len - padding_len as u64;
+
+  if needed > available {
+    return (blocks, false);
+  }
+
+  let left_over = available - needed;
+
+  if left_over == 0 {
+    let blocks = blocks.into_iter().filter(|b| !b.is_padding()).collect();
+
+    (blocks, true)
+  } else if left_over >= 4 && left_over - 4 <= MAX_BLOCK_LENGTH as u64 {
+    let length     = (left_over - 4) as u32;
+    let mut blocks = blocks.into_iter()
+                           .filter(|b| !b.is_padding())
+                           .collect::<Vec<_>>();
+
+    blocks.push(Metadata::new(true, length, Data::Padding(length)));
+
+    (blocks, true)
+  } else {
+    // Too little space for the header of a padding block, or too much
+    // for a single one.
+    (blocks, false)
+  }
+}
+
+// Serializes `stream_info` followed by `blocks`, which are expected to have
+// the last block flag already set.
+fn write_blocks<W>(stream_info: &StreamInfo, blocks: &[Metadata],
+                   buffer: &mut W)
+                   -> io::Result<()>
+ where W: io::Write {
+  let blocks_len = blocks.len();
+  let info_block = Metadata::new(blocks_len == 0, 34,
+                                 Data::StreamInfo(*stream_info));
+
+  try!(info_block.to_bytes(buffer));
+
+  for block in blocks {
+    try!(block.to_bytes(buffer));
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use metadata::{Application, Metadata, Data};
+
+  fn application(length: usize) -> Metadata {
+    Metadata::new(false, length as u32, Data::Application(Application {
+      id: "test".to_owned(),
+      data: vec![0; length - 4],
+    }))
+  }
+
+  fn padding(length: u32) -> Metadata {
+    Metadata::new(false, length, Data::Padding(length))
+  }
+
+  #[test]
+  fn test_layout() {
+    // Stream info, 38 bytes, and an application block of 24 bytes.
+    let (blocks, is_in_place) = layout(vec![application(20)], 62);
+
+    assert!(is_in_place);
+    assert_eq!(blocks.len(), 1);
+
+    let (blocks, is_in_place) = layout(vec![application(20)], 100);
+
+    assert!(is_in_place);
+    assert_eq!(blocks.len(), 2);
+    assert_eq!(blocks[1].data, Data::Padding(34));
+
+    let (blocks, is_in_place) = layout(vec![padding(10), application(20),
+                                            padding(20)], 100);
+
+    assert!(is_in_place);
+    assert_eq!(blocks.len(), 2);
+    assert!(blocks[0].is_application());
+    assert_eq!(blocks[1].data, Data::Padding(34));
+
+    // Two bytes over can't become a padding block.
+    let (blocks, is_in_place) = layout(vec![application(20)], 64);
+
+    assert!(!is_in_place);
+    assert_eq!(blocks.len(), 1);
+
+    let (blocks, is_in_place) = layout(vec![padding(2), application(20)],
+                                       68);
+
+    assert!(is_in_place);
+    assert_eq!(blocks.len(), 2);
+    assert_eq!(blocks[1].data, Data::Padding(2));
+
+    let (blocks, is_in_place) = layout(vec![application(20), padding(10)],
+                                       40);
+
+    assert!(!is_in_place);
+    assert_eq!(blocks.len(), 2);
+  }
+}
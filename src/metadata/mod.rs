@@ -3,6 +3,7 @@
 mod types;
 mod parser;
 mod metadata;
+mod editor;
 
 pub use self::types::{
   Metadata, Data, Type,
@@ -15,3 +16,5 @@ pub use self::parser::metadata_parser;
 pub use self::metadata::{
   get_stream_info, get_vorbis_comment, get_cue_sheet, get_picture,
 };
+
+pub use self::editor::Editor;
@@ -6,8 +6,6 @@ use nom::{
   Err,
 };
 
-use std::collections::HashMap;
-
 use metadata::{
   self, Metadata,
   StreamInfo, Application, VorbisComment, CueSheet, Picture,
@@ -67,7 +65,7 @@ pub fn stream_info(input: &[u8])
 pub fn padding(input: &[u8], length: u32)
                -> IResult<&[u8], metadata::Data, ErrorKind> {
   to_custom_error!(input,
-    map!(skip_bytes!(length), |_| metadata::Data::Padding(0)),
+    map!(skip_bytes!(length), |_| metadata::Data::Padding(length)),
     PaddingParser)
 }
 
@@ -111,28 +109,34 @@ pub fn seek_table(input: &[u8], length: u32)
 
 pub fn vorbis_comment(input: &[u8])
                       -> IResult<&[u8], metadata::Data, ErrorKind> {
-  to_custom_error!(input,
+  let (i, (vendor_string, comment_lines)) = try_parser!(to_custom_error!(input,
     chain!(
       vendor_string_length: le_u32 ~
       vendor_string: take_str!(vendor_string_length)  ~
       number_of_comments: le_u32 ~
       comment_lines: count!(comment_field, number_of_comments as usize),
-      || {
-        let mut comments = HashMap::with_capacity(comment_lines.len());
+      || { (vendor_string, comment_lines) }
+    ),
+    VorbisCommentParser));
 
-        for line in comment_lines {
-          let comment: Vec<&str> = line.splitn(2, '=').collect();
+  let mut comments = Vec::with_capacity(comment_lines.len());
 
-          comments.insert(comment[0].to_owned(), comment[1].to_owned());
-        }
+  for line in comment_lines {
+    // A field without a separator can't be written back the same way, so
+    // the whole block is rejected instead.
+    let index = match line.find('=') {
+      Some(index) => index,
+      None        => return IResult::Error(Err::Code(
+                       nom::ErrorKind::Custom(ErrorKind::VorbisCommentParser))),
+    };
 
-        metadata::Data::VorbisComment(VorbisComment {
-          vendor_string: vendor_string.to_owned(),
-          comments: comments,
-        })
-      }
-    ),
-    VorbisCommentParser)
+    comments.push((line[..index].to_owned(), line[(index + 1)..].to_owned()));
+  }
+
+  IResult::Done(i, metadata::Data::VorbisComment(VorbisComment {
+    vendor_string: vendor_string.to_owned(),
+    comments: comments,
+  }))
 }
 
 named!(comment_field <&[u8], String>,
@@ -265,11 +269,11 @@ pub fn picture(input: &[u8]) -> IResult<&[u8], metadata::Data, ErrorKind> {
 // As of FLAC v1.3.1, there is support for up to 127 different metadata
 // `Metadata`s but actually 7 that are implemented. When the `Metadata` type
 // isn't recognised, this block gets skipped over with this parser.
-pub fn unknown(input: &[u8], length: u32)
+pub fn unknown(input: &[u8], block_type: u8, length: u32)
                -> IResult<&[u8], metadata::Data, ErrorKind> {
   to_custom_error!(input,
     map!(take!(length), |data: &[u8]|
-      metadata::Data::Unknown(data.to_owned())),
+      metadata::Data::Unknown(block_type, data.to_owned())),
     UnknownParser)
 }
 
@@ -305,7 +309,7 @@ pub fn block_data(input: &[u8], block_type: u8, length: u32)
     4       => vorbis_comment(input),
     5       => cue_sheet(input),
     6       => picture(input),
-    7...126 => unknown(input, length),
+    7...126 => unknown(input, block_type, length),
     _       => IResult::Error(Err::Code(
                  nom::ErrorKind::Custom(ErrorKind::InvalidBlockType))),
   }
@@ -323,8 +327,6 @@ mod tests {
 
   use nom::{self, IResult, Err};
 
-  use std::collections::HashMap;
-
   #[test]
   fn test_header() {
     let inputs = [b"\x80\0\0\x22", b"\x01\0\x04\0", b"\x84\0\0\xf8"];
@@ -384,7 +386,7 @@ mod tests {
   fn test_padding() {
     let inputs = [b"\0\0\0\0\0\0\0\0\0\0", b"\0\0\0\0\x01\0\0\0\0\0"];
 
-    let result_valid   = IResult::Done(&[][..], metadata::Data::Padding(0));
+    let result_valid   = IResult::Done(&[][..], metadata::Data::Padding(10));
     let result_invalid = IResult::Error(Err::Code(nom::ErrorKind::Custom(
                            ErrorKind::PaddingParser)));
 
@@ -457,18 +459,14 @@ mod tests {
                   \x1e\0\0\0REPLAYGAIN_ALBUM_GAIN=-7.89 dB\
                   \x08\0\0\0artist=1\x07\0\0\0title=2";
 
-    let mut comments = HashMap::with_capacity(6);
-
-    comments.insert("REPLAYGAIN_TRACK_PEAK".to_owned(),
-                    "0.99996948".to_owned());
-    comments.insert("REPLAYGAIN_TRACK_GAIN".to_owned(),
-                    "-7.89 dB".to_owned());
-    comments.insert("REPLAYGAIN_ALBUM_PEAK".to_owned(),
-                    "0.99996948".to_owned());
-    comments.insert("REPLAYGAIN_ALBUM_GAIN".to_owned(),
-                    "-7.89 dB".to_owned());
-    comments.insert("artist".to_owned(), "1".to_owned());
-    comments.insert("title".to_owned(), "2".to_owned());
+    let comments = vec![
+      ("REPLAYGAIN_TRACK_PEAK".to_owned(), "0.99996948".to_owned()),
+      ("REPLAYGAIN_TRACK_GAIN".to_owned(), "-7.89 dB".to_owned()),
+      ("REPLAYGAIN_ALBUM_PEAK".to_owned(), "0.99996948".to_owned()),
+      ("REPLAYGAIN_ALBUM_GAIN".to_owned(), "-7.89 dB".to_owned()),
+      ("artist".to_owned(), "1".to_owned()),
+      ("title".to_owned(), "2".to_owned()),
+    ];
 
     let result = IResult::Done(&[][..],
       metadata::Data::VorbisComment(VorbisComment{
@@ -477,6 +475,11 @@ mod tests {
       }));
 
     assert_eq!(vorbis_comment(input), result);
+
+    let input = b"\0\0\0\0\x02\0\0\0\x08\0\0\0artist=1\x05\0\0\0title";
+
+    assert_eq!(vorbis_comment(input), IResult::Error(Err::Code(
+      nom::ErrorKind::Custom(ErrorKind::VorbisCommentParser))));
   }
 
   #[test]
@@ -579,8 +582,8 @@ mod tests {
   fn test_unknown() {
     let input  = b"random data that won't really be parsed anyway.";
     let result = IResult::Done(&[][..],
-                   metadata::Data::Unknown(input[..].to_owned()));
+                   metadata::Data::Unknown(7, input[..].to_owned()));
 
-    assert_eq!(unknown(input, 47), result);
+    assert_eq!(unknown(input, 7, 47), result);
   }
 }
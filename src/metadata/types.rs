@@ -1,8 +1,7 @@
-use std::collections::HashMap;
 use std::fmt;
 use std::io;
 
-use utility::WriteExtension;
+use utility::{ErrorKind, WriteExtension};
 
 /// Data associated with a single metadata block.
 #[derive(Debug)]
@@ -67,6 +66,15 @@ impl Metadata {
     self.is_last
   }
 
+  /// Returns the length, in bytes, of the block data.
+  ///
+  /// This is the length stored within the block header, so it doesn't
+  /// include the four bytes of the header itself.
+  #[inline]
+  pub fn length(&self) -> u32 {
+    self.length
+  }
+
   /// Returns the metadata block's type.
   pub fn data_type(&self) -> Type {
     match self.data {
@@ -77,7 +85,7 @@ impl Metadata {
       Data::VorbisComment(_) => Type::VorbisComment,
       Data::CueSheet(_)      => Type::CueSheet,
       Data::Picture(_)       => Type::Picture,
-      Data::Unknown(_)       => Type::Unknown,
+      Data::Unknown(_, _)    => Type::Unknown,
     }
   }
 
@@ -113,7 +121,7 @@ impl Metadata {
       Data::VorbisComment(ref v) => v.bytes_len(),
       Data::CueSheet(ref c)      => c.bytes_len(),
       Data::Picture(ref p)       => p.bytes_len(),
-      Data::Unknown(ref u)       => u.len(),
+      Data::Unknown(_, ref u)    => u.len(),
     }
   }
 
@@ -206,10 +214,10 @@ impl Metadata {
 
         Ok(())
       }
-      Data::Unknown(ref unknown)              => {
+      Data::Unknown(block_type, ref unknown)  => {
         let length = unknown.len();
 
-        try!(buffer.write_u8(byte + 7));
+        try!(buffer.write_u8(byte + block_type));
 
         try!(buffer.write_be_u24(length as u32));
 
@@ -226,7 +234,8 @@ impl Metadata {
 pub enum Data {
   /// Information regarding the entire audio stream.
   StreamInfo(StreamInfo),
-  /// Block that represents a number of padded bytes.
+  /// Block that represents a number of padded bytes, the value being the
+  /// length of the padding.
   Padding(u32),
   /// Data used by third-party applications.
   Application(Application),
@@ -238,8 +247,9 @@ pub enum Data {
   CueSheet(CueSheet),
   /// Stores pictures associated with the FLAC file.
   Picture(Picture),
-  /// A type of block data that isn't know or doesn't match the type above.
-  Unknown(Vec<u8>),
+  /// A type of block data that isn't know or doesn't match the type above,
+  /// along with the block type number it was stored under.
+  Unknown(u8, Vec<u8>),
 }
 
 /// Information regarding the entire audio stream.
@@ -364,21 +374,110 @@ impl SeekPoint {
 }
 
 /// Stores human-readable name/value pairs.
+///
+/// Names are case insensitive, so the helper methods ignore the case of
+/// ASCII letters when looking for matching names. A name can appear more
+/// than once, like a track with multiple artists.
 #[derive(Debug, PartialEq, Eq)]
 pub struct VorbisComment {
   /// Vendor name.
   pub vendor_string: String,
-  /// Comments associated with a name, or category, followed by it's
-  /// contents.
-  pub comments: HashMap<String, String>,
+  /// Comments as a name, or category, followed by it's contents. Kept in
+  /// the same order as the comments within the block.
+  pub comments: Vec<(String, String)>,
 }
 
 impl VorbisComment {
+  /// Constructs a `VorbisComment` without any comments.
+  pub fn new(vendor_string: &str) -> Self {
+    VorbisComment {
+      vendor_string: vendor_string.to_owned(),
+      comments: Vec::new(),
+    }
+  }
+
+  /// Returns the first value with the given name.
+  pub fn get(&self, name: &str) -> Option<&str> {
+    self.comments.iter()
+                 .find(|&&(ref key, _)| key.eq_ignore_ascii_case(name))
+                 .map(|&(_, ref value)| value.as_str())
+  }
+
+  /// Returns every value with the given name, in order.
+  pub fn get_all(&self, name: &str) -> Vec<&str> {
+    self.comments.iter()
+                 .filter(|&&(ref key, _)| key.eq_ignore_ascii_case(name))
+                 .map(|&(_, ref value)| value.as_str())
+                 .collect()
+  }
+
+  /// Adds a comment after all the others, keeping any existing comments
+  /// with the same name.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::InvalidCommentName` is returned when the name is empty
+  ///   or has a character outside of 0x20 through 0x7D, which includes
+  ///   `=`.
+  pub fn insert(&mut self, name: &str, value: &str) -> Result<(), ErrorKind> {
+    try!(validate_name(name));
+
+    self.comments.push((name.to_owned(), value.to_owned()));
+
+    Ok(())
+  }
+
+  /// Removes every comment with the given name, returning how many there
+  /// were.
+  pub fn remove_all(&mut self, name: &str) -> usize {
+    let length = self.comments.len();
+
+    self.comments.retain(|&(ref key, _)| !key.eq_ignore_ascii_case(name));
+
+    length - self.comments.len()
+  }
+
+  /// Sets the value of a name, replacing every existing comment with it.
+  ///
+  /// The comment keeps the position of the first one with the same name,
+  /// otherwise it gets added after all the others.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::InvalidCommentName` is returned for the same names that
+  ///   `insert` rejects, leaving the comments unchanged.
+  pub fn set(&mut self, name: &str, value: &str) -> Result<(), ErrorKind> {
+    try!(validate_name(name));
+
+    let position = self.comments.iter().position(|&(ref key, _)| {
+                     key.eq_ignore_ascii_case(name)
+                   });
+
+    match position {
+      Some(index) => {
+        self.comments[index].1 = value.to_owned();
+
+        let mut i = 0;
+
+        self.comments.retain(|&(ref key, _)| {
+          let is_kept = i <= index || !key.eq_ignore_ascii_case(name);
+
+          i += 1;
+
+          is_kept
+        });
+
+        Ok(())
+      }
+      None        => self.insert(name, value),
+    }
+  }
+
   pub fn bytes_len(&self) -> usize {
     let vendor_bytes   = self.vendor_string.as_bytes();
     let vendor_length  = vendor_bytes.len();
 
-     self.comments.iter().fold(0, |result, (k, v)| {
+     self.comments.iter().fold(0, |result, &(ref k, ref v)| {
        let k_length = k.as_bytes().len();
        let v_length = v.as_bytes().len();
 
@@ -397,7 +496,7 @@ impl VorbisComment {
 
     try!(buffer.write_le_u32(comments_count as u32));
 
-    for (key, value) in &self.comments {
+    for &(ref key, ref value) in &self.comments {
       let key_bytes    = key.as_bytes();
       let key_length   = key_bytes.len();
       let value_bytes  = value.as_bytes();
@@ -417,6 +516,18 @@ impl VorbisComment {
   }
 }
 
+// Field names are limited to printable ASCII, without `=`.
+fn validate_name(name: &str) -> Result<(), ErrorKind> {
+  let is_valid = !name.is_empty() &&
+                 name.bytes().all(|c| c >= 0x20 && c <= 0x7d && c != b'=');
+
+  if is_valid {
+    Ok(())
+  } else {
+    Err(ErrorKind::InvalidCommentName)
+  }
+}
+
 /// Stores cue information.
 ///
 /// Generally for storing information from Compact Disk Digital Audio, but
@@ -717,8 +828,6 @@ impl fmt::Display for PictureType {
 mod tests {
   use super::*;
 
-  use std::collections::HashMap;
-
   #[test]
   fn test_is_varied_block_size() {
     let mut info: StreamInfo = Default::default();
@@ -894,53 +1003,23 @@ mod tests {
 
   #[test]
   fn test_vorbis_comment_to_bytes() {
-    let mut comments = HashMap::with_capacity(6);
-
-    comments.insert("REPLAYGAIN_TRACK_PEAK".to_owned(),
-                    "0.99996948".to_owned());
-    comments.insert("REPLAYGAIN_TRACK_GAIN".to_owned(),
-                    "-7.89 dB".to_owned());
-    comments.insert("REPLAYGAIN_ALBUM_PEAK".to_owned(),
-                    "0.99996948".to_owned());
-    comments.insert("REPLAYGAIN_ALBUM_GAIN".to_owned(),
-                    "-7.89 dB".to_owned());
-    comments.insert("artist".to_owned(), "1".to_owned());
-    comments.insert("title".to_owned(), "2".to_owned());
-
-    let mut result = vec![0; 207];
-    let mut offset = 44;
-
-    result[0..offset].clone_from_slice(
-      b"\x04\0\0\xcb\x20\0\0\0reference libFLAC 1.1.3 20060805\x06\0\0\0");
-
-    for key in comments.keys() {
-      let bytes = if key == "REPLAYGAIN_TRACK_PEAK" {
-        &b"\x20\0\0\0REPLAYGAIN_TRACK_PEAK=0.99996948"[..]
-      } else if key == "REPLAYGAIN_TRACK_GAIN" {
-        &b"\x1e\0\0\0REPLAYGAIN_TRACK_GAIN=-7.89 dB"[..]
-      } else if key == "REPLAYGAIN_ALBUM_PEAK" {
-        &b"\x20\0\0\0REPLAYGAIN_ALBUM_PEAK=0.99996948"[..]
-      } else if key == "REPLAYGAIN_ALBUM_GAIN" {
-        &b"\x1e\0\0\0REPLAYGAIN_ALBUM_GAIN=-7.89 dB"[..]
-      } else if key == "artist" {
-        &b"\x08\0\0\0artist=1"[..]
-      } else if key == "title" {
-        &b"\x07\0\0\0title=2"[..]
-      } else {
-        &b""[..]
-      };
-
-      let bytes_len = bytes.len();
-
-      result[offset..(offset + bytes_len)].clone_from_slice(bytes);
-
-      offset += bytes_len;
-    }
-
-    let vorbis_comment = VorbisComment{
-      vendor_string: "reference libFLAC 1.1.3 20060805".to_owned(),
-      comments: comments,
-    };
+    let mut vorbis_comment =
+      VorbisComment::new("reference libFLAC 1.1.3 20060805");
+
+    vorbis_comment.insert("REPLAYGAIN_TRACK_PEAK", "0.99996948").unwrap();
+    vorbis_comment.insert("REPLAYGAIN_TRACK_GAIN", "-7.89 dB").unwrap();
+    vorbis_comment.insert("REPLAYGAIN_ALBUM_PEAK", "0.99996948").unwrap();
+    vorbis_comment.insert("REPLAYGAIN_ALBUM_GAIN", "-7.89 dB").unwrap();
+    vorbis_comment.insert("artist", "1").unwrap();
+    vorbis_comment.insert("title", "2").unwrap();
+
+    let result = b"\x04\0\0\xcb\x20\0\0\0reference libFLAC 1.1.3 20060805\
+                   \x06\0\0\0\
+                   \x20\0\0\0REPLAYGAIN_TRACK_PEAK=0.99996948\
+                   \x1e\0\0\0REPLAYGAIN_TRACK_GAIN=-7.89 dB\
+                   \x20\0\0\0REPLAYGAIN_ALBUM_PEAK=0.99996948\
+                   \x1e\0\0\0REPLAYGAIN_ALBUM_GAIN=-7.89 dB\
+                   \x08\0\0\0artist=1\x07\0\0\0title=2";
 
     let input = Metadata::new(false, 203,
       Data::VorbisComment(vorbis_comment));
@@ -951,6 +1030,53 @@ mod tests {
     assert_eq!(&bytes[..], &result[..]);
   }
 
+  #[test]
+  fn test_vorbis_comment_helpers() {
+    let mut vorbis_comment = VorbisComment::new("");
+
+    vorbis_comment.insert("ARTIST", "first").unwrap();
+    vorbis_comment.insert("title", "name").unwrap();
+    vorbis_comment.insert("Artist", "second").unwrap();
+    vorbis_comment.insert("genre", "rock").unwrap();
+
+    assert_eq!(vorbis_comment.get("artist"), Some("first"));
+    assert_eq!(vorbis_comment.get("album"), None);
+    assert_eq!(vorbis_comment.get_all("artist"), vec!["first", "second"]);
+    assert!(vorbis_comment.get_all("album").is_empty());
+
+    vorbis_comment.set("TITLE", "other").unwrap();
+
+    assert_eq!(vorbis_comment.comments[1],
+               ("title".to_owned(), "other".to_owned()));
+
+    vorbis_comment.set("artist", "only").unwrap();
+
+    assert_eq!(vorbis_comment.get_all("ARTIST"), vec!["only"]);
+    assert_eq!(vorbis_comment.comments[0],
+               ("ARTIST".to_owned(), "only".to_owned()));
+    assert_eq!(vorbis_comment.comments.len(), 3);
+
+    vorbis_comment.set("album", "new").unwrap();
+
+    assert_eq!(vorbis_comment.comments[3],
+               ("album".to_owned(), "new".to_owned()));
+
+    assert_eq!(vorbis_comment.remove_all("GENRE"), 1);
+    assert_eq!(vorbis_comment.remove_all("genre"), 0);
+    assert_eq!(vorbis_comment.comments.len(), 3);
+
+    let names = ["", "A=B", "TITLE\n", "ARTIST~", "ALBUM\u{e9}"];
+
+    for name in &names {
+      assert_eq!(vorbis_comment.insert(name, "x"),
+                 Err(ErrorKind::InvalidCommentName));
+      assert_eq!(vorbis_comment.set(name, "x"),
+                 Err(ErrorKind::InvalidCommentName));
+    }
+
+    assert_eq!(vorbis_comment.comments.len(), 3);
+  }
+
   #[test]
   fn test_cue_sheet_to_bytes() {
     let cue_sheet = CueSheet {
@@ -1058,10 +1184,10 @@ mod tests {
 
   #[test]
   fn test_unknown_to_bytes() {
-    let unknown = Data::Unknown(b"random data that won't really be parsed \
-                                  anyway."[..].to_owned());
+    let unknown = Data::Unknown(126, b"random data that won't really be \
+                                       parsed anyway."[..].to_owned());
     let input   = Metadata::new(true, 47, unknown);
-    let result  = b"\x87\0\0\x2frandom data that won't really be parsed \
+    let result  = b"\xfe\0\0\x2frandom data that won't really be parsed \
                     anyway.";
 
     let mut bytes = Vec::with_capacity(input.bytes_len());
@@ -0,0 +1,13 @@
+mod types;
+mod parser;
+mod reader;
+mod writer;
+
+pub use self::types::{
+  CONTINUED_PACKET, FIRST_PAGE, LAST_PAGE,
+  Page,
+};
+
+pub use self::parser::{page_parser, page_sync};
+pub use self::reader::OggReader;
+pub use self::writer::{DEFAULT_PAGE_SIZE, OggWriter};
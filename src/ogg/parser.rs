@@ -0,0 +1,219 @@
+use nom::{self, IResult, Err, Needed};
+
+use ogg::Page;
+use utility::{ErrorKind, crc32, crc32_update};
+
+// Bytes of the page header before the segment table.
+const HEADER_SIZE: usize = 27;
+
+// Bytes of the first packet before the "fLaC" marker, which are the packet
+// type, the "FLAC" signature, the mapping version and the number of header
+// packets.
+const MAPPING_SIZE: usize = 9;
+
+#[inline]
+fn error<T>(input: &[u8], kind: ErrorKind) -> IResult<&[u8], T, ErrorKind> {
+  IResult::Error(Err::Position(nom::ErrorKind::Custom(kind), input))
+}
+
+#[inline]
+fn le_u32(bytes: &[u8]) -> u32 {
+  bytes.iter().rev().fold(0, |value, byte| (value << 8) | *byte as u32)
+}
+
+// Checks the page at the start of `input`, including its CRC-32, and
+// returns the length of the entire page.
+fn page_length(input: &[u8]) -> IResult<&[u8], usize, ErrorKind> {
+  if input.len() < HEADER_SIZE {
+    return IResult::Incomplete(Needed::Size(HEADER_SIZE));
+  }
+
+  if &input[0..4] != b"OggS" || input[4] != 0 {
+    return error(input, ErrorKind::OggPageParser);
+  }
+
+  let header_size = HEADER_SIZE + input[26] as usize;
+
+  if input.len() < header_size {
+    return IResult::Incomplete(Needed::Size(header_size));
+  }
+
+  let segments = &input[HEADER_SIZE..header_size];
+  let length   = segments.iter().fold(header_size, |length, segment| {
+    length + *segment as usize
+  });
+
+  if input.len() < length {
+    return IResult::Incomplete(Needed::Size(length));
+  }
+
+  // The CRC-32 is calculated with its own bytes set to zero.
+  let crc = crc32(&input[0..22]);
+  let crc = crc32_update(crc, &[0, 0, 0, 0]);
+  let crc = crc32_update(crc, &input[26..length]);
+
+  if crc == le_u32(&input[22..26]) {
+    IResult::Done(input, length)
+  } else {
+    error(input, ErrorKind::InvalidCRC32)
+  }
+}
+
+/// Parses an Ogg page, checking its CRC-32.
+pub fn page_parser(input: &[u8]) -> IResult<&[u8], Page, ErrorKind> {
+  let (_, length) = try_parse!(input, page_length);
+
+  let header_size = HEADER_SIZE + input[26] as usize;
+  let granule     = &input[6..14];
+  let page        = Page {
+    header_type: input[5],
+    granule_position: granule.iter().rev().fold(0, |value, byte| {
+      (value << 8) | *byte as i64
+    }),
+    serial_number: le_u32(&input[14..18]),
+    sequence_number: le_u32(&input[18..22]),
+    segments: input[HEADER_SIZE..header_size].to_vec(),
+    body: input[header_size..length].to_vec(),
+  };
+
+  IResult::Done(&input[length..], page)
+}
+
+/// Scans for the next Ogg page, validated by its CRC-32.
+///
+/// Every byte before the page gets consumed, while the page itself is left
+/// so it can be parsed with `page_parser`. When there isn't a page within
+/// `input` the bytes that can't be the start of one are consumed and
+/// `false` is returned.
+pub fn page_sync(input: &[u8]) -> IResult<&[u8], bool, ErrorKind> {
+  let length = input.len();
+
+  if length < 4 {
+    return IResult::Incomplete(Needed::Size(4));
+  }
+
+  for i in 0..(length - 3) {
+    if &input[i..(i + 4)] != b"OggS" {
+      continue;
+    }
+
+    match page_length(&input[i..]) {
+      IResult::Done(_, _)    => return IResult::Done(&input[i..], true),
+      IResult::Incomplete(n) => {
+        // Drop what was scanned so far, unless there is nothing to drop.
+        return if i > 0 {
+          IResult::Done(&input[i..], false)
+        } else {
+          IResult::Incomplete(n)
+        };
+      }
+      IResult::Error(_)      => continue,
+    }
+  }
+
+  // The last three bytes could still be the start of a capture pattern.
+  IResult::Done(&input[(length - 3)..], false)
+}
+
+/// Parses the start of the first packet of an Ogg FLAC stream, returning
+/// the number of header packets that follow it.
+///
+/// The rest of the packet is the "fLaC" marker and `StreamInfo` block, the
+/// same as the start of a native FLAC stream. Zero header packets means the
+/// number isn't known.
+pub fn mapping_header(input: &[u8]) -> IResult<&[u8], u16, ErrorKind> {
+  if input.len() < MAPPING_SIZE {
+    return IResult::Incomplete(Needed::Size(MAPPING_SIZE));
+  }
+
+  // Only the major version changes the format of the mapping.
+  if &input[0..5] != b"\x7fFLAC" || input[5] != 1 {
+    return error(input, ErrorKind::OggMappingParser);
+  }
+
+  let headers = ((input[7] as u16) << 8) | input[8] as u16;
+
+  IResult::Done(&input[MAPPING_SIZE..], headers)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use nom::{IResult, Needed};
+
+  use ogg::Page;
+  use utility::{ErrorKind, crc32};
+
+  fn page_bytes(header_type: u8, granule: i64, body: &[u8]) -> Vec<u8> {
+    let mut bytes = b"OggS\x00".to_vec();
+
+    bytes.push(header_type);
+
+    for i in 0..8 {
+      bytes.push((granule >> (i * 8)) as u8);
+    }
+
+    bytes.extend_from_slice(&[0x78, 0x56, 0x34, 0x12, 0x02, 0x00, 0x00,
+                              0x00, 0x00, 0x00, 0x00, 0x00]);
+    bytes.push(1);
+    bytes.push(body.len() as u8);
+    bytes.extend_from_slice(body);
+
+    let crc = crc32(&bytes);
+
+    for i in 0..4 {
+      bytes[22 + i] = (crc >> (i * 8)) as u8;
+    }
+
+    bytes
+  }
+
+  #[test]
+  fn test_page_parser() {
+    let bytes       = page_bytes(0x04, 4096, b"packet");
+    let result      = Page {
+      header_type: 0x04,
+      granule_position: 4096,
+      serial_number: 0x12345678,
+      sequence_number: 2,
+      segments: vec![6],
+      body: b"packet".to_vec(),
+    };
+    let mut corrupt = bytes.clone();
+
+    corrupt[28] ^= 0x01;
+
+    assert_eq!(page_parser(&bytes), IResult::Done(&[][..], result));
+    assert_eq!(page_parser(&bytes[0..30]),
+               IResult::Incomplete(Needed::Size(34)));
+
+    match page_parser(&corrupt) {
+      IResult::Error(nom::Err::Position(kind, _)) => {
+        assert_eq!(kind, nom::ErrorKind::Custom(ErrorKind::InvalidCRC32));
+      }
+      _                                           => panic!(),
+    }
+  }
+
+  #[test]
+  fn test_page_sync() {
+    let mut bytes = b"garbage OggS".to_vec();
+
+    bytes.extend_from_slice(&page_bytes(0x00, -1, b"packet"));
+
+    assert_eq!(page_sync(&bytes), IResult::Done(&bytes[12..], true));
+    assert_eq!(page_sync(&bytes[0..20]),
+               IResult::Done(&bytes[8..20], false));
+    assert_eq!(page_sync(b"no page"), IResult::Done(&b"age"[..], false));
+  }
+
+  #[test]
+  fn test_mapping_header() {
+    let input = b"\x7fFLAC\x01\x00\x00\x02fLaC";
+
+    assert_eq!(mapping_header(input), IResult::Done(&b"fLaC"[..], 2));
+    assert!(mapping_header(b"\x7fFLAC\x02\x00\x00\x02fLaC").is_err());
+    assert!(mapping_header(b"\x7fVorbis\x00\x00").is_err());
+  }
+}
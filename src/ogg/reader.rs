@@ -0,0 +1,416 @@
+use nom::{self, IResult, Needed};
+
+use std::io::{Read, Seek};
+use std::mem;
+
+use ogg::{Page, page_parser, page_sync};
+use ogg::parser::mapping_header;
+use utility::{ErrorKind, ReadStream, StreamProducer, SeekableProducer};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+  Mapping,
+  Headers,
+  Audio,
+}
+
+/// Reads FLAC from within an Ogg container.
+///
+/// Packets from the Ogg pages are put back together and given to parsers
+/// as a native FLAC stream. The first packet becomes the "fLaC" marker and
+/// `StreamInfo`, every header packet after it is a single metadata block,
+/// and every audio packet is a single frame. Pages that belong to another
+/// logical bitstream are skipped and each page has its CRC-32 checked.
+pub struct OggReader<R: Read> {
+  stream: ReadStream<R>,
+  serial_number: Option<u32>,
+  state: State,
+  // Start of a packet that continues on the next page.
+  packet: Vec<u8>,
+  // Packets, as native FLAC bytes, that haven't been parsed yet.
+  buffer: Vec<u8>,
+  offset: usize,
+  // Offset of the next page, and of the first page that starts with an
+  // audio packet.
+  page_offset: u64,
+  audio_offset: u64,
+  // Drop the packets that end on the next page, which are before the
+  // sample a seek moved to.
+  is_discarding: bool,
+  is_finished: bool,
+}
+
+impl<R> OggReader<R> where R: Read {
+  /// Constructor for `OggReader` based on a `Read` source.
+  pub fn new(reader: R) -> Self {
+    OggReader {
+      stream: ReadStream::new(reader),
+      serial_number: None,
+      state: State::Mapping,
+      packet: Vec::new(),
+      buffer: Vec::new(),
+      offset: 0,
+      page_offset: 0,
+      audio_offset: 0,
+      is_discarding: false,
+      is_finished: false,
+    }
+  }
+
+  /// Returns the serial number of the Ogg FLAC bitstream, once its first
+  /// page has been read.
+  #[inline]
+  pub fn serial_number(&self) -> Option<u32> {
+    self.serial_number
+  }
+
+  // Read the next page and add every packet that ends on it to the buffer.
+  fn read_page(&mut self) -> Result<(), ErrorKind> {
+    let page = loop {
+      match self.stream.parse(page_parser) {
+        Ok(page)                      => break page,
+        Err(ErrorKind::Continue)      => continue,
+        // A page cut short at the end is treated as the end of the stream.
+        Err(ErrorKind::EndOfInput)    |
+        Err(ErrorKind::Incomplete(_)) => {
+          self.is_finished = true;
+
+          return Ok(());
+        }
+        Err(error)                    => return Err(error),
+      }
+    };
+
+    // Every first page comes before any other page, so one that isn't
+    // means there is no FLAC bitstream.
+    self.page_offset += (27 + page.segments.len() + page.body.len()) as u64;
+
+    if self.serial_number.is_none() {
+      if !page.is_first() {
+        return Err(ErrorKind::OggMappingParser);
+      } else if page.body.starts_with(b"\x7fFLAC") {
+        self.serial_number = Some(page.serial_number);
+      } else {
+        return Ok(());
+      }
+    }
+
+    if self.serial_number != Some(page.serial_number) {
+      return Ok(());
+    }
+
+    if self.offset > 0 {
+      self.buffer.drain(0..self.offset);
+
+      self.offset = 0;
+    }
+
+    let is_header = self.state != State::Audio;
+
+    try!(self.add_packets(&page));
+
+    if is_header && self.state == State::Audio {
+      self.audio_offset = self.page_offset;
+    }
+
+    if page.is_last() {
+      self.is_finished = true;
+    }
+
+    Ok(())
+  }
+
+  fn add_packets(&mut self, page: &Page) -> Result<(), ErrorKind> {
+    let mut packet = mem::replace(&mut self.packet, Vec::new());
+    let mut start  = 0;
+
+    // Without the start of the packet there is nothing to continue.
+    if !page.is_continued() {
+      packet.clear();
+    }
+
+    for segment in &page.segments {
+      let end = start + *segment as usize;
+
+      packet.extend_from_slice(&page.body[start..end]);
+
+      start = end;
+
+      if *segment < 255 {
+        if !self.is_discarding {
+          try!(self.add_packet(&packet));
+        }
+
+        packet.clear();
+      }
+    }
+
+    self.packet        = packet;
+    self.is_discarding = false;
+
+    Ok(())
+  }
+
+  fn add_packet(&mut self, packet: &[u8]) -> Result<(), ErrorKind> {
+    match self.state {
+      State::Mapping => {
+        let bytes = match mapping_header(packet) {
+          IResult::Done(i, _) => i,
+          _                   => return Err(ErrorKind::OggMappingParser),
+        };
+
+        // The last metadata flag of `StreamInfo`, after the "fLaC" marker.
+        let is_last = bytes.get(4).map_or(false, |byte| (byte & 0x80) != 0);
+
+        self.buffer.extend_from_slice(bytes);
+
+        self.state = if is_last { State::Audio } else { State::Headers };
+      }
+      State::Headers => {
+        let is_last = packet.first().map_or(false, |byte| (byte & 0x80) != 0);
+
+        self.buffer.extend_from_slice(packet);
+
+        if is_last {
+          self.state = State::Audio;
+        }
+      }
+      State::Audio   => self.buffer.extend_from_slice(packet),
+    }
+
+    Ok(())
+  }
+}
+
+impl<R> OggReader<R> where R: Read + Seek {
+  /// Moves to the page where decoding can start for `sample`.
+  ///
+  /// The pages are searched by their granule position for the last one
+  /// that ends a packet at or before `sample`. Returns the number of the
+  /// first sample of the next packet, which is the first frame that gets
+  /// parsed afterwards.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::InvalidSeek` is returned when the header packets haven't
+  ///   been read yet.
+  /// * `ErrorKind::IO(_)` is returned when seeking within the reader fails.
+  pub fn seek_granule(&mut self, sample: u64) -> Result<u64, ErrorKind> {
+    if self.state != State::Audio {
+      return Err(ErrorKind::InvalidSeek);
+    }
+
+    let length              = try!(self.stream.stream_len());
+    let (mut low, mut high) = (self.audio_offset, length);
+    let mut start           = (self.audio_offset, 0, false);
+
+    while low < high {
+      let middle = low + (high - low) / 2;
+
+      match try!(self.granule_at(middle)) {
+        Some((position, granule)) if position < high => {
+          if granule > sample {
+            high = middle;
+          } else {
+            start = (position, granule, true);
+            low   = position + 1;
+          }
+        }
+        _                                             => {
+          high = middle;
+        }
+      }
+    }
+
+    let (position, first, is_discarding) = start;
+
+    try!(self.stream.seek(position));
+
+    self.packet.clear();
+    self.buffer.clear();
+
+    self.offset        = 0;
+    self.page_offset   = position;
+    self.is_discarding = is_discarding;
+    self.is_finished   = false;
+
+    Ok(first)
+  }
+
+  // Find the first page at or after `offset` that ends a packet. The
+  // result is where the page starts and its granule position.
+  fn granule_at(&mut self, offset: u64)
+                -> Result<Option<(u64, u64)>, ErrorKind> {
+    try!(self.stream.seek(offset));
+
+    loop {
+      match self.stream.parse(page_sync) {
+        Ok(true)                      => (),
+        Ok(false)                     |
+        Err(ErrorKind::Continue)      => continue,
+        Err(ErrorKind::EndOfInput)    |
+        Err(ErrorKind::Incomplete(_)) => return Ok(None),
+        Err(error)                    => return Err(error),
+      }
+
+      let position = self.stream.position();
+      let page     = loop {
+        match self.stream.parse(page_parser) {
+          Err(ErrorKind::Continue) => continue,
+          result                   => break try!(result),
+        }
+      };
+
+      if Some(page.serial_number) == self.serial_number &&
+         page.granule_position >= 0 {
+        return Ok(Some((position, page.granule_position as u64)));
+      }
+    }
+  }
+}
+
+impl<R> StreamProducer for OggReader<R> where R: Read {
+  fn parse<F, T>(&mut self, f: F) -> Result<T, ErrorKind>
+   where F: FnOnce(&[u8]) -> IResult<&[u8], T, ErrorKind> {
+    while self.offset == self.buffer.len() {
+      if self.is_finished {
+        return Err(ErrorKind::EndOfInput);
+      }
+
+      try!(self.read_page());
+    }
+
+    let result = {
+      let bytes = &self.buffer[self.offset..];
+
+      match f(bytes) {
+        IResult::Done(i, o)    => Ok((bytes.len() - i.len(), o)),
+        IResult::Incomplete(n) => {
+          let mut needed = bytes.len() + 1;
+
+          if let Needed::Size(size) = n {
+            needed = size;
+          }
+
+          Err(ErrorKind::Incomplete(needed))
+        }
+        IResult::Error(e)      => {
+          match e {
+            nom::Err::Code(k)               |
+            nom::Err::Node(k, _)            |
+            nom::Err::Position(k, _)        |
+            nom::Err::NodePosition(k, _, _) => {
+              if let nom::ErrorKind::Custom(kind) = k {
+                Err(kind)
+              } else {
+                Err(ErrorKind::Unknown)
+              }
+            }
+          }
+        }
+      }
+    };
+
+    match result {
+      Ok((consumed, o))               => {
+        self.offset += consumed;
+
+        Ok(o)
+      }
+      Err(ErrorKind::Incomplete(n))   => {
+        // There are no more pages to read, so asking to continue would end
+        // up parsing the same bytes forever.
+        if self.is_finished {
+          return Err(ErrorKind::Incomplete(n));
+        }
+
+        try!(self.read_page());
+
+        Err(ErrorKind::Continue)
+      }
+      Err(error)                      => Err(error),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use utility::{ErrorKind, StreamProducer, crc32};
+
+  use nom::IResult;
+
+  fn page_bytes(header_type: u8, granule: i64, segments: &[u8],
+                body: &[u8])
+                -> Vec<u8> {
+    let mut bytes = b"OggS\x00".to_vec();
+
+    bytes.push(header_type);
+
+    for i in 0..8 {
+      bytes.push((granule >> (i * 8)) as u8);
+    }
+
+    bytes.extend_from_slice(&[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                              0x00, 0x00, 0x00, 0x00, 0x00]);
+    bytes.push(segments.len() as u8);
+    bytes.extend_from_slice(segments);
+    bytes.extend_from_slice(body);
+
+    let crc = crc32(&bytes);
+
+    for i in 0..4 {
+      bytes[22 + i] = (crc >> (i * 8)) as u8;
+    }
+
+    bytes
+  }
+
+  fn take(length: usize) -> impl Fn(&[u8]) -> IResult<&[u8], Vec<u8>,
+                                                        ErrorKind> {
+    move |input| {
+      if input.len() < length {
+        IResult::Incomplete(Needed::Size(length))
+      } else {
+        IResult::Done(&input[length..], input[0..length].to_vec())
+      }
+    }
+  }
+
+  #[test]
+  fn test_packets() {
+    let mut mapping = b"\x7fFLAC\x01\x00\x00\x00fLaC".to_vec();
+    let mut bytes   = Vec::new();
+
+    // Last metadata block flag along with the type and length.
+    mapping.extend_from_slice(&[0x80, 0x00, 0x00, 0x01, 0xaa]);
+
+    bytes.extend(page_bytes(0x02, 0, &[mapping.len() as u8], &mapping));
+    // A packet of 300 bytes split across two pages.
+    bytes.extend(page_bytes(0x00, -1, &[255], &[0x01; 255]));
+    bytes.extend(page_bytes(0x05, 16, &[45, 2], &[0x01; 47]));
+
+    let mut reader = OggReader::new(&bytes[..]);
+
+    assert_eq!(reader.parse(take(9)),
+               Ok(b"fLaC\x80\x00\x00\x01\xaa".to_vec()));
+
+    let mut packets = Vec::new();
+
+    loop {
+      match reader.parse(take(150)) {
+        Ok(packet)               => packets.push(packet),
+        Err(ErrorKind::Continue) => continue,
+        Err(error)               => {
+          assert_eq!(error, ErrorKind::Incomplete(150));
+
+          break;
+        }
+      }
+    }
+
+    assert_eq!(reader.serial_number(), Some(1));
+    assert_eq!(packets, vec![vec![0x01; 150], vec![0x01; 150]]);
+  }
+}
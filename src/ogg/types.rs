@@ -0,0 +1,51 @@
+/// Flag for a page that starts with the rest of a packet from the previous
+/// page.
+pub const CONTINUED_PACKET: u8 = 0x01;
+
+/// Flag for the first page of a logical bitstream.
+pub const FIRST_PAGE: u8 = 0x02;
+
+/// Flag for the last page of a logical bitstream.
+pub const LAST_PAGE: u8 = 0x04;
+
+/// A single page of an Ogg bitstream.
+///
+/// Packets are split into segments of at most 255 bytes, where a segment
+/// shorter than 255 bytes ends a packet. A page holds up to 255 of these
+/// segments and a packet can continue on the next page.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Page {
+  /// Flags for a continued packet, the first page, and the last page.
+  pub header_type: u8,
+  /// Number of samples, per channel, up to the end of the last packet that
+  /// ends on this page. A page without the end of a packet has -1.
+  pub granule_position: i64,
+  /// Number that identifies which logical bitstream the page belongs to.
+  pub serial_number: u32,
+  /// Number of the page within its logical bitstream.
+  pub sequence_number: u32,
+  /// Length of each segment within the body, also known as lacing values.
+  pub segments: Vec<u8>,
+  /// Bytes of every segment.
+  pub body: Vec<u8>,
+}
+
+impl Page {
+  /// Returns true when the page starts with the rest of a packet.
+  #[inline]
+  pub fn is_continued(&self) -> bool {
+    (self.header_type & CONTINUED_PACKET) != 0
+  }
+
+  /// Returns true for the first page of a logical bitstream.
+  #[inline]
+  pub fn is_first(&self) -> bool {
+    (self.header_type & FIRST_PAGE) != 0
+  }
+
+  /// Returns true for the last page of a logical bitstream.
+  #[inline]
+  pub fn is_last(&self) -> bool {
+    (self.header_type & LAST_PAGE) != 0
+  }
+}
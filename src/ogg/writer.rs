@@ -0,0 +1,354 @@
+use nom::IResult;
+
+use std::cmp;
+use std::io;
+
+use frame::frame_sync;
+use metadata::{self, Metadata, StreamInfo};
+use ogg::{CONTINUED_PACKET, FIRST_PAGE, LAST_PAGE};
+use stream::Stream;
+use utility::{ErrorKind, StreamProducer, crc32};
+
+/// Default upper limit, in bytes, for the body of a page.
+pub const DEFAULT_PAGE_SIZE: usize = 4096;
+
+// Largest body a page can have, 255 segments of 255 bytes.
+const MAX_PAGE_SIZE: usize = 255 * 255;
+
+fn to_io_error(error: io::Error) -> ErrorKind {
+  ErrorKind::IO(error.kind())
+}
+
+/// Writes FLAC into an Ogg container.
+///
+/// The first page only has the mapping packet, which includes the "fLaC"
+/// marker and `StreamInfo`, and is followed by a packet for each metadata
+/// block. Every frame is then a single packet, where the granule position
+/// of a page is the number of samples up to the end of the last frame that
+/// ends on it. Frames are copied as is, so an existing stream can be moved
+/// into Ogg without decoding it.
+///
+/// # Examples
+///
+/// ```
+/// use flac::{OggWriter, StreamReader};
+/// use std::fs::File;
+///
+/// match StreamReader::<File>::from_file("path/to/file.flac") {
+///   Ok(mut stream) => {
+///     let file       = File::create("path/to/file.oga").unwrap();
+///     let mut writer = OggWriter::new(file, 1);
+///
+///     writer.write_stream(&mut stream).unwrap();
+///   }
+///   Err(error)     => println!("{:?}", error),
+/// }
+/// ```
+pub struct OggWriter<W: io::Write> {
+  writer: W,
+  info: Option<StreamInfo>,
+  serial_number: u32,
+  sequence_number: u32,
+  page_size: usize,
+  // Segments of the page being filled, along with its header type and
+  // granule position.
+  segments: Vec<u8>,
+  body: Vec<u8>,
+  header_type: u8,
+  granule_position: i64,
+  is_finished: bool,
+}
+
+impl<W> OggWriter<W> where W: io::Write {
+  /// Constructs an `OggWriter` using `DEFAULT_PAGE_SIZE` for the pages.
+  #[inline]
+  pub fn new(writer: W, serial_number: u32) -> Self {
+    OggWriter::with_page_size(writer, serial_number, DEFAULT_PAGE_SIZE)
+  }
+
+  /// Constructs an `OggWriter` where the body of each page is at most
+  /// `page_size` bytes.
+  ///
+  /// Packets are split across pages to keep within this size, which gets
+  /// clamped between 255 and 65025 bytes, the smallest and largest body a
+  /// page can have when full. `serial_number` identifies the logical
+  /// bitstream within the container.
+  pub fn with_page_size(writer: W, serial_number: u32, page_size: usize)
+                        -> Self {
+    OggWriter {
+      writer: writer,
+      info: None,
+      serial_number: serial_number,
+      sequence_number: 0,
+      page_size: cmp::min(cmp::max(page_size, 255), MAX_PAGE_SIZE),
+      segments: Vec::with_capacity(255),
+      body: Vec::new(),
+      header_type: FIRST_PAGE,
+      granule_position: -1,
+      is_finished: false,
+    }
+  }
+
+  /// Writes the mapping packet and a packet for every metadata block.
+  ///
+  /// Any `StreamInfo` blocks inside of `metadata` are skipped, and the
+  /// last metadata block flag gets set on the last block written.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::InvalidMetadata` is returned when the headers have
+  ///   already been written.
+  /// * `ErrorKind::IO(_)` is returned when writing a page fails.
+  pub fn write_headers(&mut self, info: &StreamInfo, metadata: &[Metadata])
+                       -> Result<(), ErrorKind> {
+    if self.info.is_some() {
+      return Err(ErrorKind::InvalidMetadata);
+    }
+
+    let blocks = metadata.iter().filter(|block| !block.is_stream_info())
+                                .collect::<Vec<_>>();
+    let blocks_len = blocks.len();
+    let mut packet = Vec::new();
+
+    packet.extend_from_slice(b"\x7fFLAC\x01\x00");
+    packet.push((blocks_len >> 8) as u8);
+    packet.push(blocks_len as u8);
+    packet.extend_from_slice(b"fLaC");
+
+    let info_block = Metadata::new(blocks_len == 0, 34,
+                                   metadata::Data::StreamInfo(*info));
+
+    try!(info_block.to_bytes(&mut packet).map_err(to_io_error));
+    try!(self.write_packet(&packet, 0));
+    try!(self.flush_page(false));
+
+    for (i, block) in blocks.iter().enumerate() {
+      packet.clear();
+
+      try!(block.to_bytes(&mut packet).map_err(to_io_error));
+
+      // The flag from the block itself might not fit its new position.
+      if i + 1 == blocks_len {
+        packet[0] |= 0x80;
+      } else {
+        packet[0] &= 0x7f;
+      }
+
+      try!(self.write_packet(&packet, 0));
+    }
+
+    // Audio packets have to start on a new page.
+    try!(self.flush_page(false));
+
+    self.info = Some(*info);
+
+    Ok(())
+  }
+
+  /// Writes the bytes of a single frame as a packet.
+  ///
+  /// The frame header is parsed to find the sample number the frame ends
+  /// at, which becomes the granule position.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::InvalidStreamInfo` is returned when the headers haven't
+  ///   been written yet.
+  /// * `ErrorKind::InvalidSyncCode` is returned when `bytes` doesn't start
+  ///   with a valid frame header.
+  /// * `ErrorKind::IO(_)` is returned when writing a page fails.
+  pub fn write_frame(&mut self, bytes: &[u8]) -> Result<(), ErrorKind> {
+    let info = match self.info {
+      Some(info) => info,
+      None       => return Err(ErrorKind::InvalidStreamInfo),
+    };
+
+    let header = match frame_sync(bytes, &info) {
+      IResult::Done(i, Some(header)) if i.len() == bytes.len() => header,
+      _                                                       => {
+        return Err(ErrorKind::InvalidSyncCode);
+      }
+    };
+
+    let first = header.first_sample(&info);
+
+    self.write_packet(bytes, first + header.block_size as u64)
+  }
+
+  /// Writes the headers and every frame left within `stream`, copying the
+  /// frames without decoding them, and then finishes the bitstream.
+  ///
+  /// # Failures
+  ///
+  /// * Everything that `OggWriter::write_headers` and
+  ///   `Stream::next_frame_bytes` can return.
+  pub fn write_stream<P>(&mut self, stream: &mut Stream<P>)
+                         -> Result<(), ErrorKind>
+   where P: StreamProducer {
+    let mut bytes = Vec::new();
+
+    try!(self.write_headers(&stream.info(), stream.metadata()));
+
+    while try!(stream.next_frame_bytes(&mut bytes)
+                     .map_err(|error| error.kind)) {
+      try!(self.write_frame(&bytes));
+    }
+
+    self.finish()
+  }
+
+  /// Writes the last page, marking the end of the bitstream, and flushes
+  /// the underlining writer.
+  ///
+  /// Calling it more than once doesn't do anything.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::IO(_)` is returned when writing the page, or flushing
+  ///   the writer, fails.
+  pub fn finish(&mut self) -> Result<(), ErrorKind> {
+    if self.is_finished {
+      return Ok(());
+    }
+
+    self.header_type |= LAST_PAGE;
+
+    try!(self.flush_page(true));
+
+    self.is_finished = true;
+
+    self.writer.flush().map_err(to_io_error)
+  }
+
+  /// Returns the underlining writer.
+  #[inline]
+  pub fn into_inner(self) -> W {
+    self.writer
+  }
+
+  // Split `packet` into segments, writing out each page that fills up.
+  fn write_packet(&mut self, packet: &[u8], granule_position: u64)
+                  -> Result<(), ErrorKind> {
+    // A packet that is a multiple of 255 bytes ends with an empty segment.
+    let segments = packet.len() / 255 + 1;
+
+    for i in 0..segments {
+      let start  = i * 255;
+      let end    = cmp::min(start + 255, packet.len());
+      let length = end - start;
+
+      if self.segments.len() == 255 ||
+         (!self.segments.is_empty() && self.body.len() + length >
+                                       self.page_size) {
+        try!(self.flush_page(i > 0));
+      }
+
+      self.segments.push(length as u8);
+      self.body.extend_from_slice(&packet[start..end]);
+    }
+
+    self.granule_position = granule_position as i64;
+
+    Ok(())
+  }
+
+  // Write out the page being filled, when it has any segments or is the
+  // last page. `is_continued` is whether the next page starts in the middle
+  // of a packet.
+  fn flush_page(&mut self, is_continued: bool) -> Result<(), ErrorKind> {
+    self.write_page().map_err(to_io_error).map(|is_written| {
+      if is_written {
+        self.header_type = if is_continued { CONTINUED_PACKET } else { 0 };
+      }
+    })
+  }
+
+  fn write_page(&mut self) -> io::Result<bool> {
+    let is_last = (self.header_type & LAST_PAGE) != 0;
+
+    if self.segments.is_empty() && !is_last {
+      return Ok(false);
+    }
+
+    let mut page = Vec::with_capacity(27 + self.segments.len() +
+                                      self.body.len());
+
+    page.extend_from_slice(b"OggS\x00");
+    page.push(self.header_type);
+
+    for i in 0..8 {
+      page.push((self.granule_position >> (i * 8)) as u8);
+    }
+
+    for i in 0..4 {
+      page.push((self.serial_number >> (i * 8)) as u8);
+    }
+
+    for i in 0..4 {
+      page.push((self.sequence_number >> (i * 8)) as u8);
+    }
+
+    // The CRC-32 gets filled in once the entire page is there.
+    page.extend_from_slice(&[0, 0, 0, 0]);
+    page.push(self.segments.len() as u8);
+    page.extend_from_slice(&self.segments);
+    page.extend_from_slice(&self.body);
+
+    let crc = crc32(&page);
+
+    for i in 0..4 {
+      page[22 + i] = (crc >> (i * 8)) as u8;
+    }
+
+    try!(self.writer.write_all(&page));
+
+    self.segments.clear();
+    self.body.clear();
+
+    self.sequence_number  += 1;
+    self.granule_position  = -1;
+
+    Ok(true)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use ogg::{Page, page_parser};
+
+  fn pages(mut bytes: &[u8]) -> Vec<Page> {
+    let mut pages = Vec::new();
+
+    while !bytes.is_empty() {
+      let (i, page) = page_parser(bytes).unwrap();
+
+      pages.push(page);
+
+      bytes = i;
+    }
+
+    pages
+  }
+
+  #[test]
+  fn test_write_packet() {
+    let mut writer = OggWriter::with_page_size(Vec::new(), 1, 300);
+
+    writer.write_packet(&[0; 510], 10).unwrap();
+    writer.write_packet(&[0; 20], 20).unwrap();
+    writer.finish().unwrap();
+
+    let pages = pages(&writer.into_inner());
+
+    assert_eq!(pages.len(), 2);
+    assert_eq!(pages[0].header_type, FIRST_PAGE);
+    assert_eq!(pages[0].granule_position, -1);
+    assert_eq!(pages[0].segments, vec![255]);
+    assert_eq!(pages[1].header_type, CONTINUED_PACKET | LAST_PAGE);
+    assert_eq!(pages[1].granule_position, 20);
+    assert_eq!(pages[1].sequence_number, 1);
+    assert_eq!(pages[1].segments, vec![255, 0, 20]);
+  }
+}
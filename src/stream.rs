@@ -2,22 +2,149 @@ use metadata;
 use frame;
 use subframe;
 
+use subframe::adjust_bits_per_sample;
+
 use metadata::{Metadata, StreamInfo};
-use frame::frame_parser;
+use ogg::OggReader;
+use frame::{
+  check_subset, frame_parser_into, frame_parser_unchecked, frame_span,
+  frame_sync, ChannelAssignment, NumberType, Frame, Header, Scratch,
+  SubsetRule,
+};
 use utility::{
   ErrorKind, ByteStream, ReadStream, Sample, SampleSize, StreamProducer,
-  many_metadata,
+  SeekableProducer, MD5, many_metadata,
 };
 
+use nom::IResult;
+
 use std::io;
+use std::cmp;
+use std::thread;
 use std::usize;
+use std::collections::BTreeMap;
 use std::fs::File;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{mpsc, Arc, Mutex};
 
 /// FLAC stream that decodes and hold file information.
 pub struct Stream<P: StreamProducer> {
   info: StreamInfo,
   metadata: Vec<Metadata>,
   producer: P,
+  // Byte offset of the first frame within the producer.
+  frame_offset: u64,
+  // Sample number of the next sample `Iter` returns.
+  next_sample: u64,
+  // Samples, per channel, to discard from the next decoded frame.
+  skip_samples: usize,
+  // Byte offset, number and first sample of the next frame to decode.
+  byte_offset: u64,
+  frame_number: u64,
+  frame_sample: u64,
+  last_error: Option<DecodeError>,
+  // Frames read ahead were dropped, so decoding fails until a seek.
+  is_position_lost: bool,
+  // Corrupt frames get skipped, instead of stopping at them, once enabled.
+  skipped_regions: Vec<SkippedRegion>,
+  is_resync_enabled: bool,
+  // Rules of the streamable subset that decoded frames break, only checked
+  // once enabled.
+  subset_violations: Vec<SubsetViolation>,
+  is_subset_checked: bool,
+  // Samples, per channel, still to be filled in for the last region
+  // skipped over.
+  concealment: Concealment,
+  lost_samples: u64,
+  // Signature of the decoded samples, only kept while every sample from
+  // the start of the stream has gone through it.
+  md5: Option<MD5>,
+  md5_sum: Option<[u8; 16]>,
+  md5_buffer: Vec<u8>,
+  is_md5_enabled: bool,
+  // Samples of the frames that get copied without being decoded.
+  frame_buffer: Vec<i64>,
+  // Frame that every frame gets parsed into, so it's only allocated once.
+  scratch: Scratch,
+}
+
+/// Outcome of checking the decoded samples against the MD5 signature
+/// within `StreamInfo`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verification {
+  /// The signature of the decoded samples matches.
+  Match,
+  /// The signature of the decoded samples doesn't match.
+  Mismatch,
+  /// The stream has an all zero signature, meaning the encoder didn't
+  /// compute one.
+  Unset,
+  /// The signature of the decoded samples isn't available. Either the MD5
+  /// computation isn't enabled, the end of the stream hasn't been reached
+  /// yet, or not every sample from the start was decoded.
+  Unverified,
+}
+
+/// How the samples of frames that fail to decode get replaced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Concealment {
+  /// Lost samples are left out, so every sample after them comes earlier
+  /// than it should.
+  Skip,
+  /// Lost samples are replaced with silence.
+  Silence,
+  /// Frames where only the CRC-16 doesn't match get decoded as is, every
+  /// other lost sample is replaced with silence.
+  IgnoreCRC,
+}
+
+/// Error that stopped the decoding of a stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeError {
+  /// The kind of error that occurred.
+  pub kind: ErrorKind,
+  /// Number of the frame that failed to decode, counting from zero.
+  ///
+  /// After a seek within a stream that has a varied block size, this is
+  /// an estimate based on the maximum block size.
+  pub frame_number: u64,
+  /// Offset, in bytes, from the start of the stream to where the frame
+  /// that failed to decode begins.
+  pub byte_offset: u64,
+}
+
+/// Bytes skipped over, while looking for the next valid frame, after a
+/// frame failed to decode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SkippedRegion {
+  /// The kind of error that the first skipped frame failed with.
+  pub kind: ErrorKind,
+  /// Offset, in bytes, from the start of the stream to where the region
+  /// begins.
+  pub byte_offset: u64,
+  /// Number of bytes skipped.
+  pub byte_length: u64,
+  /// Number of the first sample lost.
+  pub sample_number: u64,
+  /// Number of samples, per channel, lost within the region.
+  ///
+  /// This is the difference between the sample numbers in the headers of
+  /// the frames around the region, or up to `StreamInfo::total_samples`
+  /// when the region runs to the end of the stream.
+  pub samples: u64,
+}
+
+/// Rule of the streamable subset that a frame breaks, found while decoding
+/// with `Stream::check_subset` enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SubsetViolation {
+  /// The rule being broken.
+  pub rule: SubsetRule,
+  /// Number of the frame breaking the rule, counting from zero.
+  pub frame_number: u64,
+  /// Offset, in bytes, from the start of the stream to where the frame
+  /// begins.
+  pub byte_offset: u64,
 }
 
 /// Alias for a FLAC stream produced from `Read`.
@@ -26,6 +153,9 @@ pub type StreamReader<R>  = Stream<ReadStream<R>>;
 /// Alias for a FLAC stream produced from a byte stream buffer.
 pub type StreamBuffer<'a> = Stream<ByteStream<'a>>;
 
+/// Alias for a FLAC stream produced from an Ogg container.
+pub type OggStreamReader<R> = Stream<OggReader<R>>;
+
 impl<P> Stream<P> where P: StreamProducer {
   /// Constructor for the default state of a FLAC stream.
   #[inline]
@@ -92,129 +222,1674 @@ impl<P> Stream<P> where P: StreamProducer {
     Stream::from_stream_producer(producer)
   }
 
+  /// Constructs a decoder for FLAC inside of an Ogg container.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::OggMappingParser` is returned when the first page of the
+  ///   container isn't FLAC.
+  /// * `ErrorKind::InvalidCRC32` is returned when a page is corrupt.
+  /// * Several different parser specific errors that are structured as
+  ///   `ErrorKind::<parser_name>Parser`.
+  #[inline]
+  pub fn from_ogg<R: io::Read>(reader: R)
+                               -> Result<OggStreamReader<R>, ErrorKind> {
+    let producer = OggReader::new(reader);
+
+    Stream::from_stream_producer(producer)
+  }
+
+  /// Constructs a decoder for FLAC inside of an Ogg container with the
+  /// given file name.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::IO(io::ErrorKind::NotFound)` is returned when the given
+  ///   filename isn't found.
+  /// * Everything else that `Stream::from_ogg` can return.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use flac::OggStreamReader;
+  /// use std::fs::File;
+  ///
+  /// match OggStreamReader::<File>::from_ogg_file("path/to/file.oga") {
+  ///   Ok(mut stream) => {
+  ///     for sample in stream.iter::<i16>() {
+  ///       // Iterate over each decoded sample
+  ///     }
+  ///   }
+  ///   Err(error)     => println!("{:?}", error),
+  /// }
+  /// ```
+  #[inline]
+  pub fn from_ogg_file(filename: &str)
+                       -> Result<OggStreamReader<File>, ErrorKind> {
+    File::open(filename).map_err(|e| ErrorKind::IO(e.kind()))
+                        .and_then(|file| {
+      let producer = OggReader::new(file);
+
+      Stream::from_stream_producer(producer)
+    })
+  }
+
   fn from_stream_producer(mut producer: P) -> Result<Self, ErrorKind> {
-    let mut stream_info = Default::default();
-    let mut metadata    = Vec::new();
+    let mut stream_info  = Default::default();
+    let mut metadata     = Vec::new();
+    // Starts after the "fLaC" marker.
+    let mut frame_offset = 4;
 
     many_metadata(&mut producer, |block| {
+      frame_offset += 4 + block.length() as u64;
+
       if let metadata::Data::StreamInfo(info) = block.data {
         stream_info = info;
       } else {
         metadata.push(block);
       }
     }).map(|_| {
+      let channels       = stream_info.channels as usize;
+      let max_block_size = stream_info.max_block_size as usize;
+
       Stream {
         info: stream_info,
         metadata: metadata,
         producer: producer,
+        frame_offset: frame_offset,
+        next_sample: 0,
+        skip_samples: 0,
+        byte_offset: frame_offset,
+        frame_number: 0,
+        frame_sample: 0,
+        last_error: None,
+        is_position_lost: false,
+        skipped_regions: Vec::new(),
+        is_resync_enabled: false,
+        subset_violations: Vec::new(),
+        is_subset_checked: false,
+        concealment: Concealment::Skip,
+        lost_samples: 0,
+        md5: None,
+        md5_sum: None,
+        md5_buffer: Vec::new(),
+        is_md5_enabled: false,
+        frame_buffer: Vec::new(),
+        scratch: Scratch::new(channels, max_block_size),
       }
     })
   }
 
+  /// Returns the error that stopped the last iteration over the stream.
+  ///
+  /// `Stream::iter` ends on an error the same way it does at the end of the
+  /// stream, this is how both cases can be told apart. The error is cleared
+  /// when seeking.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use flac::StreamReader;
+  /// use std::fs::File;
+  ///
+  /// match StreamReader::<File>::from_file("path/to/file.flac") {
+  ///   Ok(mut stream) => {
+  ///     let samples = stream.iter::<i16>().count();
+  ///
+  ///     if let Some(error) = stream.last_error() {
+  ///       println!("Stopped after {} samples: {:?}", samples, error);
+  ///     }
+  ///   }
+  ///   Err(error)     => println!("{:?}", error),
+  /// }
+  /// ```
+  #[inline]
+  pub fn last_error(&self) -> Option<DecodeError> {
+    self.last_error
+  }
+
+  /// Enables, or disables, skipping over frames that fail to decode.
+  ///
+  /// When enabled, a frame that fails to parse, or whose CRC doesn't
+  /// match, is skipped by scanning forward for the next sync code with a
+  /// valid frame header, and decoding continues from there. Each region
+  /// skipped over is kept in `Stream::skipped_regions`. Since samples are
+  /// missing afterwards, the MD5 signature can't be verified.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use flac::StreamReader;
+  /// use std::fs::File;
+  ///
+  /// match StreamReader::<File>::from_file("path/to/file.flac") {
+  ///   Ok(mut stream) => {
+  ///     stream.skip_corrupt_frames(true);
+  ///
+  ///     for sample in stream.iter::<i16>() {
+  ///       // Use the sample
+  ///     }
+  ///
+  ///     for region in stream.skipped_regions() {
+  ///       println!("Lost {} samples at byte {}", region.samples,
+  ///                                              region.byte_offset);
+  ///     }
+  ///   }
+  ///   Err(error)     => println!("{:?}", error),
+  /// }
+  /// ```
+  #[inline]
+  pub fn skip_corrupt_frames(&mut self, is_enabled: bool) {
+    self.is_resync_enabled = is_enabled;
+  }
+
+  /// Sets how the samples of frames that fail to decode get replaced.
+  ///
+  /// The default, `Concealment::Skip`, leaves them out. Anything else keeps
+  /// every sample after a corrupt frame where it belongs, so the number of
+  /// decoded samples still matches `StreamInfo::total_samples`. The number
+  /// of samples to fill in comes from the sample numbers in the headers
+  /// of the frames around a skipped region. Also enables
+  /// `Stream::skip_corrupt_frames`, unless it's `Concealment::Skip`.
+  ///
+  /// Frames decoded while ignoring their CRC-16 aren't skipped over, so
+  /// they don't show up in `Stream::skipped_regions`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use flac::{Concealment, StreamReader};
+  /// use std::fs::File;
+  ///
+  /// match StreamReader::<File>::from_file("path/to/file.flac") {
+  ///   Ok(mut stream) => {
+  ///     stream.conceal_lost_frames(Concealment::Silence);
+  ///
+  ///     for sample in stream.iter::<i16>() {
+  ///       // Samples of corrupt frames are zero
+  ///     }
+  ///   }
+  ///   Err(error)     => println!("{:?}", error),
+  /// }
+  /// ```
+  pub fn conceal_lost_frames(&mut self, concealment: Concealment) {
+    self.concealment = concealment;
+
+    if concealment != Concealment::Skip {
+      self.is_resync_enabled = true;
+    }
+  }
+
+  /// Returns every region skipped over so far because of a corrupt frame,
+  /// in the order they were found.
+  #[inline]
+  pub fn skipped_regions(&self) -> &[SkippedRegion] {
+    &self.skipped_regions
+  }
+
+  /// Enables, or disables, checking every frame against the streamable
+  /// subset.
+  ///
+  /// The streamable subset is the part of the format that a decoder can
+  /// start playing from any frame, without `StreamInfo`. Each rule a frame
+  /// breaks, out of its header and subframes, is kept in
+  /// `Stream::subset_violations`. Frames decoded by `Stream::parallel_blocks`
+  /// aren't checked.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use flac::StreamReader;
+  /// use std::fs::File;
+  ///
+  /// match StreamReader::<File>::from_file("path/to/file.flac") {
+  ///   Ok(mut stream) => {
+  ///     stream.check_subset(true);
+  ///
+  ///     for sample in stream.iter::<i16>() {
+  ///       // Use the sample
+  ///     }
+  ///
+  ///     for violation in stream.subset_violations() {
+  ///       println!("Frame {} breaks {:?}", violation.frame_number,
+  ///                                        violation.rule);
+  ///     }
+  ///   }
+  ///   Err(error)     => println!("{:?}", error),
+  /// }
+  /// ```
+  #[inline]
+  pub fn check_subset(&mut self, is_enabled: bool) {
+    self.is_subset_checked = is_enabled;
+  }
+
+  /// Returns every rule of the streamable subset broken by the frames
+  /// decoded so far, in the order they were found.
+  #[inline]
+  pub fn subset_violations(&self) -> &[SubsetViolation] {
+    &self.subset_violations
+  }
+
+  /// Enables, or disables, computing the MD5 signature of the decoded
+  /// samples.
+  ///
+  /// The signature is over the samples interleaved by channel, each one
+  /// sign extended to a whole number of bytes in little-endian order, the
+  /// same way the encoder computes it. Computing only starts when nothing
+  /// has been decoded yet or after seeking back to the first sample. See
+  /// `Stream::verify` for the result.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use flac::{StreamReader, Verification};
+  /// use std::fs::File;
+  ///
+  /// match StreamReader::<File>::from_file("path/to/file.flac") {
+  ///   Ok(mut stream) => {
+  ///     stream.compute_md5(true);
+  ///
+  ///     for sample in stream.iter::<i16>() {
+  ///       // Use the sample
+  ///     }
+  ///
+  ///     if stream.verify() == Verification::Mismatch {
+  ///       println!("Decoded samples don't match the signature");
+  ///     }
+  ///   }
+  ///   Err(error)     => println!("{:?}", error),
+  /// }
+  /// ```
+  pub fn compute_md5(&mut self, is_enabled: bool) {
+    let is_at_start = self.next_sample == 0 && self.skip_samples == 0 &&
+                      self.byte_offset == self.frame_offset;
+
+    self.is_md5_enabled = is_enabled;
+    self.md5_sum        = None;
+    self.md5            = if is_enabled && is_at_start {
+      Some(MD5::new())
+    } else {
+      None
+    };
+  }
+
+  /// Checks the MD5 signature of the decoded samples against the one
+  /// within `StreamInfo`.
+  ///
+  /// Only gives a `Verification::Match` or `Verification::Mismatch` once
+  /// `Stream::compute_md5` is enabled and the whole stream has been
+  /// decoded.
+  pub fn verify(&self) -> Verification {
+    if self.info.md5_sum == [0; 16] {
+      return Verification::Unset;
+    }
+
+    match self.md5_sum {
+      Some(md5_sum) if md5_sum == self.info.md5_sum => Verification::Match,
+      Some(_)                                       => {
+        Verification::Mismatch
+      }
+      None                                          => {
+        Verification::Unverified
+      }
+    }
+  }
+
+  /// Returns true when the decoded samples match the MD5 signature, same
+  /// as `Stream::verify` returning `Verification::Match`.
+  #[inline]
+  pub fn md5_matches(&self) -> bool {
+    self.verify() == Verification::Match
+  }
+
   /// Returns an iterator over the decoded samples.
+  ///
+  /// Iteration ends at the end of the stream or at the first error, see
+  /// `Stream::last_error` and `Stream::try_iter` for finding out which.
   #[inline]
   pub fn iter<S: SampleSize>(&mut self) -> Iter<P, S::Extended> {
-    let samples_left = self.info.total_samples;
-    let channels     = self.info.channels as usize;
-    let block_size   = self.info.max_block_size as usize;
-    let buffer_size  = block_size * channels;
-
     Iter {
+      inner: self.try_iter::<S>(),
+    }
+  }
+
+  /// Returns an iterator over the decoded samples that yields the error
+  /// that stopped decoding.
+  ///
+  /// After an `Err` is returned the iterator ends. A decoded sample that
+  /// doesn't fit within `S` is an error with the kind
+  /// `ErrorKind::InvalidSampleSize`.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use flac::StreamReader;
+  /// use std::fs::File;
+  ///
+  /// match StreamReader::<File>::from_file("path/to/file.flac") {
+  ///   Ok(mut stream) => {
+  ///     for result in stream.try_iter::<i16>() {
+  ///       match result {
+  ///         Ok(sample) => {
+  ///           // Use the sample
+  ///         }
+  ///         Err(error) => {
+  ///           println!("Corrupt frame {} at byte {}", error.frame_number,
+  ///                                                   error.byte_offset);
+  ///         }
+  ///       }
+  ///     }
+  ///   }
+  ///   Err(error)     => println!("{:?}", error),
+  /// }
+  /// ```
+  pub fn try_iter<S: SampleSize>(&mut self) -> TryIter<'_, P, S::Extended> {
+    let total_samples = self.info.total_samples;
+    let samples_left  = total_samples.saturating_sub(self.next_sample);
+
+    self.last_error = None;
+
+    TryIter {
       stream: self,
       channel: 0,
-      block_size: 0,
       sample_index: 0,
       samples_left: samples_left,
-      buffer: vec![S::Extended::from_i8(0); buffer_size]
+      is_finished: false,
+      block: Block::new(),
+      frame_number: 0,
+      byte_offset: 0,
+    }
+  }
+
+  /// Returns an iterator over the blocks of the stream, where the frames
+  /// get decoded on `threads` separate threads.
+  ///
+  /// Frames are split apart on the current thread, each one ending where
+  /// the next valid frame header begins, and handed off to be parsed and
+  /// decoded. Blocks still come out in the order of the stream, the same
+  /// as from `Stream::next_block`, and go into the MD5 signature. Zero
+  /// threads uses one for each available CPU.
+  ///
+  /// Corrupt frames end the iteration, they aren't skipped or concealed.
+  /// Frames read ahead are lost when the iterator is dropped early, after
+  /// which decoding fails with `ErrorKind::InvalidPosition` until a seek to
+  /// a known sample.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use flac::{StreamReader, Verification};
+  /// use std::fs::File;
+  ///
+  /// match StreamReader::<File>::from_file("path/to/file.flac") {
+  ///   Ok(mut stream) => {
+  ///     stream.compute_md5(true);
+  ///
+  ///     for result in stream.parallel_blocks::<i32>(4) {
+  ///       match result {
+  ///         Ok(block)  => {
+  ///           // Process the block
+  ///         }
+  ///         Err(error) => println!("{:?}", error),
+  ///       }
+  ///     }
+  ///
+  ///     if stream.verify() == Verification::Mismatch {
+  ///       println!("Decoded samples don't match the signature");
+  ///     }
+  ///   }
+  ///   Err(error)     => println!("{:?}", error),
+  /// }
+  /// ```
+  pub fn parallel_blocks<S>(&mut self, threads: usize)
+                            -> ParallelBlocks<'_, P, S>
+   where S: Sample + Send + 'static {
+    let threads = if threads > 0 {
+      threads
+    } else {
+      thread::available_parallelism().map(|count| count.get()).unwrap_or(1)
+    };
+
+    let (jobs, receiver)  = mpsc::channel();
+    let (sender, results) = mpsc::channel();
+    let receiver          = Arc::new(Mutex::new(receiver));
+    let mut workers       = Vec::with_capacity(threads);
+
+    for _ in 0..threads {
+      let info     = self.info;
+      let receiver = receiver.clone();
+      let sender   = sender.clone();
+
+      workers.push(thread::spawn(move || {
+        decode_jobs(info, receiver, sender)
+      }));
+    }
+
+    self.last_error = None;
+
+    ParallelBlocks {
+      stream: self,
+      jobs: Some(jobs),
+      results: results,
+      workers: workers,
+      finished: BTreeMap::new(),
+      positions: BTreeMap::new(),
+      next_index: 0,
+      job_index: 0,
+      max_jobs: threads as u64 * 4,
+      is_read: false,
+      is_finished: false,
     }
   }
 
-  fn next_frame<S>(&mut self, buffer: &mut [S]) -> Option<usize>
+  /// Decodes the next frame into `block`.
+  ///
+  /// Returns `Ok(true)` when a frame was decoded and `Ok(false)` at the end
+  /// of the stream. The buffer inside of `block` gets reused, so passing
+  /// the same `Block` for every call avoids any allocation after the first
+  /// one.
+  ///
+  /// # Failures
+  ///
+  /// * `DecodeError` is returned when the frame fails to decode, the same
+  ///   error is also available from `Stream::last_error`.
+  /// * `ErrorKind::InvalidSampleSize` is the kind of error when `S` can't
+  ///   hold one more bit than the stream's bits per sample.
+  /// * `ErrorKind::InvalidPosition` is the kind of error when frames read
+  ///   ahead by `Stream::parallel_blocks` were dropped since the last seek.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use flac::{Block, StreamReader};
+  /// use std::fs::File;
+  ///
+  /// match StreamReader::<File>::from_file("path/to/file.flac") {
+  ///   Ok(mut stream) => {
+  ///     let mut block = Block::<i32>::new();
+  ///
+  ///     while let Ok(true) = stream.next_block(&mut block) {
+  ///       for channel in 0..block.channels() {
+  ///         let samples = block.channel(channel);
+  ///
+  ///         // Process the samples of a single channel
+  ///       }
+  ///     }
+  ///   }
+  ///   Err(error)     => println!("{:?}", error),
+  /// }
+  /// ```
+  pub fn next_block<S>(&mut self, block: &mut Block<S>)
+                       -> Result<bool, DecodeError>
    where S: Sample {
-    let stream_info = &self.info;
+    let channels    = self.info.channels as usize;
+    let block_size  = self.info.max_block_size as usize;
+    let buffer_size = block_size * channels;
+
+    // Side channels need an extra bit on top of the bits per sample.
+    if self.info.bits_per_sample as usize >= S::size_extended() {
+      let error = DecodeError {
+        kind: ErrorKind::InvalidSampleSize,
+        frame_number: self.frame_number,
+        byte_offset: self.byte_offset,
+      };
+
+      self.last_error = Some(error);
+
+      return Err(error);
+    }
+
+    if block.buffer.len() < buffer_size {
+      block.buffer.resize(buffer_size, S::from_i8(0));
+    }
+
+    loop {
+      match try!(self.next_frame(&mut block.buffer)) {
+        Some(header) => {
+          block.header = header;
+
+          if self.add_block(block) {
+            return Ok(true);
+          }
+        }
+        None         => {
+          self.finish_md5();
+
+          return Ok(false);
+        }
+      }
+    }
+  }
+
+  // Account for the frame just decoded into `block`, returning false when
+  // the whole frame is before the sample a seek moved to.
+  fn add_block<S>(&mut self, block: &mut Block<S>) -> bool
+   where S: Sample {
+    let block_size = block.header.block_size as usize;
+
+    if self.skip_samples >= block_size {
+      self.skip_samples -= block_size;
+
+      return false;
+    }
+
+    block.offset = self.skip_samples;
+
+    self.next_sample  += (block_size - self.skip_samples) as u64;
+    self.skip_samples  = 0;
+
+    if let Some(ref mut md5) = self.md5 {
+      let bits_per_sample = self.info.bits_per_sample as usize;
+
+      update_md5(md5, &mut self.md5_buffer, block, bits_per_sample);
+    }
+
+    true
+  }
+
+  fn finish_md5(&mut self) {
+    if let Some(md5) = self.md5.take() {
+      self.md5_sum = Some(md5.finish());
+    }
+  }
+
+  /// Copies the bytes of the next frame into `bytes`, instead of decoding
+  /// the samples.
+  ///
+  /// Returns `Ok(true)` when a frame was copied and `Ok(false)` at the end
+  /// of the stream. The frame still gets parsed, so it's only copied when
+  /// both of its CRCs match. Since the samples aren't decoded, the MD5
+  /// signature can't be computed afterwards, and after a seek the whole
+  /// frame gets copied, including the samples before the one sought to.
+  ///
+  /// # Failures
+  ///
+  /// * `DecodeError` is returned when the frame fails to parse, the same
+  ///   error is also available from `Stream::last_error`.
+  /// * `ErrorKind::InvalidPosition` is the kind of error when frames read
+  ///   ahead by `Stream::parallel_blocks` were dropped since the last seek.
+  pub fn next_frame_bytes(&mut self, bytes: &mut Vec<u8>)
+                          -> Result<bool, DecodeError> {
+    let channels    = self.info.channels as usize;
+    let block_size  = self.info.max_block_size as usize;
+    let buffer_size = block_size * channels;
+
+    if self.is_position_lost {
+      return Err(self.decode_error(ErrorKind::InvalidPosition));
+    }
+
+    if self.frame_buffer.len() < buffer_size {
+      self.frame_buffer.resize(buffer_size, 0);
+    }
 
     loop {
-      match self.producer.parse(|i| frame_parser(i, stream_info, buffer)) {
-        Ok(frame)                => {
-          let channels   = frame.header.channels as usize;
-          let block_size = frame.header.block_size as usize;
-          let subframes  = frame.subframes[0..channels].iter();
-
-          for (channel, subframe) in subframes.enumerate() {
-            let start  = channel * block_size;
-            let end    = (channel + 1) * block_size;
-            let output = &mut buffer[start..end];
-
-            subframe::decode(&subframe, block_size, output);
+      let stream_info = &self.info;
+      let buffer      = &mut self.frame_buffer;
+      let scratch     = &mut self.scratch;
+      let result      = self.producer.parse(|i| {
+        let result = frame_parser_into(i, stream_info, scratch, buffer);
+
+        if let IResult::Done(rest, _) = result {
+          bytes.clear();
+          bytes.extend_from_slice(&i[0..(i.len() - rest.len())]);
+        }
+
+        result
+      });
+
+      let kind = match result {
+        Ok(_)                      => {
+          if self.is_subset_checked {
+            add_violations(&mut self.subset_violations, bytes,
+                           &scratch.frame, self.frame_number,
+                           self.byte_offset);
           }
 
-          frame::decode(frame.header.channel_assignment, buffer);
+          let header       = &scratch.frame.header;
+          let block_size   = header.block_size as usize;
+          let skip_samples = cmp::min(self.skip_samples, block_size);
 
-          return Some(block_size);
+          self.next_sample  += (block_size - skip_samples) as u64;
+          self.skip_samples  = 0;
+          self.byte_offset  += bytes.len() as u64;
+          self.frame_number += 1;
+          self.frame_sample  = header.first_sample(stream_info) +
+                               block_size as u64;
+          self.md5           = None;
+
+          return Ok(true);
         }
-        Err(ErrorKind::Continue) => continue,
-        Err(_)                   => return None,
+        Err(ErrorKind::Continue)   => continue,
+        Err(ErrorKind::EndOfInput) => return Ok(false),
+        Err(kind)                  => kind,
+      };
+
+      if !try!(self.resync(kind, false)) {
+        return Ok(false);
       }
     }
   }
-}
 
-/// An iterator over a reference of the decoded FLAC stream.
-pub struct Iter<'a, P, S>
- where P: 'a + StreamProducer,
-       S: Sample{
-  stream: &'a mut Stream<P>,
-  channel: usize,
-  block_size: usize,
-  sample_index: usize,
-  samples_left: u64,
-  buffer: Vec<S>,
-}
+  /// Writes the stream as native FLAC, with every metadata block and each
+  /// frame that hasn't been decoded yet.
+  ///
+  /// The frames are copied byte for byte, the same way as
+  /// `Stream::next_frame_bytes`, so a stream read from an Ogg container
+  /// can be moved back out of it without decoding it.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::IO(_)` is returned when writing fails.
+  /// * Every kind of error `Stream::next_frame_bytes` can return.
+  pub fn write_native<W: io::Write>(&mut self, writer: &mut W)
+                                    -> Result<(), ErrorKind> {
+    let to_error   = |e: io::Error| ErrorKind::IO(e.kind());
+    let blocks_len = self.metadata.len();
+    let info_block = Metadata::new(blocks_len == 0, 34,
+                                   metadata::Data::StreamInfo(self.info));
+    let mut bytes  = Vec::new();
 
-impl<'a, P, S> Iterator for Iter<'a, P, S>
- where P: StreamProducer,
-       S: Sample {
-  type Item = S::Normal;
+    bytes.extend_from_slice(b"fLaC");
 
-  fn next(&mut self) -> Option<Self::Item> {
-    if self.sample_index == self.block_size {
-      let buffer = &mut self.buffer;
+    try!(info_block.to_bytes(&mut bytes).map_err(to_error));
+
+    for (i, block) in self.metadata.iter().enumerate() {
+      let start = bytes.len();
+
+      try!(block.to_bytes(&mut bytes).map_err(to_error));
 
-      if let Some(block_size) = self.stream.next_frame(buffer) {
-        self.sample_index = 0;
-        self.block_size   = block_size;
+      // The flag from the block itself might not fit its new position.
+      if i + 1 == blocks_len {
+        bytes[start] |= 0x80;
       } else {
-        return None;
+        bytes[start] &= 0x7f;
       }
     }
 
-    let channels = self.stream.info.channels as usize;
-    let index    = self.sample_index + (self.channel * self.block_size);
-    let sample   = unsafe { *self.buffer.get_unchecked(index) };
+    try!(writer.write_all(&bytes).map_err(to_error));
 
-    self.channel += 1;
+    while try!(self.next_frame_bytes(&mut bytes).map_err(|e| e.kind)) {
+      try!(writer.write_all(&bytes).map_err(to_error));
+    }
 
-    // Reset current channel
-    if self.channel == channels {
-      self.channel       = 0;
-      self.sample_index += 1;
-      self.samples_left -= 1;
+    writer.flush().map_err(to_error)
+  }
+
+  fn next_frame<S>(&mut self, buffer: &mut [S])
+                   -> Result<Option<Header>, DecodeError>
+   where S: Sample {
+    let mut consumed     = 0;
+    let mut header_bytes = [0; 4];
+
+    if self.is_position_lost {
+      return Err(self.decode_error(ErrorKind::InvalidPosition));
     }
 
-    S::to_normal(sample)
+    loop {
+      if self.lost_samples > 0 {
+        return Ok(Some(self.silence(buffer)));
+      }
+
+      let is_concealed = self.concealment != Concealment::Skip;
+      let is_checked   = self.concealment != Concealment::IgnoreCRC;
+      let stream_info  = &self.info;
+      let scratch      = &mut self.scratch;
+      let result       = self.producer.parse(|i| {
+        let result = if is_checked {
+          frame_parser_into(i, stream_info, scratch, buffer)
+        } else {
+          frame_parser_unchecked(i, stream_info, scratch, buffer)
+        };
+
+        if let IResult::Done(rest, _) = result {
+          consumed = i.len() - rest.len();
+
+          // Only the codes in the header are needed for the subset.
+          header_bytes.copy_from_slice(&i[0..4]);
+        }
+
+        result
+      });
+
+      let kind = match result {
+        Ok(_)                      => {
+          let frame = &scratch.frame;
+
+          if self.is_subset_checked {
+            add_violations(&mut self.subset_violations, &header_bytes,
+                           frame, self.frame_number, self.byte_offset);
+          }
+
+          decode_frame(frame, buffer);
+
+          self.byte_offset  += consumed as u64;
+          self.frame_number += 1;
+          self.frame_sample  = frame.header.first_sample(stream_info) +
+                               frame.header.block_size as u64;
+
+          return Ok(Some(frame.header));
+        }
+        Err(ErrorKind::Continue)   => continue,
+        Err(ErrorKind::EndOfInput) => return Ok(None),
+        Err(kind)                  => kind,
+      };
+
+      // Concealing the region still has to happen when it runs to the end
+      // of the stream.
+      if !try!(self.resync(kind, is_concealed)) && self.lost_samples == 0 {
+        return Ok(None);
+      }
+    }
   }
 
-  fn size_hint(&self) -> (usize, Option<usize>) {
-    let samples_left = self.samples_left as usize;
-    let max_value    = usize::max_value() as u64;
+  // Fill `buffer` with a block of silence, of up to the maximum block size,
+  // taken from the samples lost within the last skipped region.
+  fn silence<S>(&mut self, buffer: &mut [S]) -> Header
+   where S: Sample {
+    let max_block_size = cmp::max(1, self.info.max_block_size as u64);
+    let block_size     = cmp::min(self.lost_samples, max_block_size);
+    let channels       = self.info.channels as usize;
+    let length         = block_size as usize * channels;
 
-    // There is a chance that samples_left will be larger than a usize since
-    // it is a u64. Make the upper bound None when it is.
-    if self.samples_left > max_value {
-      (samples_left, None)
-    } else {
-      (samples_left, Some(samples_left))
+    for sample in &mut buffer[0..length] {
+      *sample = S::from_i8(0);
+    }
+
+    let header = Header {
+      block_size: block_size as u32,
+      sample_rate: self.info.sample_rate,
+      channels: self.info.channels,
+      channel_assignment: ChannelAssignment::Independent,
+      bits_per_sample: self.info.bits_per_sample as usize,
+      number: NumberType::Sample(self.frame_sample),
+      crc: 0,
+    };
+
+    self.lost_samples -= block_size;
+    self.frame_sample += block_size;
+
+    header
+  }
+
+  // Skip past the frame that failed with `kind` to the next valid frame
+  // header, returning false when the end of the stream comes first. The
+  // error is returned as is when skipping isn't enabled or can't help.
+  // When `is_concealed` the lost samples are kept to be filled in, instead
+  // of moving the stream ahead of them.
+  fn resync(&mut self, kind: ErrorKind, is_concealed: bool)
+            -> Result<bool, DecodeError> {
+    if !self.is_resync_enabled || !is_recoverable(kind) {
+      return Err(self.decode_error(kind));
     }
+
+    // Move past the sync code of the corrupt frame before scanning.
+    let mut skipped = 1;
+    let mut header  = None;
+
+    match self.producer.parse(|i| IResult::Done(&i[1..], ())) {
+      Ok(_)                      => (),
+      Err(ErrorKind::EndOfInput) => skipped = 0,
+      Err(error)                 => return Err(self.decode_error(error)),
+    }
+
+    while skipped > 0 && header.is_none() {
+      let stream_info = &self.info;
+      let mut scanned = 0;
+      let result      = self.producer.parse(|i| {
+        let result = frame_sync(i, stream_info);
+
+        if let IResult::Done(rest, _) = result {
+          scanned = i.len() - rest.len();
+        }
+
+        result
+      });
+
+      match result {
+        Ok(frame_header)              => {
+          skipped += scanned as u64;
+          header   = frame_header;
+        }
+        Err(ErrorKind::Continue)      => continue,
+        // Whatever is left can't hold a frame header.
+        Err(ErrorKind::Incomplete(_)) => {
+          let rest = self.producer.parse(|i| {
+            IResult::Done(&i[i.len()..], i.len())
+          });
+
+          skipped += rest.unwrap_or(0) as u64;
+
+          break;
+        }
+        Err(ErrorKind::EndOfInput)    => break,
+        Err(error)                    => {
+          return Err(self.decode_error(error));
+        }
+      }
+    }
+
+    let sample_number = self.frame_sample;
+    let next_sample   = match header {
+      Some(ref header) => header.first_sample(&self.info),
+      None             => cmp::max(self.info.total_samples, sample_number),
+    };
+
+    self.skipped_regions.push(SkippedRegion {
+      kind: kind,
+      byte_offset: self.byte_offset,
+      byte_length: skipped,
+      sample_number: sample_number,
+      samples: next_sample.saturating_sub(sample_number),
+    });
+
+    let max_block_size = cmp::max(1, self.info.max_block_size as u64);
+
+    if is_concealed {
+      self.lost_samples = next_sample.saturating_sub(sample_number);
+    } else {
+      // A region skipped past the sample a seek moved to moves the start
+      // of the next block to the frame after it.
+      self.next_sample  = cmp::max(self.next_sample, next_sample);
+      self.skip_samples = (self.next_sample - next_sample) as usize;
+      self.frame_sample = next_sample;
+    }
+
+    self.byte_offset += skipped;
+    self.frame_number = next_sample / max_block_size;
+    self.md5          = None;
+
+    Ok(header.is_some())
+  }
+
+  // Keep `kind` as the last error, of the frame about to be decoded, and
+  // stop computing the MD5 signature since the samples are incomplete.
+  fn decode_error(&mut self, kind: ErrorKind) -> DecodeError {
+    let error = DecodeError {
+      kind: kind,
+      frame_number: self.frame_number,
+      byte_offset: self.byte_offset,
+    };
+
+    self.last_error = Some(error);
+    self.md5        = None;
+
+    error
+  }
+
+  // Reset the decoding state after the producer moved to the frame starting
+  // with sample `first`, at `byte_offset`, so the next decoded sample is
+  // `sample`.
+  fn moved_to(&mut self, sample: u64, first: u64, byte_offset: u64) {
+    let max_block_size = cmp::max(1, self.info.max_block_size as u64);
+
+    self.is_position_lost = false;
+
+    self.next_sample  = sample;
+    self.skip_samples = (sample - first) as usize;
+    self.byte_offset  = byte_offset;
+    self.frame_number = first / max_block_size;
+    self.frame_sample = first;
+    self.lost_samples = 0;
+    self.last_error   = None;
+    self.md5_sum      = None;
+    self.md5          = if self.is_md5_enabled && sample == 0 {
+      Some(MD5::new())
+    } else {
+      None
+    };
+  }
+}
+
+// Whether skipping to the next frame can get past an error of `kind`, which
+// is the case when the bytes of the frame itself are the problem.
+fn is_recoverable(kind: ErrorKind) -> bool {
+  match kind {
+    ErrorKind::IO(_)             |
+    ErrorKind::InvalidSampleSize |
+    ErrorKind::Continue          |
+    ErrorKind::EndOfInput        => false,
+    _                            => true,
+  }
+}
+
+// Add every rule of the streamable subset broken by `frame`, which got
+// parsed from `bytes`, to `violations`.
+fn add_violations(violations: &mut Vec<SubsetViolation>, bytes: &[u8],
+                  frame: &Frame, frame_number: u64, byte_offset: u64) {
+  check_subset(bytes, frame, |rule| {
+    violations.push(SubsetViolation {
+      rule: rule,
+      frame_number: frame_number,
+      byte_offset: byte_offset,
+    });
+  });
+}
+
+// Restore the samples of every channel from the parsed `frame`, where
+// `buffer` already holds the residuals.
+pub(crate) fn decode_frame<S>(frame: &Frame, buffer: &mut [S])
+ where S: Sample {
+  let channels   = frame.header.channels as usize;
+  let block_size = frame.header.block_size as usize;
+  let subframes  = frame.subframes[0..channels].iter();
+
+  for (channel, subframe) in subframes.enumerate() {
+    let start           = channel * block_size;
+    let end             = (channel + 1) * block_size;
+    let output          = &mut buffer[start..end];
+    let bits_per_sample = adjust_bits_per_sample(&frame.header, channel);
+
+    subframe::decode(&subframe, block_size, bits_per_sample, output);
+  }
+
+  // The buffer fits the largest block, which a frame can be shorter than.
+  frame::decode(frame.header.channel_assignment,
+                &mut buffer[0..(channels * block_size)]);
+}
+
+// Add the samples of `block` to the MD5 signature, interleaved by channel
+// with each sample being the smallest number of bytes that fit
+// `bits_per_sample`.
+fn update_md5<S>(md5: &mut MD5, buffer: &mut Vec<u8>, block: &Block<S>,
+                 bits_per_sample: usize)
+ where S: Sample {
+  let channels     = block.channels();
+  let block_size   = block.header.block_size as usize;
+  let sample_bytes = (bits_per_sample + 7) / 8;
+
+  buffer.clear();
+
+  for i in block.offset..block_size {
+    for channel in 0..channels {
+      let sample = S::to_i64(block.buffer[channel * block_size + i]);
+
+      for byte in 0..sample_bytes {
+        buffer.push((sample >> (byte * 8)) as u8);
+      }
+    }
+  }
+
+  md5.update(buffer);
+}
+
+impl<P> Stream<P> where P: SeekableProducer {
+  /// Moves the stream so the next decoded sample is `sample`.
+  ///
+  /// The frame containing `sample` is found with the seek points inside
+  /// `SeekTable`, when the stream has one, and a binary search over the
+  /// frame headers between them. Samples before `sample` inside of that
+  /// frame are discarded once it gets decoded.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::InvalidSeek` is returned when `sample` is past the end of
+  ///   the stream or the frame containing it can't be found.
+  /// * `ErrorKind::IO(_)` is returned when seeking within the producer
+  ///   fails.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use flac::StreamReader;
+  /// use std::fs::File;
+  ///
+  /// match StreamReader::<File>::from_file("path/to/file.flac") {
+  ///   Ok(mut stream) => {
+  ///     let sample_rate = stream.info().sample_rate as u64;
+  ///
+  ///     // Skip the first ten seconds.
+  ///     stream.seek(sample_rate * 10).unwrap();
+  ///
+  ///     for sample in stream.iter::<i16>() {
+  ///       // Starts at ten seconds into the stream
+  ///     }
+  ///   }
+  ///   Err(error)     => println!("{:?}", error),
+  /// }
+  /// ```
+  pub fn seek(&mut self, sample: u64) -> Result<(), ErrorKind> {
+    let total_samples = self.info.total_samples;
+
+    if total_samples > 0 && sample >= total_samples {
+      return Err(ErrorKind::InvalidSeek);
+    }
+
+    let length              = try!(self.producer.stream_len());
+    let (mut low, mut high) = self.seek_bounds(sample, length);
+    let mut offset          = low;
+
+    while low < high {
+      let middle = low + (high - low) / 2;
+
+      match try!(self.sync(middle)) {
+        Some((position, first, block_size)) if position < high => {
+          if first > sample {
+            high = middle;
+          } else {
+            offset = position;
+
+            if sample < first + block_size {
+              break;
+            }
+
+            low = position + 1;
+          }
+        }
+        _                                                      => {
+          high = middle;
+        }
+      }
+    }
+
+    // Walk forward until the frame that contains `sample`. Each following
+    // frame has to start right where the previous one ended, which also
+    // filters out any false sync codes.
+    let (mut start, mut first, mut block_size) =
+      match try!(self.sync(offset)) {
+        Some(frame) => frame,
+        None        => return Err(ErrorKind::InvalidSeek),
+      };
+    let mut position = start;
+
+    while sample >= first + block_size {
+      match try!(self.sync(position + 1)) {
+        Some((next_position, next_first, next_block_size)) => {
+          if next_first == first + block_size {
+            start      = next_position;
+            first      = next_first;
+            block_size = next_block_size;
+          } else if next_first > sample {
+            // The frame containing `sample` is corrupt, so decoding starts
+            // from the last frame before it.
+            break;
+          }
+
+          position = next_position;
+        }
+        None                                               => {
+          return Err(ErrorKind::InvalidSeek);
+        }
+      }
+    }
+
+    try!(self.producer.seek(start));
+
+    self.moved_to(sample, first, start);
+
+    Ok(())
+  }
+
+  // Byte range of where the frame containing `sample` can be, narrowed
+  // down by the closest seek points around it.
+  fn seek_bounds(&self, sample: u64, length: u64) -> (u64, u64) {
+    let mut low  = self.frame_offset;
+    let mut high = length;
+
+    for block in &self.metadata {
+      if let metadata::Data::SeekTable(ref seek_points) = block.data {
+        for seek_point in seek_points {
+          // Placeholder points don't refer to any frame.
+          if seek_point.sample_number == u64::max_value() {
+            continue;
+          }
+
+          let offset = self.frame_offset + seek_point.stream_offset;
+
+          if seek_point.sample_number <= sample {
+            low = cmp::max(low, offset);
+          } else {
+            high = cmp::min(high, offset + 1);
+          }
+        }
+      }
+    }
+
+    if low >= high {
+      (self.frame_offset, length)
+    } else {
+      (low, high)
+    }
+  }
+
+  // Find the first valid frame header at or after `offset`. The result is
+  // where the frame starts, the number of its first sample, and its block
+  // size.
+  fn sync(&mut self, offset: u64)
+          -> Result<Option<(u64, u64, u64)>, ErrorKind> {
+    try!(self.producer.seek(offset));
+
+    let stream_info = &self.info;
+
+    loop {
+      match self.producer.parse(|i| frame_sync(i, stream_info)) {
+        Ok(Some(header))             => {
+          let first = header.first_sample(stream_info);
+
+          return Ok(Some((self.producer.position(), first,
+                          header.block_size as u64)));
+        }
+        Ok(None)                     |
+        Err(ErrorKind::Continue)     => continue,
+        Err(ErrorKind::EndOfInput)   |
+        Err(ErrorKind::Incomplete(_)) => return Ok(None),
+        Err(error)                   => return Err(error),
+      }
+    }
+  }
+}
+
+impl<R> Stream<OggReader<R>> where R: io::Read + io::Seek {
+  /// Moves the stream so the next decoded sample is `sample`.
+  ///
+  /// The page to start decoding from is found with a binary search over
+  /// the granule positions of the Ogg pages. Frames, and samples within a
+  /// frame, before `sample` are discarded once they get decoded.
+  ///
+  /// After seeking, `DecodeError::byte_offset` counts from the start of
+  /// the Ogg page decoding started at.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::InvalidSeek` is returned when `sample` is past the end of
+  ///   the stream.
+  /// * `ErrorKind::IO(_)` is returned when seeking within the reader fails.
+  pub fn seek(&mut self, sample: u64) -> Result<(), ErrorKind> {
+    let total_samples = self.info.total_samples;
+
+    if total_samples > 0 && sample >= total_samples {
+      return Err(ErrorKind::InvalidSeek);
+    }
+
+    let first = try!(self.producer.seek_granule(sample));
+
+    self.moved_to(sample, first, 0);
+
+    Ok(())
+  }
+}
+
+// Bytes of a frame to decode on a worker thread, along with where it is
+// within the stream for reporting errors.
+struct Job {
+  index: u64,
+  bytes: Vec<u8>,
+  frame_number: u64,
+  byte_offset: u64,
+}
+
+type JobResult<S> = (u64, Result<Block<S>, DecodeError>);
+
+// Decode the frames handed off to a worker thread until there are no more.
+fn decode_jobs<S>(info: StreamInfo, jobs: Arc<Mutex<mpsc::Receiver<Job>>>,
+                  results: mpsc::Sender<JobResult<S>>)
+ where S: Sample {
+  let channels    = info.channels as usize;
+  let block_size  = info.max_block_size as usize;
+  let buffer_size = block_size * channels;
+  let mut scratch = Scratch::new(channels, block_size);
+
+  loop {
+    let job = match jobs.lock().ok().and_then(|jobs| jobs.recv().ok()) {
+      Some(job) => job,
+      None      => break,
+    };
+
+    let mut block    = Block::new();
+    let mut producer = ByteStream::new(&job.bytes);
+
+    block.buffer.resize(buffer_size, S::from_i8(0));
+
+    // A panic while decoding a frame gets reported as an error instead of
+    // stopping the thread, which would leave the iterator waiting on it.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+      let buffer  = &mut block.buffer;
+      let scratch = &mut scratch;
+
+      producer.parse(|i| frame_parser_into(i, &info, scratch, buffer))
+        .map(|_| {
+          decode_frame(&scratch.frame, buffer);
+
+          scratch.frame.header
+        })
+    }));
+
+    let result = match result {
+      Ok(result) => result,
+      Err(_)     => {
+        scratch = Scratch::new(channels, block_size);
+
+        Err(ErrorKind::Unknown)
+      }
+    };
+
+    let result = match result {
+      Ok(header) => {
+        block.header = header;
+
+        Ok(block)
+      }
+      Err(kind)  => {
+        Err(DecodeError {
+          kind: kind,
+          frame_number: job.frame_number,
+          byte_offset: job.byte_offset,
+        })
+      }
+    };
+
+    if results.send((job.index, result)).is_err() {
+      break;
+    }
+  }
+}
+
+/// An iterator over the blocks of a FLAC stream, decoded on multiple
+/// threads.
+///
+/// The threads stop once the iterator is dropped.
+pub struct ParallelBlocks<'a, P, S>
+ where P: 'a + StreamProducer,
+       S: Sample {
+  stream: &'a mut Stream<P>,
+  jobs: Option<mpsc::Sender<Job>>,
+  results: mpsc::Receiver<JobResult<S>>,
+  workers: Vec<thread::JoinHandle<()>>,
+  // Blocks that finished decoding before the ones ahead of them did.
+  finished: BTreeMap<u64, Result<Block<S>, DecodeError>>,
+  // Frame number and byte offset of the frames handed off that haven't
+  // been returned yet.
+  positions: BTreeMap<u64, (u64, u64)>,
+  // Index of the next block to return and of the next frame handed off,
+  // with the most frames handed off at once.
+  next_index: u64,
+  job_index: u64,
+  max_jobs: u64,
+  is_read: bool,
+  is_finished: bool,
+}
+
+impl<'a, P, S> ParallelBlocks<'a, P, S>
+ where P: StreamProducer,
+       S: Sample {
+  // Split the next frame off of the stream, returning `None` at the end of
+  // the stream.
+  fn read_frame(&mut self) -> Result<Option<Vec<u8>>, ErrorKind> {
+    let stream = &mut *self.stream;
+
+    loop {
+      let stream_info = &stream.info;
+      let result      = stream.producer.parse(|i| {
+        match frame_span(i, stream_info) {
+          IResult::Done(rest, bytes) => IResult::Done(rest, bytes.to_vec()),
+          IResult::Error(error)      => IResult::Error(error),
+          IResult::Incomplete(n)     => IResult::Incomplete(n),
+        }
+      });
+
+      match result {
+        Ok(bytes)                     => return Ok(Some(bytes)),
+        Err(ErrorKind::Continue)      => continue,
+        Err(ErrorKind::EndOfInput)    => return Ok(None),
+        // Without another frame after it, the rest is the last frame.
+        Err(ErrorKind::Incomplete(_)) => {
+          return stream.producer.parse(|i| {
+            IResult::Done(&i[i.len()..], i.to_vec())
+          }).map(Some);
+        }
+        Err(kind)                     => return Err(kind),
+      }
+    }
+  }
+
+  // Hand off frames until there are enough being decoded, or there are no
+  // frames left.
+  fn add_jobs(&mut self) {
+    while !self.is_read && self.job_index - self.next_index < self.max_jobs {
+      let frame_number = self.stream.frame_number;
+      let byte_offset  = self.stream.byte_offset;
+      let index        = self.job_index;
+
+      match self.read_frame() {
+        Ok(Some(bytes)) => {
+          self.stream.byte_offset  += bytes.len() as u64;
+          self.stream.frame_number += 1;
+
+          self.positions.insert(index, (frame_number, byte_offset));
+
+          let job = Job {
+            index: index,
+            bytes: bytes,
+            frame_number: frame_number,
+            byte_offset: byte_offset,
+          };
+
+          if let Some(ref jobs) = self.jobs {
+            let _ = jobs.send(job);
+          }
+        }
+        Ok(None)        => {
+          self.is_read = true;
+
+          break;
+        }
+        // The error comes out after every block before it.
+        Err(kind)       => {
+          let error = DecodeError {
+            kind: kind,
+            frame_number: frame_number,
+            byte_offset: byte_offset,
+          };
+
+          self.finished.insert(index, Err(error));
+
+          self.is_read = true;
+        }
+      }
+
+      self.job_index += 1;
+    }
+  }
+}
+
+impl<'a, P, S> Iterator for ParallelBlocks<'a, P, S>
+ where P: StreamProducer,
+       S: Sample {
+  type Item = Result<Block<S>, DecodeError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let bits_per_sample = self.stream.info.bits_per_sample as usize;
+
+    if !self.is_finished && bits_per_sample >= S::size_extended() {
+      self.is_finished = true;
+
+      return Some(Err(self.stream.decode_error(
+                        ErrorKind::InvalidSampleSize)));
+    }
+
+    if !self.is_finished && self.stream.is_position_lost {
+      self.is_finished = true;
+
+      return Some(Err(self.stream.decode_error(
+                        ErrorKind::InvalidPosition)));
+    }
+
+    while !self.is_finished {
+      self.add_jobs();
+
+      if self.next_index == self.job_index {
+        self.is_finished = true;
+
+        self.stream.finish_md5();
+
+        break;
+      }
+
+      let result = match self.finished.remove(&self.next_index) {
+        Some(result) => result,
+        None         => {
+          match self.results.recv() {
+            Ok((index, result)) => {
+              self.finished.insert(index, result);
+
+              continue;
+            }
+            // Every worker thread stopped before finishing.
+            Err(_)              => {
+              let (frame_number, byte_offset) =
+                self.positions[&self.next_index];
+
+              let error = DecodeError {
+                kind: ErrorKind::Unknown,
+                frame_number: frame_number,
+                byte_offset: byte_offset,
+              };
+
+              self.is_finished = true;
+
+              self.stream.last_error = Some(error);
+              self.stream.md5        = None;
+
+              return Some(Err(error));
+            }
+          }
+        }
+      };
+
+      self.positions.remove(&self.next_index);
+      self.next_index += 1;
+
+      match result {
+        Ok(mut block) => {
+          let first = block.header.first_sample(&self.stream.info);
+
+          self.stream.frame_sample = first + block.header.block_size as u64;
+
+          if self.stream.add_block(&mut block) {
+            return Some(Ok(block));
+          }
+        }
+        Err(error)    => {
+          self.is_finished = true;
+
+          self.stream.last_error = Some(error);
+          self.stream.md5        = None;
+
+          return Some(Err(error));
+        }
+      }
+    }
+
+    None
+  }
+}
+
+impl<'a, P, S> Drop for ParallelBlocks<'a, P, S>
+ where P: StreamProducer,
+       S: Sample {
+  fn drop(&mut self) {
+    // Without any more jobs the worker threads stop.
+    self.jobs = None;
+
+    for worker in self.workers.drain(..) {
+      let _ = worker.join();
+    }
+
+    // The producer is past frames that never got handed out.
+    if self.job_index > self.next_index {
+      self.stream.is_position_lost = true;
+      self.stream.md5              = None;
+    }
+  }
+}
+
+/// An iterator over a reference of the decoded FLAC stream.
+pub struct Iter<'a, P, S>
+ where P: 'a + StreamProducer,
+       S: Sample {
+  inner: TryIter<'a, P, S>,
+}
+
+impl<'a, P, S> Iterator for Iter<'a, P, S>
+ where P: StreamProducer,
+       S: Sample {
+  type Item = S::Normal;
+
+  #[inline]
+  fn next(&mut self) -> Option<Self::Item> {
+    match self.inner.next() {
+      Some(Ok(sample)) => Some(sample),
+      _                => None,
+    }
+  }
+
+  #[inline]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.inner.size_hint()
+  }
+}
+
+/// An iterator over a reference of the decoded FLAC stream that yields
+/// decoding errors.
+pub struct TryIter<'a, P, S>
+ where P: 'a + StreamProducer,
+       S: Sample {
+  stream: &'a mut Stream<P>,
+  channel: usize,
+  sample_index: usize,
+  samples_left: u64,
+  is_finished: bool,
+  block: Block<S>,
+  // Number and byte offset of the frame `block` was decoded from.
+  frame_number: u64,
+  byte_offset: u64,
+}
+
+impl<'a, P, S> Iterator for TryIter<'a, P, S>
+ where P: StreamProducer,
+       S: Sample {
+  type Item = Result<S::Normal, DecodeError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.sample_index == self.block.len() {
+      if self.is_finished {
+        return None;
+      }
+
+      let regions_len  = self.stream.skipped_regions.len();
+      self.byte_offset = self.stream.byte_offset;
+
+      match self.stream.next_block(&mut self.block) {
+        Ok(true)   => self.sample_index = 0,
+        Ok(false)  => {
+          self.is_finished = true;
+
+          return None;
+        }
+        Err(error) => {
+          self.is_finished = true;
+
+          return Some(Err(error));
+        }
+      }
+
+      // A corrupt frame skipped over moves the frame to the end of the
+      // skipped region.
+      if let Some(region) = self.stream.skipped_regions.get(regions_len) {
+        self.byte_offset = region.byte_offset + region.byte_length;
+      }
+
+      self.frame_number = self.stream.frame_number.saturating_sub(1);
+    }
+
+    let channels   = self.block.channels();
+    let block_size = self.block.header.block_size as usize;
+    let index      = self.block.offset + self.sample_index +
+                     (self.channel * block_size);
+    let sample     = unsafe { *self.block.buffer.get_unchecked(index) };
+
+    self.channel += 1;
+
+    // Reset current channel
+    if self.channel == channels {
+      self.channel       = 0;
+      self.sample_index += 1;
+      self.samples_left  = self.samples_left.saturating_sub(1);
+    }
+
+    match S::to_normal(sample) {
+      Some(sample) => Some(Ok(sample)),
+      None         => {
+        let error = DecodeError {
+          kind: ErrorKind::InvalidSampleSize,
+          frame_number: self.frame_number,
+          byte_offset: self.byte_offset,
+        };
+
+        self.stream.last_error = Some(error);
+        self.is_finished       = true;
+        self.sample_index      = self.block.len();
+
+        Some(Err(error))
+      }
+    }
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let samples_left = self.samples_left as usize;
+    let max_value    = usize::max_value() as u64;
+
+    // There is a chance that samples_left will be larger than a usize since
+    // it is a u64. Make the upper bound None when it is.
+    if self.samples_left > max_value {
+      (samples_left, None)
+    } else {
+      (samples_left, Some(samples_left))
+    }
+  }
+}
+
+/// A single decoded frame with a buffer for each channel.
+///
+/// The sample type needs room for one more bit than the stream's bits per
+/// sample, because of the side channel. So `i32` works for streams up to 31
+/// bits per sample and 32 bit streams need an `i64`.
+pub struct Block<S: Sample> {
+  pub(crate) header: Header,
+  // Samples, per channel, skipped at the start of the frame after a seek.
+  pub(crate) offset: usize,
+  pub(crate) buffer: Vec<S>,
+}
+
+impl<S> Block<S> where S: Sample {
+  /// Constructs an empty `Block`, the buffer gets allocated once the first
+  /// frame is decoded into it.
+  pub fn new() -> Self {
+    Block {
+      header: Header::default(),
+      offset: 0,
+      buffer: Vec::new(),
+    }
+  }
+
+  /// Returns the header of the decoded frame.
+  ///
+  /// `block_size` within the header is the size of the entire frame, which
+  /// only differs from `Block::len` for the first block after a seek.
+  #[inline]
+  pub fn header(&self) -> &Header {
+    &self.header
+  }
+
+  /// Returns the number of channels.
+  #[inline]
+  pub fn channels(&self) -> usize {
+    self.header.channels as usize
+  }
+
+  /// Returns the number of samples, per channel, inside the block.
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.header.block_size as usize - self.offset
+  }
+
+  /// Returns true if there are no samples inside the block.
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Returns the samples of a single channel.
+  ///
+  /// # Panics
+  ///
+  /// Panics when `channel` isn't less than `Block::channels`.
+  pub fn channel(&self, channel: usize) -> &[S] {
+    assert!(channel < self.channels());
+
+    let block_size = self.header.block_size as usize;
+    let start      = channel * block_size;
+
+    &self.buffer[(start + self.offset)..(start + block_size)]
   }
 }
 
@@ -1,4 +1,4 @@
-use subframe::{self, Subframe, MAX_FIXED_ORDER, MAX_LPC_ORDER};
+use subframe::{self, kernel, Subframe, MAX_FIXED_ORDER, MAX_LPC_ORDER};
 use utility::Sample;
 
 // Restore the original signal from a fixed linear prediction.
@@ -9,12 +9,24 @@ use utility::Sample;
 // `MAX_FIXED_ORDER`, which is 4.
 //
 // This function also assumes that `output` already has the warm up values
-// from the `Fixed` subframe in it.
+// from the `Fixed` subframe in it. The prediction is summed up as an `i64`,
+// so side channels of 32 bit streams can't overflow no matter the size of
+// `S`. Samples of `i32` and `i64` go through the kernels instead, which
+// only sum up as an `i32` when `bits_per_sample` keeps it from overflowing.
 pub fn fixed_restore_signal<S: Sample>(order: usize,
                                        block_size: usize,
+                                       bits_per_sample: usize,
                                        output: &mut [S]) {
   debug_assert!(order <= MAX_FIXED_ORDER);
 
+  if let Some(output) = S::as_i32_slice(&mut output[0..block_size]) {
+    return kernel::fixed_i32(order, bits_per_sample, output);
+  }
+
+  if let Some(output) = S::as_i64_slice(&mut output[0..block_size]) {
+    return kernel::fixed_i64(order, output);
+  }
+
   let polynomial = [ &[][..]
                    , &[1][..]
                    , &[-1, 2][..]
@@ -26,15 +38,13 @@ pub fn fixed_restore_signal<S: Sample>(order: usize,
   let length       = block_size - order;
 
   for i in 0..length {
-    let zero       = S::from_i8(0);
     let offset     = i + order;
     let prediction = coefficients.iter()
                       .zip(&output[i..offset])
-                      .fold(zero, |result, (coefficient, signal)|
-                         result + S::from_i32_lossy(*coefficient) * *signal);
-
+                      .fold(0, |result, (coefficient, signal)|
+                         result + (*coefficient as i64) * S::to_i64(*signal));
 
-    output[offset] += prediction;
+    output[offset] += S::from_i64_lossy(prediction);
   }
 }
 
@@ -53,8 +63,13 @@ pub fn fixed_restore_signal<S: Sample>(order: usize,
 // length is assumed to be the value of order. And the max order is
 // `MAX_LPC_ORDER`, which is 32. This function also assumes that `output`
 // already has the warm up values from the `LPC` subframe in it.
+//
+// Like the fixed version, the prediction is summed up as an `i64` because
+// a 24 bit sample with a 15 bit coefficient is already past what an `i32`
+// can hold, and samples of `i32` and `i64` go through the kernels.
 pub fn lpc_restore_signal<S: Sample>(quantization_level: i8,
                                      block_size: usize,
+                                     bits_per_sample: usize,
                                      coefficients: &[i32],
                                      output: &mut [S]) {
   let order  = coefficients.len();
@@ -62,15 +77,31 @@ pub fn lpc_restore_signal<S: Sample>(quantization_level: i8,
 
   debug_assert!(order <= MAX_LPC_ORDER);
 
+  // A negative shift isn't valid, so it's left to the generic version.
+  if quantization_level >= 0 {
+    let mut taps = [0; MAX_LPC_ORDER];
+    let shift    = quantization_level as u32;
+
+    kernel::lpc_taps(coefficients, &mut taps);
+
+    if let Some(output) = S::as_i32_slice(&mut output[0..block_size]) {
+      return kernel::lpc_i32(&taps[0..order], shift, bits_per_sample,
+                             output);
+    }
+
+    if let Some(output) = S::as_i64_slice(&mut output[0..block_size]) {
+      return kernel::lpc_i64(&taps[0..order], shift, output);
+    }
+  }
+
   for i in 0..length {
-    let zero       = S::from_i8(0);
     let offset     = i + order;
     let prediction = coefficients.iter().rev()
                        .zip(&output[i..offset])
-                       .fold(zero, |result, (coefficient, signal)|
-                         result + S::from_i32_lossy(*coefficient) * *signal);
+                       .fold(0, |result, (coefficient, signal)|
+                         result + (*coefficient as i64) * S::to_i64(*signal));
 
-    output[offset] += prediction >> quantization_level;
+    output[offset] += S::from_i64_lossy(prediction >> quantization_level);
   }
 }
 
@@ -84,11 +115,15 @@ pub fn lpc_restore_signal<S: Sample>(quantization_level: i8,
 ///   the result into `output`.
 /// * `LPC` - restore the signal of the finite impulse response linear
 ///   prediction and put the result into `output`.
-pub fn decode<S>(subframe: &Subframe, block_size: usize, output: &mut [S])
+///
+/// `bits_per_sample` is the size of the samples within the subframe, which
+/// is one more than the frame's for side channels.
+pub fn decode<S>(subframe: &Subframe, block_size: usize,
+                 bits_per_sample: usize, output: &mut [S])
  where S: Sample {
   match subframe.data {
     subframe::Data::Constant(constant)     => {
-      let _constant = S::from_i32_lossy(constant);
+      let _constant = S::from_i64_lossy(constant);
 
       for i in 0..output.len() {
         output[i] = _constant
@@ -96,32 +131,32 @@ pub fn decode<S>(subframe: &Subframe, block_size: usize, output: &mut [S])
     }
     subframe::Data::Verbatim(ref verbatim) => {
       for i in 0..verbatim.len() {
-        output[i] = S::from_i32_lossy(verbatim[i]);
+        output[i] = S::from_i64_lossy(verbatim[i]);
       }
     }
     subframe::Data::Fixed(ref fixed)       => {
       let order = fixed.order as usize;
 
       for i in 0..order {
-        let warmup = S::from_i32_lossy(fixed.warmup[i]);
+        let warmup = S::from_i64_lossy(fixed.warmup[i]);
 
         output[i] = warmup;
       }
 
-      fixed_restore_signal(order, block_size, output);
+      fixed_restore_signal(order, block_size, bits_per_sample, output);
     }
     subframe::Data::LPC(ref lpc)           => {
       let order        = lpc.order as usize;
       let coefficients = &lpc.qlp_coefficients[0..order];
 
       for i in 0..order {
-        let warmup = S::from_i32_lossy(lpc.warmup[i]);
+        let warmup = S::from_i64_lossy(lpc.warmup[i]);
 
         output[i] = warmup;
       }
 
-      lpc_restore_signal(lpc.quantization_level, block_size, coefficients,
-                         output);
+      lpc_restore_signal(lpc.quantization_level, block_size,
+                         bits_per_sample, coefficients, output);
     }
   }
 
@@ -150,8 +185,8 @@ mod tests {
                       , &mut [21877, 27482, -6513][..]
                       ];
 
-    fixed_restore_signal(3, 16, &mut outputs[0]);
-    fixed_restore_signal(2, 3, &mut outputs[1]);
+    fixed_restore_signal(3, 16, 16, &mut outputs[0]);
+    fixed_restore_signal(2, 3, 16, &mut outputs[1]);
 
     assert_eq!(&outputs[0], &[-729, -722, -667, -583, -486, -359, -225, -91
                              , 59, 209, 354, 497, 630, 740, 812, 845]);
@@ -169,8 +204,8 @@ mod tests {
                              ,-30017, 3157][..]
                       ];
 
-    lpc_restore_signal(9, 16, &coefficients[0], &mut outputs[0]);
-    lpc_restore_signal(10, 8, &coefficients[1], &mut outputs[1]);
+    lpc_restore_signal(9, 16, 16, &coefficients[0], &mut outputs[0]);
+    lpc_restore_signal(10, 8, 16, &coefficients[1], &mut outputs[1]);
 
     assert_eq!(&outputs[0], &[-796, -547, -285, -32, 199, 443, 670, 875
                              , 1046, 1208, 1343, 1454, 1541, 1616, 1663
@@ -179,6 +214,18 @@ mod tests {
                              , -30017, -29718]);
   }
 
+  #[test]
+  fn test_wide_restore_signal() {
+    let mut fixed_output: [i32; 4] = [8000000, 8100000, 5, 0];
+    let mut lpc_output: [i32; 4]   = [8000000, 8100000, 5, 0];
+
+    fixed_restore_signal(2, 4, 24, &mut fixed_output);
+    lpc_restore_signal(12, 4, 24, &[8192, -4096], &mut lpc_output);
+
+    assert_eq!(&fixed_output, &[8000000, 8100000, 8200005, 8300010]);
+    assert_eq!(&lpc_output, &[8000000, 8100000, 8200005, 8300010]);
+  }
+
   #[test]
   fn test_decode() {
     let mut output = [0; 16];
@@ -239,10 +286,10 @@ mod tests {
       wasted_bits: 0,
     };
 
-    decode(&constant, 16, &mut output);
+    decode(&constant, 16, 16, &mut output);
     assert_eq!(&output, &[4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4]);
 
-    decode(&verbatim, 16, &mut output);
+    decode(&verbatim, 16, 16, &mut output);
     assert_eq!(&output, &[16, -3, 55, 49, -32, 6, 40 , -90, 1, 0, 77, -12, 84
                          ,10 , -112, 136]);
 
@@ -254,7 +301,7 @@ mod tests {
         output[i + 3] = residual[i];
       }
 
-      decode(&fixed, 16, &mut output);
+      decode(&fixed, 16, 16, &mut output);
       assert_eq!(&output, &[-729, -722, -667, -583, -486, -359, -225, -91, 59
                            ,209, 354, 497, 630, 740, 812, 845]);
     }
@@ -266,7 +313,7 @@ mod tests {
         output[i + 7] = residual[i];
       }
 
-      decode(&lpc, 16, &mut output);
+      decode(&lpc, 16, 16, &mut output);
       assert_eq!(&output, &[-796, -547, -285, -32, 199, 443, 670, 875, 1046
                            ,1208, 1343, 1454, 1541, 1616, 1663, 1701]);
     }
@@ -281,7 +328,7 @@ mod tests {
       wasted_bits: 10,
     };
 
-    decode(&constant, 4, &mut output);
+    decode(&constant, 4, 16, &mut output);
     assert_eq!(&output, &[1024, 1024, 1024, 1024]);
   }
 }
@@ -0,0 +1,923 @@
+use std::cmp;
+use std::ops::Range;
+
+use subframe::{
+  self, Subframe, MAX_FIXED_ORDER, MAX_LPC_ORDER,
+  EntropyCodingMethod, CodingMethod, PartitionedRice,
+  PartitionedRiceContents,
+};
+use subframe::lpc::{self, Apodization};
+use utility::BitWriter;
+
+// Largest Rice parameters for each coding method, the next value up is
+// reserved as the escape code.
+const MAX_RICE_PARAMETER: u32  = 14;
+const MAX_RICE2_PARAMETER: u32 = 30;
+
+// Largest number of raw bits for an escaped partition, which is written
+// with five bits.
+const MAX_RAW_BITS: u32 = 31;
+
+// Largest partition order that fits into the four bits of the residual.
+const MAX_PARTITION_ORDER: u32 = 15;
+
+/// Settings for the search of the smallest subframe.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+  /// Largest order tried for `LPC` subframes, zero leaves them out.
+  pub max_lpc_order: usize,
+  /// Precision, in bits, of the quantized `LPC` coefficients, up to 15.
+  /// Zero picks one based on the bits per sample and block size.
+  pub qlp_coeff_precision: u8,
+  /// Windows applied to the samples before the `LPC` analysis, where the
+  /// coefficients from each window get tried.
+  pub apodizations: Vec<Apodization>,
+  /// Try every `LPC` order, instead of only the one estimated to result in
+  /// the fewest bits.
+  pub exhaustive_model_search: bool,
+  /// Smallest Rice partition order that gets tried.
+  pub min_partition_order: u32,
+  /// Largest Rice partition order that gets tried, up to 15. It gets
+  /// lowered for residuals that can't be split into that many partitions.
+  pub max_partition_order: u32,
+}
+
+impl Default for Config {
+  /// Constructs the same settings as the default compression level of the
+  /// reference encoder, `LPC` up to order eight with a Tukey window and
+  /// up to 32 Rice partitions.
+  fn default() -> Self {
+    Config {
+      max_lpc_order: 8,
+      qlp_coeff_precision: 0,
+      apodizations: vec![Apodization::Tukey(0.5)],
+      exhaustive_model_search: false,
+      min_partition_order: 0,
+      max_partition_order: 5,
+    }
+  }
+}
+
+// Number of trailing zero bits that every sample has in common.
+//
+// When all the samples are zero there is nothing to be gained from wasted
+// bits, so zero gets returned.
+pub fn wasted_bits(samples: &[i32]) -> u32 {
+  let combined = samples.iter().fold(0, |result, sample| result | *sample);
+
+  if combined == 0 {
+    0
+  } else {
+    combined.trailing_zeros()
+  }
+}
+
+// Compute the residual of a fixed linear predictor of the given order.
+//
+// The residual gets computed with a wider integer so overflow can be
+// detected, when any value doesn't fit inside of an `i32` the function
+// returns false and this order shouldn't be used.
+pub fn fixed_residual(order: usize, samples: &[i32], residual: &mut Vec<i32>)
+                      -> bool {
+  debug_assert!(order <= MAX_FIXED_ORDER);
+
+  residual.clear();
+
+  for i in order..samples.len() {
+    let s = |offset: usize| samples[i - offset] as i64;
+
+    let value = match order {
+      0 => s(0),
+      1 => s(0) - s(1),
+      2 => s(0) - 2 * s(1) + s(2),
+      3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+      _ => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+    };
+
+    if value < i32::min_value() as i64 || value > i32::max_value() as i64 {
+      return false;
+    }
+
+    residual.push(value as i32);
+  }
+
+  true
+}
+
+// Rough number of bits a channel takes, based on the residual of the best
+// fixed predictor, for comparing channels without encoding them.
+//
+// The sum of the absolute residual gets turned into bits the same way the
+// reference encoder estimates the size of a Rice coded residual.
+pub fn estimate_bits(samples: &[i32]) -> u64 {
+  let mut sums = [0; MAX_FIXED_ORDER + 1];
+  let length   = samples.len().saturating_sub(MAX_FIXED_ORDER);
+
+  for i in MAX_FIXED_ORDER..samples.len() {
+    let s = |offset: usize| samples[i - offset] as i64;
+
+    sums[0] += s(0).abs() as u64;
+    sums[1] += (s(0) - s(1)).abs() as u64;
+    sums[2] += (s(0) - 2 * s(1) + s(2)).abs() as u64;
+    sums[3] += (s(0) - 3 * s(1) + 3 * s(2) - s(3)).abs() as u64;
+    sums[4] += (s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4)).abs() as u64;
+  }
+
+  let sum = *sums.iter().min().unwrap();
+
+  if sum == 0 {
+    return 0;
+  }
+
+  let mean = ::std::f64::consts::LN_2 * sum as f64 / length as f64;
+  let bits = if mean > 1.0 { mean.log2() } else { 0.0 };
+
+  (bits * length as f64) as u64
+}
+
+// Number of bits needed to Rice code the folded values with a specific
+// parameter.
+#[inline]
+fn rice_bits(folded: &[u32], parameter: u32) -> u64 {
+  folded.iter().fold(0, |result, value|
+    result + (*value >> parameter) as u64 + 1 + parameter as u64)
+}
+
+// Find the Rice parameter for a partition and the number of bits it takes
+// to code the partition with that parameter.
+//
+// The parameter is estimated from the mean of the folded residual and its
+// neighbours are checked in case the estimate was off by one.
+fn rice_parameter(folded: &[u32], max_parameter: u32) -> (u32, u64) {
+  if folded.is_empty() {
+    return (0, 0);
+  }
+
+  let sum  = folded.iter().fold(0, |result, value| result + *value as u64);
+  let mean = sum / (folded.len() as u64);
+  let mut estimate = if mean > 0 {
+    63 - mean.leading_zeros()
+  } else {
+    0
+  };
+
+  if estimate > max_parameter {
+    estimate = max_parameter;
+  }
+
+  let start = if estimate > 0 { estimate - 1 } else { 0 };
+  let end   = if estimate < max_parameter { estimate + 1 } else { estimate };
+
+  (start..(end + 1)).fold((0, u64::max_value()), |best, parameter| {
+    let bits = rice_bits(folded, parameter);
+
+    if bits < best.1 { (parameter, bits) } else { best }
+  })
+}
+
+#[inline]
+fn fold(value: i32) -> u32 {
+  ((value << 1) ^ (value >> 31)) as u32
+}
+
+// Best Rice parameter out of every five bit one for a partition, along
+// with the bits it takes, and the number of raw bits needed to escape it.
+fn analyze_partition(folded: &[u32]) -> ((u32, u64), u32) {
+  let largest = folded.iter()
+                      .fold(0, |result, value| cmp::max(result, *value));
+
+  // Folded values below two to the power of n fit inside of an n bit two's
+  // complement number.
+  (rice_parameter(folded, MAX_RICE2_PARAMETER), 32 - largest.leading_zeros())
+}
+
+// Cheapest way to code a partition with the parameter size of `method`,
+// returning the parameter, the raw bits and the number of bits it takes,
+// including the parameter.
+//
+// Partitions that take fewer bits with every value written as raw bits get
+// escaped, where the parameter is the escape code.
+fn code_partition(folded: &[u32], rice: (u32, u64), raw_bits: u32,
+                  method: CodingMethod)
+                  -> (u32, u32, u64) {
+  let (parameter_size, max_parameter, escape_code) = match method {
+    CodingMethod::PartitionedRice  => (4, MAX_RICE_PARAMETER, 0b1111),
+    CodingMethod::PartitionedRice2 => (5, MAX_RICE2_PARAMETER, 0b11111),
+  };
+
+  // The bits only go up moving away from the best parameter, so the largest
+  // one allowed is the best one left.
+  let (parameter, bits) = if rice.0 > max_parameter {
+    (max_parameter, rice_bits(folded, max_parameter))
+  } else {
+    rice
+  };
+
+  let raw_size = 5 + raw_bits as u64 * folded.len() as u64;
+
+  if raw_bits <= MAX_RAW_BITS && raw_size < bits {
+    (escape_code, raw_bits, parameter_size + raw_size)
+  } else {
+    (parameter, 0, parameter_size + bits)
+  }
+}
+
+// Values of the residual that belong to `partition`, out of partitions of
+// `size` samples with the first one missing the warm up samples.
+#[inline]
+fn partition_range(partition: usize, size: usize, predictor_order: usize)
+                   -> Range<usize> {
+  let end = (partition + 1) * size - predictor_order;
+
+  if partition == 0 {
+    0..end
+  } else {
+    (end - size)..end
+  }
+}
+
+// Build the entropy coding method for a residual and return the number of
+// bits needed to write it, including the coding method header.
+//
+// Every partition order in the range of `config` gets tried, limited to
+// the ones the block size can be split into, with the parameters picked
+// for each partition. The five bit parameters only get used when they
+// result in fewer bits overall.
+pub fn entropy_coding_method(residual: &[i32], predictor_order: usize,
+                             config: &Config)
+                             -> (EntropyCodingMethod, u64) {
+  let folded: Vec<u32> = residual.iter().map(|value| fold(*value)).collect();
+  let block_size       = residual.len() + predictor_order;
+
+  // Each partition has to be the same size and the first one has to be
+  // larger than the warm up samples it leaves out.
+  let mut max_order = cmp::min(config.max_partition_order,
+                               MAX_PARTITION_ORDER);
+
+  while max_order > 0 && (block_size % (1 << max_order) != 0 ||
+                          (block_size >> max_order) <= predictor_order) {
+    max_order -= 1;
+  }
+
+  let min_order = cmp::min(config.min_partition_order, max_order);
+  let methods   = [CodingMethod::PartitionedRice,
+                   CodingMethod::PartitionedRice2];
+  let mut best  = (0, CodingMethod::PartitionedRice, u64::max_value());
+
+  for order in min_order..(max_order + 1) {
+    let size     = block_size >> order;
+    let mut bits = [0; 2];
+
+    for partition in 0..(1 << order) {
+      let folded           = &folded[partition_range(partition, size,
+                                                     predictor_order)];
+      let (rice, raw_bits) = analyze_partition(folded);
+
+      for (bits, method) in bits.iter_mut().zip(&methods) {
+        *bits += code_partition(folded, rice, raw_bits, *method).2;
+      }
+    }
+
+    for (bits, method) in bits.iter().zip(&methods) {
+      if *bits < best.2 {
+        best = (order, *method, *bits);
+      }
+    }
+  }
+
+  let (order, method, bits) = best;
+  let partitions            = 1 << order;
+  let size                  = block_size >> order;
+  let mut contents          = PartitionedRiceContents::new(partitions);
+
+  for partition in 0..partitions {
+    let folded           = &folded[partition_range(partition, size,
+                                                   predictor_order)];
+    let (rice, raw_bits) = analyze_partition(folded);
+    let (parameter, raw_bits, _) = code_partition(folded, rice, raw_bits,
+                                                  method);
+
+    contents.parameters()[partition] = parameter;
+    contents.raw_bits()[partition]   = raw_bits;
+  }
+
+  let entropy_coding_method = EntropyCodingMethod {
+    method_type: method,
+    data: PartitionedRice {
+      order: order,
+      contents: contents,
+    },
+  };
+
+  (entropy_coding_method, bits + 6)
+}
+
+// Predictor that resulted in the fewest bits so far.
+enum Model {
+  Fixed(usize, EntropyCodingMethod),
+  LPC {
+    order: usize,
+    precision: u32,
+    shift: i8,
+    coefficients: [i32; MAX_LPC_ORDER],
+    method: EntropyCodingMethod,
+  },
+}
+
+// Search the `LPC` predictors that `config` asks for, replacing `best` with
+// any that needs fewer than `min_bits`.
+//
+// Each window gets its own set of coefficients, and for each set either
+// every order is tried or only the order the prediction error estimates to
+// be the smallest.
+fn search_lpc(samples: &[i32], bits_per_sample: usize, config: &Config,
+              residual: &mut Vec<i32>, best: &mut Option<Model>,
+              min_bits: &mut u64) {
+  let block_size = samples.len();
+  let max_order  = cmp::min(cmp::min(config.max_lpc_order, MAX_LPC_ORDER),
+                            block_size - 1);
+
+  if max_order == 0 {
+    return;
+  }
+
+  let requested = match config.qlp_coeff_precision {
+    0         => lpc::default_precision(bits_per_sample, block_size),
+    precision => precision as u32,
+  };
+
+  let mut window          = vec![0.0; block_size];
+  let mut windowed        = Vec::with_capacity(block_size);
+  let mut autocorrelation = [0.0; MAX_LPC_ORDER + 1];
+  let mut coefficients    = [[0.0; MAX_LPC_ORDER]; MAX_LPC_ORDER];
+  let mut errors          = [0.0; MAX_LPC_ORDER];
+
+  for apodization in &config.apodizations {
+    for index in 0..apodization.windows() {
+      lpc::window(*apodization, index, &mut window);
+      lpc::autocorrelation(samples, &window, &mut windowed,
+                           &mut autocorrelation[0..(max_order + 1)]);
+
+      // A silent window has nothing to predict.
+      if autocorrelation[0] == 0.0 {
+        continue;
+      }
+
+      let max_order = lpc::lp_coefficients(&autocorrelation, max_order,
+                                           &mut coefficients, &mut errors);
+
+      let orders = if config.exhaustive_model_search {
+        1..(max_order + 1)
+      } else {
+        let precision = lpc::precision(requested, bits_per_sample,
+                                       max_order);
+        let order     = lpc::best_order(&errors[0..max_order], block_size,
+                                        bits_per_sample + precision as usize);
+
+        order..(order + 1)
+      };
+
+      for order in orders {
+        let precision     = lpc::precision(requested, bits_per_sample, order);
+        let mut quantized = [0; MAX_LPC_ORDER];
+
+        let shift = match lpc::quantize(&coefficients[order - 1][0..order],
+                                        precision, &mut quantized[0..order]) {
+          Some(shift) => shift,
+          None        => continue,
+        };
+
+        if !lpc::residual(samples, &quantized[0..order], shift, residual) {
+          continue;
+        }
+
+        let (method, rice_bits) = entropy_coding_method(residual, order,
+                                                        config);
+
+        // Warm up samples and coefficients, along with the four bit
+        // precision and five bit shift.
+        let bits = (order * (bits_per_sample + precision as usize)) as u64 +
+                   9 + rice_bits;
+
+        if bits < *min_bits {
+          *min_bits = bits;
+          *best     = Some(Model::LPC {
+            order: order,
+            precision: precision,
+            shift: shift,
+            coefficients: quantized,
+            method: method,
+          });
+        }
+      }
+    }
+  }
+}
+
+/// Encodes a single channel of audio data into a subframe.
+///
+/// Every subframe type gets evaluated, with the `LPC` predictors limited to
+/// the ones that `config` asks for, and the type that results in the least
+/// amount of bits is returned.
+pub fn encode(samples: &[i32], bits_per_sample: usize, config: &Config)
+              -> Subframe {
+  let block_size = samples.len();
+  let first      = samples[0];
+
+  if samples.iter().all(|sample| *sample == first) {
+    return Subframe {
+      data: subframe::Data::Constant(first as i64),
+      wasted_bits: 0,
+    };
+  }
+
+  let wasted_bits = wasted_bits(samples);
+  let shifted: Vec<i32> = samples.iter()
+                                 .map(|sample| *sample >> wasted_bits)
+                                 .collect();
+
+  let bits_per_sample = bits_per_sample - wasted_bits as usize;
+  let max_order       = if block_size > MAX_FIXED_ORDER {
+    MAX_FIXED_ORDER
+  } else {
+    block_size - 1
+  };
+
+  let mut residual = Vec::with_capacity(block_size);
+  let mut best     = None;
+  let mut min_bits = (block_size * bits_per_sample) as u64;
+
+  for order in 0..(max_order + 1) {
+    if !fixed_residual(order, &shifted, &mut residual) {
+      continue;
+    }
+
+    let (method, rice_bits) = entropy_coding_method(&residual, order,
+                                                    config);
+    let bits                = (order * bits_per_sample) as u64 + rice_bits;
+
+    if bits < min_bits {
+      min_bits = bits;
+      best     = Some(Model::Fixed(order, method));
+    }
+  }
+
+  search_lpc(&shifted, bits_per_sample, config, &mut residual, &mut best,
+             &mut min_bits);
+
+  let data = match best {
+    Some(Model::Fixed(order, method)) => {
+      let mut warmup = [0; MAX_FIXED_ORDER];
+
+      for (warmup, sample) in warmup.iter_mut().zip(&shifted[0..order]) {
+        *warmup = *sample as i64;
+      }
+
+      fixed_residual(order, &shifted, &mut residual);
+
+      subframe::Data::Fixed(subframe::Fixed {
+        entropy_coding_method: method,
+        order: order as u8,
+        warmup: warmup,
+        residual: residual,
+      })
+    }
+    Some(Model::LPC { order, precision, shift, coefficients, method }) => {
+      let mut warmup = [0; MAX_LPC_ORDER];
+
+      for (warmup, sample) in warmup.iter_mut().zip(&shifted[0..order]) {
+        *warmup = *sample as i64;
+      }
+
+      lpc::residual(&shifted, &coefficients[0..order], shift, &mut residual);
+
+      subframe::Data::LPC(subframe::LPC {
+        entropy_coding_method: method,
+        order: order as u8,
+        qlp_coeff_precision: precision as u8,
+        quantization_level: shift,
+        qlp_coefficients: coefficients,
+        warmup: warmup,
+        residual: residual,
+      })
+    }
+    None                              => {
+      subframe::Data::Verbatim(shifted.iter()
+                                      .map(|sample| *sample as i64)
+                                      .collect())
+    }
+  };
+
+  Subframe {
+    data: data,
+    wasted_bits: wasted_bits,
+  }
+}
+
+fn write_residual(entropy_coding_method: &EntropyCodingMethod,
+                  predictor_order: usize,
+                  residual: &[i32],
+                  writer: &mut BitWriter) {
+  let (method, parameter_size, escape_code) =
+    match entropy_coding_method.method_type {
+      CodingMethod::PartitionedRice  => (0, 4, 0b1111),
+      CodingMethod::PartitionedRice2 => (1, 5, 0b11111),
+    };
+
+  let rice       = &entropy_coding_method.data;
+  let contents   = &rice.contents;
+  let partitions = 1 << rice.order;
+  let block_size = (residual.len() + predictor_order) >> rice.order;
+
+  writer.write_bits(method, 2);
+  writer.write_bits(rice.order, 4);
+
+  let mut start = 0;
+
+  for partition in 0..partitions {
+    let parameter = contents.data[partition];
+    let raw_bits  = contents.data[contents.capacity + partition];
+    let end       = if partition == 0 {
+      start + block_size - predictor_order
+    } else {
+      start + block_size
+    };
+
+    if raw_bits > 0 || parameter == escape_code {
+      writer.write_bits(escape_code, parameter_size);
+      writer.write_bits(raw_bits, 5);
+
+      for value in &residual[start..end] {
+        writer.write_signed_bits(*value, raw_bits as usize);
+      }
+    } else {
+      writer.write_bits(parameter, parameter_size);
+
+      for value in &residual[start..end] {
+        writer.write_rice(*value, parameter);
+      }
+    }
+
+    start = end;
+  }
+}
+
+/// Writes a subframe to the bit writer.
+///
+/// `bits_per_sample` is the sample size of the channel that the subframe
+/// belongs to, before the wasted bits get removed.
+pub fn write(subframe: &Subframe,
+             bits_per_sample: usize,
+             writer: &mut BitWriter) {
+  let bits_per_sample = bits_per_sample - subframe.wasted_bits as usize;
+  let subframe_type   = match subframe.data {
+    subframe::Data::Constant(_)   => 0b000000,
+    subframe::Data::Verbatim(_)   => 0b000001,
+    subframe::Data::Fixed(ref f)  => 0b001000 | f.order as u32,
+    subframe::Data::LPC(ref l)    => 0b100000 | (l.order as u32 - 1),
+  };
+
+  writer.write_bits(subframe_type << 1 | (subframe.wasted_bits > 0) as u32,
+                    8);
+
+  if subframe.wasted_bits > 0 {
+    writer.write_unary(subframe.wasted_bits - 1);
+  }
+
+  match subframe.data {
+    subframe::Data::Constant(constant)     => {
+      writer.write_wide_signed_bits(constant, bits_per_sample);
+    }
+    subframe::Data::Verbatim(ref verbatim) => {
+      for sample in verbatim {
+        writer.write_wide_signed_bits(*sample, bits_per_sample);
+      }
+    }
+    subframe::Data::Fixed(ref fixed)       => {
+      let order = fixed.order as usize;
+
+      for warmup in &fixed.warmup[0..order] {
+        writer.write_wide_signed_bits(*warmup, bits_per_sample);
+      }
+
+      write_residual(&fixed.entropy_coding_method, order, &fixed.residual,
+                     writer);
+    }
+    subframe::Data::LPC(ref lpc)           => {
+      let order     = lpc.order as usize;
+      let precision = lpc.qlp_coeff_precision as usize;
+
+      for warmup in &lpc.warmup[0..order] {
+        writer.write_wide_signed_bits(*warmup, bits_per_sample);
+      }
+
+      writer.write_bits(lpc.qlp_coeff_precision as u32 - 1, 4);
+      writer.write_signed_bits(lpc.quantization_level as i32, 5);
+
+      for coefficient in &lpc.qlp_coefficients[0..order] {
+        writer.write_signed_bits(*coefficient, precision);
+      }
+
+      write_residual(&lpc.entropy_coding_method, order, &lpc.residual,
+                     writer);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use subframe::{self, Buffers, Data, Fixed};
+  use subframe::parser::{fixed, lpc};
+  use utility::{BitReader, BitWriter};
+
+  #[test]
+  fn test_wasted_bits() {
+    assert_eq!(wasted_bits(&[0, 0, 0]), 0);
+    assert_eq!(wasted_bits(&[4, 8, -12]), 2);
+    assert_eq!(wasted_bits(&[1024, 512, 0]), 9);
+    assert_eq!(wasted_bits(&[3, 8, 16]), 0);
+  }
+
+  #[test]
+  fn test_fixed_residual() {
+    let samples      = [-729, -722, -667, -583, -486, -359, -225, -91];
+    let mut residual = Vec::new();
+
+    assert!(fixed_residual(3, &samples, &mut residual));
+    assert_eq!(&residual, &[-19, -16, 17, -23, -7]);
+
+    assert!(fixed_residual(0, &samples[0..2], &mut residual));
+    assert_eq!(&residual, &[-729, -722]);
+
+    assert!(!fixed_residual(1, &[i32::min_value(), i32::max_value()],
+                            &mut residual));
+  }
+
+  #[test]
+  fn test_estimate_bits() {
+    assert_eq!(estimate_bits(&[3, 3, 3, 3, 3, 3, 3, 3]), 0);
+    assert_eq!(estimate_bits(&[0, 1, 2, 3, 4, 5, 6, 7]), 0);
+
+    let quiet: Vec<i32> = (0..64).map(|i| (i * 37) % 16 - 8).collect();
+    let loud: Vec<i32>  = quiet.iter().map(|sample| sample * 64).collect();
+
+    assert!(estimate_bits(&quiet) > 0);
+    assert!(estimate_bits(&quiet) < estimate_bits(&loud));
+  }
+
+  #[test]
+  fn test_encode() {
+    let mut config = Config::default();
+
+    config.max_lpc_order = 0;
+
+    let constant = encode(&[7, 7, 7, 7], 16, &config);
+
+    assert_eq!(constant.data, Data::Constant(7));
+    assert_eq!(constant.wasted_bits, 0);
+
+    let samples = [-729, -722, -667, -583, -486, -359, -225, -91, 59, 209
+                  , 354, 497, 630, 740, 812, 845];
+    let fixed   = encode(&samples, 16, &config);
+
+    match fixed.data {
+      Data::Fixed(Fixed { order, ref residual, .. }) => {
+        assert!(order > 0);
+        assert_eq!(residual.len(), samples.len() - order as usize);
+      }
+      _                                              => {
+        panic!("Expected a fixed subframe");
+      }
+    }
+
+    let wasted = encode(&[8, -16, 32, 48], 16, &config);
+
+    assert_eq!(wasted.wasted_bits, 3);
+  }
+
+  // Samples of two sine waves added together, which a low order fixed
+  // predictor can't follow as well as an `LPC` one.
+  fn sines(length: usize) -> Vec<i32> {
+    (0..length).map(|i| {
+      let x = i as f64;
+
+      ((x * 0.05).sin() * 12000.0 + (x * 0.37).sin() * 6000.0) as i32
+    }).collect()
+  }
+
+  #[test]
+  fn test_encode_lpc() {
+    let samples = sines(1024);
+    let configs = [
+      Config::default(),
+      Config {
+        max_lpc_order: 12,
+        qlp_coeff_precision: 12,
+        apodizations: vec![
+          Apodization::Hann, Apodization::Welch,
+          Apodization::PartialTukey(2), Apodization::PunchoutTukey(3),
+        ],
+        exhaustive_model_search: true,
+        ..Config::default()
+      },
+    ];
+
+    for config in configs.iter() {
+      let subframe = encode(&samples, 16, config);
+
+      let mut output = vec![0; samples.len()];
+
+      // The decoder expects the residual to already be in place, the same
+      // as the parser leaves it.
+      match subframe.data {
+        Data::LPC(ref lpc) => {
+          let order = lpc.order as usize;
+
+          assert!(order <= config.max_lpc_order);
+          assert!(lpc.qlp_coeff_precision <= 15);
+          assert!(lpc.quantization_level >= 0);
+
+          output[order..].copy_from_slice(&lpc.residual);
+        }
+        _                  => panic!("Expected a LPC subframe"),
+      }
+
+      subframe::decode(&subframe, samples.len(), 16, &mut output);
+
+      assert_eq!(output, samples);
+    }
+
+    let mut config = Config::default();
+    let mut writer = BitWriter::new();
+
+    write(&encode(&samples, 16, &config), 16, &mut writer);
+
+    let lpc_bytes = writer.as_slice().len();
+
+    config.max_lpc_order = 0;
+    writer.clear();
+
+    write(&encode(&samples, 16, &config), 16, &mut writer);
+
+    assert!(lpc_bytes < writer.as_slice().len());
+  }
+
+  // Writes `residual` as a second order `Fixed` subframe, checking that
+  // the bits match the estimate and the parser reads back the same thing.
+  fn round_trip(residual: &[i32], config: &Config) -> EntropyCodingMethod {
+    let (method, bits) = entropy_coding_method(residual, 2, config);
+    let block_size     = residual.len() + 2;
+
+    let subframe = Subframe {
+      data: Data::Fixed(Fixed {
+        entropy_coding_method: method,
+        order: 2,
+        warmup: [0; MAX_FIXED_ORDER],
+        residual: residual.to_vec(),
+      }),
+      wasted_bits: 0,
+    };
+
+    let mut writer = BitWriter::new();
+
+    write(&subframe, 16, &mut writer);
+    writer.align();
+
+    // Subframe header and warm up samples, rounded up to a whole byte.
+    assert_eq!(writer.as_slice().len() as u64, (8 + 32 + bits + 7) / 8);
+
+    let mut buffer  = vec![0; block_size];
+    let mut buffers = Buffers::default();
+    let mut reader  = BitReader::new(&writer.as_slice()[1..]);
+    let data        = fixed(&mut reader, 2, 16, block_size, &mut buffers,
+                            &mut buffer).unwrap();
+
+    assert_eq!(data, subframe.data);
+
+    match subframe.data {
+      Data::Fixed(fixed) => fixed.entropy_coding_method,
+      _                  => unreachable!(),
+    }
+  }
+
+  #[test]
+  fn test_entropy_coding_method() {
+    let config = Config::default();
+
+    // Quiet start followed by a loud end splits into partitions.
+    let residual: Vec<i32> = (0..254).map(|i| {
+      if i < 126 { i % 3 - 1 } else { (i * 37) % 4001 - 2000 }
+    }).collect();
+
+    let method = round_trip(&residual, &config);
+
+    assert_eq!(method.method_type, CodingMethod::PartitionedRice);
+    assert!(method.data.order > 0);
+
+    let mut contents = method.data.contents;
+    let partitions   = contents.capacity;
+
+    assert!(contents.parameters()[0] < contents.parameters()[partitions - 1]);
+
+    // Silence gets escaped with zero raw bits.
+    let residual: Vec<i32> = (0..126).map(|i| {
+      if i < 62 { 0 } else { i % 5 - 2 }
+    }).collect();
+
+    let mut method = round_trip(&residual, &config);
+
+    assert_eq!(method.data.contents.parameters()[0], 0b1111);
+    assert_eq!(method.data.contents.raw_bits()[0], 0);
+
+    // Evenly spread out values take the same raw bits either way.
+    let residual: Vec<i32> = (0..62).map(|i| (i * 97) % 256 - 128)
+                                    .collect();
+
+    let mut method = round_trip(&residual, &config);
+
+    assert_eq!(method.data.order, 0);
+    assert_eq!(method.data.contents.raw_bits()[0], 8);
+
+    // Large values need the five bit parameters.
+    let residual: Vec<i32> = (0..1022).map(|i| {
+      let value = ((i * 7919) % 1000 + 1) as f64 / 1001.0;
+      let value = (-value.ln() * 1048576.0) as i32;
+
+      if i % 2 == 0 { value } else { -value }
+    }).collect();
+
+    let mut method = round_trip(&residual, &config);
+
+    assert_eq!(method.method_type, CodingMethod::PartitionedRice2);
+    assert!(method.data.contents.parameters()[0] > MAX_RICE_PARAMETER);
+
+    // Partition orders that don't divide the block size get skipped.
+    let config = Config {
+      min_partition_order: 4,
+      max_partition_order: 6,
+      ..Config::default()
+    };
+
+    assert_eq!(round_trip(&[1, -2, 3, -4, 5, -6, 7, -8], &config).data.order,
+               1);
+  }
+
+  #[test]
+  fn test_write() {
+    let mut writer = BitWriter::new();
+
+    let constant = Subframe {
+      data: subframe::Data::Constant(-8),
+      wasted_bits: 0,
+    };
+
+    write(&constant, 5, &mut writer);
+    writer.align();
+
+    assert_eq!(writer.as_slice(), &[0x00, 0xc0]);
+
+    writer.clear();
+
+    let verbatim = Subframe {
+      data: subframe::Data::Verbatim(vec![1, -1]),
+      wasted_bits: 2,
+    };
+
+    write(&verbatim, 6, &mut writer);
+    writer.align();
+
+    assert_eq!(writer.as_slice(), &[0x03, 0b01000111, 0b11000000]);
+  }
+
+  #[test]
+  fn test_to_bytes() {
+    let fixed_input = b"\xe8\0\x40\xaf\x02\x01\x04\x80\x42\x92\x84\x65\
+                        \x64";
+    let lpc_input   = b"\xe8\0\x40\xaf\x74\x73\x19\0\x75\x81\xe8\x16\0\
+                        \x05\x18\xef\x36";
+
+    let mut buffer  = [0; 10];
+    let mut buffers = Buffers::default();
+    let mut output  = Vec::new();
+
+    let mut reader = BitReader::new(&fixed_input[..]);
+    let data       = fixed(&mut reader, 4, 8, 10, &mut buffers, &mut buffer);
+    let subframe   = Subframe { data: data.unwrap(), wasted_bits: 0 };
+
+    assert!(subframe.to_bytes(8, &mut output).is_ok());
+    assert_eq!(output[0], 0b00011000);
+    assert_eq!(&output[1..], &fixed_input[..]);
+
+    output.clear();
+
+    let mut reader = BitReader::new(&lpc_input[..]);
+    let data       = lpc(&mut reader, 4, 8, 10, &mut buffers, &mut buffer);
+    let subframe   = Subframe { data: data.unwrap(), wasted_bits: 0 };
+
+    assert!(subframe.to_bytes(8, &mut output).is_ok());
+    assert_eq!(output[0], 0b01000110);
+    assert_eq!(&output[1..], &lpc_input[..]);
+  }
+}
@@ -0,0 +1,584 @@
+// Kernels for restoring the signal of `Fixed` and `LPC` subframes.
+//
+// Each prediction is a sum of products between the taps, which are the
+// coefficients in the same order as the samples they get multiplied with,
+// and the samples right before it. There are two sizes of accumulators for
+// the sum. The 32 bit ones are only used when the bits per sample and the
+// coefficients make it impossible for the sum to go past an `i32`, and they
+// wrap the same way the sum would as an `i64` truncated to an `i32`. So
+// every kernel gives the same output as summing up with an `i64`.
+//
+// The common orders have a kernel with the loop over the taps unrolled, and
+// the rest go through a single loop. On x86_64 the higher orders use AVX2
+// or SSE 4.1, and on AArch64 they use NEON, when the processor supports it.
+
+use std::i32;
+
+use subframe::MAX_LPC_ORDER;
+
+// Lowest order that uses the vector kernels, below it there aren't enough
+// taps to make up for summing the lanes afterwards.
+const MIN_VECTOR_ORDER: usize = 8;
+
+// Restore every sample of `output` after the first `$order`, where `$taps`
+// is an array of the taps and `$accumulator` is the type of the sum. The
+// order is a constant so the loop over the taps gets unrolled.
+macro_rules! restore_order (
+  ($output: expr, $taps: expr, $shift: expr, $order: expr,
+   $accumulator: ty) => ({
+    let output = $output;
+    let mut taps: [$accumulator; $order] = [0; $order];
+
+    for (tap, coefficient) in taps.iter_mut().zip($taps.iter()) {
+      *tap = *coefficient as $accumulator;
+    }
+
+    for i in $order..output.len() {
+      let mut prediction: $accumulator = 0;
+
+      {
+        let window = &output[(i - $order)..i];
+
+        for j in 0..$order {
+          prediction = prediction.wrapping_add(
+                         taps[j].wrapping_mul(window[j] as $accumulator));
+        }
+      }
+
+      output[i] = output[i].wrapping_add((prediction >> $shift) as _);
+    }
+  })
+);
+
+// Restore `output` for any order, with the same result as the unrolled
+// kernels.
+macro_rules! restore_any (
+  ($output: expr, $taps: expr, $shift: expr, $accumulator: ty) => ({
+    let output = $output;
+    let taps   = $taps;
+    let order  = taps.len();
+
+    for i in order..output.len() {
+      let prediction = taps.iter().zip(&output[(i - order)..i])
+                           .fold(0 as $accumulator, |result, (tap, signal)| {
+        result.wrapping_add((*tap as $accumulator)
+                              .wrapping_mul(*signal as $accumulator))
+      });
+
+      output[i] = output[i].wrapping_add((prediction >> $shift) as _);
+    }
+  })
+);
+
+// Pick the unrolled kernel for the order of `$taps`.
+macro_rules! restore (
+  ($output: expr, $taps: expr, $shift: expr, $accumulator: ty) => ({
+    let taps = $taps;
+
+    match taps.len() {
+      1  => restore_order!($output, taps, $shift, 1, $accumulator),
+      2  => restore_order!($output, taps, $shift, 2, $accumulator),
+      3  => restore_order!($output, taps, $shift, 3, $accumulator),
+      4  => restore_order!($output, taps, $shift, 4, $accumulator),
+      5  => restore_order!($output, taps, $shift, 5, $accumulator),
+      6  => restore_order!($output, taps, $shift, 6, $accumulator),
+      7  => restore_order!($output, taps, $shift, 7, $accumulator),
+      8  => restore_order!($output, taps, $shift, 8, $accumulator),
+      9  => restore_order!($output, taps, $shift, 9, $accumulator),
+      10 => restore_order!($output, taps, $shift, 10, $accumulator),
+      11 => restore_order!($output, taps, $shift, 11, $accumulator),
+      12 => restore_order!($output, taps, $shift, 12, $accumulator),
+      32 => restore_order!($output, taps, $shift, 32, $accumulator),
+      _  => restore_any!($output, taps, $shift, $accumulator),
+    }
+  })
+);
+
+// Taps of the fixed predictors, from order zero up to four.
+const FIXED_TAPS: [&'static [i32]; 5] = [
+  &[],
+  &[1],
+  &[-1, 2],
+  &[1, -3, 3],
+  &[-1, 4, -6, 4],
+];
+
+// Whether the prediction, from `taps` and samples of `bits_per_sample`,
+// always fits within an `i32`.
+pub fn is_narrow(taps: &[i32], bits_per_sample: usize) -> bool {
+  if bits_per_sample == 0 || bits_per_sample > 32 {
+    return false;
+  }
+
+  let sum = taps.iter().fold(0, |sum, tap| sum + (*tap as i64).abs() as u64);
+
+  (sum << (bits_per_sample - 1)) <= i32::max_value() as u64
+}
+
+// Put the coefficients of an `LPC` subframe in the order of the samples
+// they get multiplied with.
+pub fn lpc_taps(coefficients: &[i32], taps: &mut [i32; MAX_LPC_ORDER]) {
+  for (tap, coefficient) in taps.iter_mut().zip(coefficients.iter().rev()) {
+    *tap = *coefficient;
+  }
+}
+
+pub fn fixed_i32(order: usize, bits_per_sample: usize, output: &mut [i32]) {
+  let taps = FIXED_TAPS[order];
+
+  if order == 0 {
+    return;
+  }
+
+  if is_narrow(taps, bits_per_sample) {
+    restore!(output, taps, 0, i32)
+  } else {
+    restore!(output, taps, 0, i64)
+  }
+}
+
+pub fn fixed_i64(order: usize, output: &mut [i64]) {
+  let taps = FIXED_TAPS[order];
+
+  if order > 0 {
+    restore!(output, taps, 0, i64)
+  }
+}
+
+pub fn lpc_i32(taps: &[i32], shift: u32, bits_per_sample: usize,
+               output: &mut [i32]) {
+  let is_narrow = is_narrow(taps, bits_per_sample);
+
+  if taps.len() >= MIN_VECTOR_ORDER &&
+     lpc_vector(taps, shift, is_narrow, output) {
+    return;
+  }
+
+  if is_narrow {
+    restore!(output, taps, shift, i32)
+  } else {
+    restore!(output, taps, shift, i64)
+  }
+}
+
+pub fn lpc_i64(taps: &[i32], shift: u32, output: &mut [i64]) {
+  restore!(output, taps, shift, i64)
+}
+
+// Restore `output` with the best vector kernel the processor supports,
+// returning false when there isn't one.
+#[cfg(target_arch = "x86_64")]
+fn lpc_vector(taps: &[i32], shift: u32, is_narrow: bool,
+              output: &mut [i32])
+              -> bool {
+  unsafe {
+    if is_x86_feature_detected!("avx2") {
+      if is_narrow {
+        x86::lpc_narrow_avx2(taps, shift, output);
+      } else {
+        x86::lpc_wide_avx2(taps, shift, output);
+      }
+    } else if is_x86_feature_detected!("sse4.1") {
+      if is_narrow {
+        x86::lpc_narrow_sse41(taps, shift, output);
+      } else {
+        x86::lpc_wide_sse41(taps, shift, output);
+      }
+    } else {
+      return false;
+    }
+  }
+
+  true
+}
+
+#[cfg(target_arch = "aarch64")]
+fn lpc_vector(taps: &[i32], shift: u32, is_narrow: bool,
+              output: &mut [i32])
+              -> bool {
+  if !is_aarch64_feature_detected!("neon") {
+    return false;
+  }
+
+  unsafe {
+    if is_narrow {
+      neon::lpc_narrow(taps, shift, output);
+    } else {
+      neon::lpc_wide(taps, shift, output);
+    }
+  }
+
+  true
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn lpc_vector(_taps: &[i32], _shift: u32, _is_narrow: bool,
+              _output: &mut [i32])
+              -> bool {
+  false
+}
+
+// Sum of the products of the taps, before the ones that fill whole vectors,
+// with the samples at `window`.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[inline(always)]
+unsafe fn head_i32(taps: &[i32], head: usize, window: *const i32) -> i32 {
+  let mut prediction = 0i32;
+
+  for j in 0..head {
+    prediction = prediction.wrapping_add(
+                   taps[j].wrapping_mul(*window.offset(j as isize)));
+  }
+
+  prediction
+}
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[inline(always)]
+unsafe fn head_i64(taps: &[i32], head: usize, window: *const i32) -> i64 {
+  let mut prediction = 0i64;
+
+  for j in 0..head {
+    prediction += taps[j] as i64 * *window.offset(j as isize) as i64;
+  }
+
+  prediction
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+  use std::arch::x86_64::*;
+
+  use super::{head_i32, head_i64};
+
+  #[target_feature(enable = "sse4.1")]
+  pub unsafe fn lpc_narrow_sse41(taps: &[i32], shift: u32,
+                                 output: &mut [i32]) {
+    let order = taps.len();
+    let head  = order % 4;
+    let shift = _mm_cvtsi32_si128(shift as i32);
+
+    for i in order..output.len() {
+      let window  = output.as_ptr().offset((i - order) as isize);
+      let mut sum = _mm_setzero_si128();
+      let mut j   = head;
+
+      while j < order {
+        let tap    = _mm_loadu_si128(taps.as_ptr().offset(j as isize)
+                                       as *const __m128i);
+        let signal = _mm_loadu_si128(window.offset(j as isize)
+                                       as *const __m128i);
+
+        sum  = _mm_add_epi32(sum, _mm_mullo_epi32(tap, signal));
+        j   += 4;
+      }
+
+      sum = _mm_add_epi32(sum, _mm_shuffle_epi32(sum, 0b01001110));
+      sum = _mm_add_epi32(sum, _mm_shuffle_epi32(sum, 0b10110001));
+      sum = _mm_add_epi32(sum, _mm_cvtsi32_si128(head_i32(taps, head,
+                                                          window)));
+      sum = _mm_sra_epi32(sum, shift);
+
+      output[i] = output[i].wrapping_add(_mm_cvtsi128_si32(sum));
+    }
+  }
+
+  #[target_feature(enable = "avx2")]
+  pub unsafe fn lpc_narrow_avx2(taps: &[i32], shift: u32,
+                                output: &mut [i32]) {
+    let order = taps.len();
+    let head  = order % 8;
+    let shift = _mm_cvtsi32_si128(shift as i32);
+
+    for i in order..output.len() {
+      let window  = output.as_ptr().offset((i - order) as isize);
+      let mut sum = _mm256_setzero_si256();
+      let mut j   = head;
+
+      while j < order {
+        let tap    = _mm256_loadu_si256(taps.as_ptr().offset(j as isize)
+                                          as *const __m256i);
+        let signal = _mm256_loadu_si256(window.offset(j as isize)
+                                          as *const __m256i);
+
+        sum  = _mm256_add_epi32(sum, _mm256_mullo_epi32(tap, signal));
+        j   += 8;
+      }
+
+      let mut half = _mm_add_epi32(_mm256_castsi256_si128(sum),
+                                   _mm256_extracti128_si256(sum, 1));
+
+      half = _mm_add_epi32(half, _mm_shuffle_epi32(half, 0b01001110));
+      half = _mm_add_epi32(half, _mm_shuffle_epi32(half, 0b10110001));
+      half = _mm_add_epi32(half, _mm_cvtsi32_si128(head_i32(taps, head,
+                                                            window)));
+      half = _mm_sra_epi32(half, shift);
+
+      output[i] = output[i].wrapping_add(_mm_cvtsi128_si32(half));
+    }
+  }
+
+  #[target_feature(enable = "sse4.1")]
+  pub unsafe fn lpc_wide_sse41(taps: &[i32], shift: u32,
+                               output: &mut [i32]) {
+    let order = taps.len();
+    let head  = order % 2;
+
+    for i in order..output.len() {
+      let window  = output.as_ptr().offset((i - order) as isize);
+      let mut sum = _mm_setzero_si128();
+      let mut j   = head;
+
+      // Each pair gets sign extended into two 64 bit lanes.
+      while j < order {
+        let tap    = _mm_cvtepi32_epi64(_mm_loadl_epi64(
+                       taps.as_ptr().offset(j as isize) as *const __m128i));
+        let signal = _mm_cvtepi32_epi64(_mm_loadl_epi64(
+                       window.offset(j as isize) as *const __m128i));
+
+        sum  = _mm_add_epi64(sum, _mm_mul_epi32(tap, signal));
+        j   += 2;
+      }
+
+      sum = _mm_add_epi64(sum, _mm_unpackhi_epi64(sum, sum));
+
+      let prediction = _mm_cvtsi128_si64(sum) +
+                       head_i64(taps, head, window);
+
+      output[i] = output[i].wrapping_add((prediction >> shift) as i32);
+    }
+  }
+
+  #[target_feature(enable = "avx2")]
+  pub unsafe fn lpc_wide_avx2(taps: &[i32], shift: u32,
+                              output: &mut [i32]) {
+    let order = taps.len();
+    let head  = order % 4;
+
+    for i in order..output.len() {
+      let window  = output.as_ptr().offset((i - order) as isize);
+      let mut sum = _mm256_setzero_si256();
+      let mut j   = head;
+
+      while j < order {
+        let tap    = _mm256_cvtepi32_epi64(_mm_loadu_si128(
+                       taps.as_ptr().offset(j as isize) as *const __m128i));
+        let signal = _mm256_cvtepi32_epi64(_mm_loadu_si128(
+                       window.offset(j as isize) as *const __m128i));
+
+        sum  = _mm256_add_epi64(sum, _mm256_mul_epi32(tap, signal));
+        j   += 4;
+      }
+
+      let mut half = _mm_add_epi64(_mm256_castsi256_si128(sum),
+                                   _mm256_extracti128_si256(sum, 1));
+
+      half = _mm_add_epi64(half, _mm_unpackhi_epi64(half, half));
+
+      let prediction = _mm_cvtsi128_si64(half) +
+                       head_i64(taps, head, window);
+
+      output[i] = output[i].wrapping_add((prediction >> shift) as i32);
+    }
+  }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+  use std::arch::aarch64::*;
+
+  use super::{head_i32, head_i64};
+
+  #[target_feature(enable = "neon")]
+  pub unsafe fn lpc_narrow(taps: &[i32], shift: u32, output: &mut [i32]) {
+    let order = taps.len();
+    let head  = order % 4;
+
+    for i in order..output.len() {
+      let window  = output.as_ptr().offset((i - order) as isize);
+      let mut sum = vdupq_n_s32(0);
+      let mut j   = head;
+
+      while j < order {
+        let tap    = vld1q_s32(taps.as_ptr().offset(j as isize));
+        let signal = vld1q_s32(window.offset(j as isize));
+
+        sum  = vmlaq_s32(sum, tap, signal);
+        j   += 4;
+      }
+
+      let prediction = vaddvq_s32(sum).wrapping_add(head_i32(taps, head,
+                                                             window));
+
+      output[i] = output[i].wrapping_add(prediction >> shift);
+    }
+  }
+
+  #[target_feature(enable = "neon")]
+  pub unsafe fn lpc_wide(taps: &[i32], shift: u32, output: &mut [i32]) {
+    let order = taps.len();
+    let head  = order % 4;
+
+    for i in order..output.len() {
+      let window  = output.as_ptr().offset((i - order) as isize);
+      let mut sum = vdupq_n_s64(0);
+      let mut j   = head;
+
+      while j < order {
+        let tap    = vld1q_s32(taps.as_ptr().offset(j as isize));
+        let signal = vld1q_s32(window.offset(j as isize));
+
+        sum  = vmlal_s32(sum, vget_low_s32(tap), vget_low_s32(signal));
+        sum  = vmlal_high_s32(sum, tap, signal);
+        j   += 4;
+      }
+
+      let prediction = vaddvq_s64(sum) + head_i64(taps, head, window);
+
+      output[i] = output[i].wrapping_add((prediction >> shift) as i32);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use subframe::MAX_LPC_ORDER;
+
+  // Samples of `bits_per_sample` from a linear congruential generator.
+  fn signal(length: usize, bits_per_sample: usize, seed: u64) -> Vec<i64> {
+    let mut state = seed;
+
+    (0..length).map(|_| {
+      state = state.wrapping_mul(6364136223846793005)
+                   .wrapping_add(1442695040888963407);
+
+      (state as i64) >> (64 - bits_per_sample)
+    }).collect()
+  }
+
+  // Restore the same way as summing up every prediction as an `i64`.
+  fn reference(taps: &[i32], shift: u32, output: &mut [i64]) {
+    let order = taps.len();
+
+    for i in order..output.len() {
+      let prediction = taps.iter().zip(&output[(i - order)..i])
+                           .fold(0, |result, (tap, signal)| {
+        result + *tap as i64 * *signal
+      });
+
+      output[i] = (output[i] + (prediction >> shift)) as i32 as i64;
+    }
+  }
+
+  // Residuals that keep the restored signal within `bits_per_sample`,
+  // found by running the prediction forward over `samples`.
+  fn residuals(taps: &[i32], shift: u32, samples: &[i64]) -> Vec<i64> {
+    let order      = taps.len();
+    let mut output = samples.to_vec();
+
+    for i in order..samples.len() {
+      let prediction = taps.iter().zip(&samples[(i - order)..i])
+                           .fold(0, |result, (tap, signal)| {
+        result + *tap as i64 * *signal
+      });
+
+      output[i] = samples[i] - (prediction >> shift);
+    }
+
+    output
+  }
+
+  #[test]
+  fn test_is_narrow() {
+    assert!(is_narrow(&[-1, 4, -6, 4], 16));
+    assert!(is_narrow(&[-1, 4, -6, 4], 28));
+    assert!(!is_narrow(&[-1, 4, -6, 4], 29));
+    assert!(!is_narrow(&[1], 33));
+    assert!(is_narrow(&[1], 31));
+    assert!(!is_narrow(&[1], 32));
+  }
+
+  #[test]
+  fn test_kernels() {
+    let mut taps = [0; MAX_LPC_ORDER];
+
+    for order in 1..(MAX_LPC_ORDER + 1) {
+      for &(bits_per_sample, precision, shift) in [(16, 12, 10), (24, 15, 14),
+                                                   (8, 5, 3)].iter() {
+        let coefficients = signal(order, precision, order as u64);
+        let coefficients = coefficients.iter().map(|c| *c as i32)
+                                       .collect::<Vec<_>>();
+
+        lpc_taps(&coefficients, &mut taps);
+
+        let taps     = &taps[0..order];
+        let samples  = signal(300, bits_per_sample, 7 + order as u64);
+        let residual = residuals(taps, shift, &samples);
+
+        let mut expected = residual.clone();
+
+        reference(taps, shift, &mut expected);
+
+        let mut output_i32 = residual.iter().map(|r| *r as i32)
+                                     .collect::<Vec<_>>();
+        let mut output_i64 = residual.clone();
+
+        lpc_i32(taps, shift, bits_per_sample, &mut output_i32);
+        lpc_i64(taps, shift, &mut output_i64);
+
+        assert_eq!(&expected[..], &samples[..]);
+        assert_eq!(output_i64, expected);
+        assert!(output_i32.iter().zip(&expected)
+                          .all(|(a, b)| *a as i64 == *b));
+
+        // Every kernel, no matter if it gets picked for this order.
+        let mut narrow = residual.iter().map(|r| *r as i32)
+                                 .collect::<Vec<_>>();
+        let mut wide   = narrow.clone();
+
+        restore_any!(&mut narrow[..], taps, shift, i32);
+        restore_any!(&mut wide[..], taps, shift, i64);
+
+        if is_narrow(taps, bits_per_sample) {
+          assert!(narrow.iter().zip(&expected).all(|(a, b)| *a as i64 == *b));
+        }
+
+        assert!(wide.iter().zip(&expected).all(|(a, b)| *a as i64 == *b));
+
+        for is_narrow in [false, true].iter() {
+          let mut vector = residual.iter().map(|r| *r as i32)
+                                   .collect::<Vec<_>>();
+
+          if *is_narrow && !super::is_narrow(taps, bits_per_sample) {
+            continue;
+          }
+
+          if lpc_vector(taps, shift, *is_narrow, &mut vector) {
+            assert!(vector.iter().zip(&expected)
+                          .all(|(a, b)| *a as i64 == *b));
+          }
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_fixed_kernels() {
+    for order in 1..5 {
+      let samples  = signal(100, 20, order as u64);
+      let residual = residuals(FIXED_TAPS[order], 0, &samples);
+
+      let mut output_i32 = residual.iter().map(|r| *r as i32)
+                                   .collect::<Vec<_>>();
+      let mut output_i64 = residual.clone();
+
+      fixed_i32(order, 20, &mut output_i32);
+      fixed_i64(order, &mut output_i64);
+
+      assert_eq!(output_i64, samples);
+      assert!(output_i32.iter().zip(&samples).all(|(a, b)| *a as i64 == *b));
+    }
+  }
+}
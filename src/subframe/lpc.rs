@@ -0,0 +1,558 @@
+use std::cmp;
+use std::f64::consts::PI;
+
+use subframe::MAX_LPC_ORDER;
+
+// Range of the quantized coefficient precision, the largest one being the
+// most that fits into the four bits of the subframe, with 0b1111 being
+// invalid.
+const MIN_QLP_COEFF_PRECISION: u32 = 5;
+const MAX_QLP_COEFF_PRECISION: u32 = 15;
+
+// Largest shift that fits into the five bit signed number of the subframe.
+const MAX_QUANTIZATION_LEVEL: i32 = 15;
+
+/// Window applied to the samples before the autocorrelation gets computed.
+///
+/// The windows follow the ones from the reference encoder, where tapering
+/// off the ends of the block gives a better estimate of the coefficients
+/// than using the samples as they are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Apodization {
+  /// Rectangle with the ends tapered off by a cosine, the value being the
+  /// fraction of the window that is tapered. Zero is the same as a
+  /// rectangle and one is the same as `Hann`.
+  Tukey(f64),
+  /// Raised cosine over the whole block.
+  Hann,
+  /// Splits the block into the given number of overlapping parts, with a
+  /// Tukey window over each part and zeros everywhere else. Each part is a
+  /// separate window.
+  PartialTukey(usize),
+  /// Splits the block the same way as `PartialTukey`, except each window
+  /// leaves out one of the parts instead.
+  PunchoutTukey(usize),
+  /// Parabola over the whole block.
+  Welch,
+}
+
+impl Apodization {
+  /// Number of windows, and in turn analyses, the apodization results in.
+  pub fn windows(&self) -> usize {
+    match *self {
+      Apodization::PartialTukey(parts)  |
+      Apodization::PunchoutTukey(parts) => cmp::max(parts, 1),
+      _                                 => 1,
+    }
+  }
+}
+
+// Fraction of the tapered part of each Tukey window and the amount of
+// overlap between the parts of the partial and punchout windows, the same
+// defaults the reference encoder uses.
+const PARTIAL_TUKEY_P: f64        = 0.2;
+const PARTIAL_TUKEY_OVERLAP: f64  = 0.1;
+const PUNCHOUT_TUKEY_OVERLAP: f64 = 0.2;
+
+#[inline]
+fn raised_cosine(numerator: f64, denominator: f64) -> f64 {
+  0.5 - 0.5 * (PI * numerator / denominator).cos()
+}
+
+fn tukey(p: f64, window: &mut [f64]) {
+  let length = window.len();
+
+  if p >= 1.0 {
+    return hann(window);
+  }
+
+  fill(window, 1.0);
+
+  if p <= 0.0 {
+    return;
+  }
+
+  let np = (p / 2.0 * length as f64) as isize - 1;
+
+  if np > 0 {
+    let np = np as usize;
+
+    for n in 0..(np + 1) {
+      window[n]                   = raised_cosine(n as f64, np as f64);
+      window[length - np - 1 + n] = raised_cosine((n + np) as f64,
+                                                  np as f64);
+    }
+  }
+}
+
+fn hann(window: &mut [f64]) {
+  let length = window.len();
+
+  if length < 2 {
+    return fill(window, 1.0);
+  }
+
+  let n_1 = (length - 1) as f64;
+
+  for (n, value) in window.iter_mut().enumerate() {
+    *value = raised_cosine(2.0 * n as f64, n_1);
+  }
+}
+
+fn welch(window: &mut [f64]) {
+  let length = window.len();
+
+  if length < 2 {
+    return fill(window, 1.0);
+  }
+
+  let half = (length - 1) as f64 / 2.0;
+
+  for (n, value) in window.iter_mut().enumerate() {
+    let x = (n as f64 - half) / half;
+
+    *value = 1.0 - x * x;
+  }
+}
+
+#[inline]
+fn fill(window: &mut [f64], value: f64) {
+  for sample in window {
+    *sample = value;
+  }
+}
+
+// Start and end, as a fraction of the block, of `part` out of `parts` when
+// neighbouring parts overlap by `overlap`.
+fn part_bounds(part: usize, parts: usize, overlap: f64) -> (f64, f64) {
+  let units = 1.0 / (1.0 - overlap) - 1.0;
+  let total = parts as f64 + units;
+
+  (part as f64 / total, (part as f64 + 1.0 + units) / total)
+}
+
+// Tukey window between `start` and `end`, with zeros outside of it.
+fn partial_tukey(p: f64, start: f64, end: f64, window: &mut [f64]) {
+  let length  = window.len();
+  let start_n = (start * length as f64) as usize;
+  let end_n   = cmp::min((end * length as f64) as usize, length);
+  let np      = (p / 2.0 * (end_n - start_n) as f64) as usize;
+
+  fill(window, 0.0);
+
+  for n in start_n..end_n {
+    window[n] = if n < start_n + np {
+      raised_cosine((n - start_n + 1) as f64, np as f64)
+    } else if n + np >= end_n {
+      raised_cosine((end_n - n) as f64, np as f64)
+    } else {
+      1.0
+    };
+  }
+}
+
+// Tukey windows before `start` and after `end`, with zeros between them.
+fn punchout_tukey(p: f64, start: f64, end: f64, window: &mut [f64]) {
+  let length  = window.len();
+  let start_n = (start * length as f64) as usize;
+  let end_n   = cmp::min((end * length as f64) as usize, length);
+
+  fill(window, 0.0);
+  partial_tukey(p, 0.0, 1.0, &mut window[0..start_n]);
+  partial_tukey(p, 0.0, 1.0, &mut window[end_n..length]);
+}
+
+// Fills `output` with the `index`th window of `apodization`.
+pub fn window(apodization: Apodization, index: usize, output: &mut [f64]) {
+  match apodization {
+    Apodization::Tukey(p)             => tukey(p, output),
+    Apodization::Hann                 => hann(output),
+    Apodization::Welch                => welch(output),
+    Apodization::PartialTukey(parts)  => {
+      if parts <= 1 {
+        tukey(PARTIAL_TUKEY_P, output)
+      } else {
+        let (start, end) = part_bounds(index, parts, PARTIAL_TUKEY_OVERLAP);
+
+        partial_tukey(PARTIAL_TUKEY_P, start, end, output)
+      }
+    }
+    Apodization::PunchoutTukey(parts) => {
+      if parts <= 1 {
+        tukey(PARTIAL_TUKEY_P, output)
+      } else {
+        let (start, end) = part_bounds(index, parts,
+                                       PUNCHOUT_TUKEY_OVERLAP);
+
+        punchout_tukey(PARTIAL_TUKEY_P, start, end, output)
+      }
+    }
+  }
+}
+
+// Autocorrelation of the windowed samples for every lag up to the length
+// of `autocorrelation` minus one.
+pub fn autocorrelation(samples: &[i32], window: &[f64],
+                       windowed: &mut Vec<f64>,
+                       autocorrelation: &mut [f64]) {
+  windowed.clear();
+  windowed.extend(samples.iter().zip(window)
+                         .map(|(sample, value)| *sample as f64 * *value));
+
+  for (lag, result) in autocorrelation.iter_mut().enumerate() {
+    *result = if lag < windowed.len() {
+      windowed[lag..].iter().zip(windowed.iter())
+                     .fold(0.0, |sum, (a, b)| sum + a * b)
+    } else {
+      0.0
+    };
+  }
+}
+
+// Levinson-Durbin recursion, which computes the predictor coefficients for
+// every order up to `max_order` from the autocorrelation.
+//
+// The coefficients for an order of `n` end up in `coefficients[n - 1]`, in
+// the same order as `LPC::qlp_coefficients`, and the error of the
+// prediction in `errors[n - 1]`. The highest order that got computed gets
+// returned, which is smaller than `max_order` when the signal is perfectly
+// predicted before reaching it.
+pub fn lp_coefficients(autocorrelation: &[f64], max_order: usize,
+                       coefficients: &mut [[f64; MAX_LPC_ORDER]],
+                       errors: &mut [f64])
+                       -> usize {
+  let mut lpc   = [0.0; MAX_LPC_ORDER];
+  let mut error = autocorrelation[0];
+
+  debug_assert!(max_order <= MAX_LPC_ORDER);
+
+  for i in 0..max_order {
+    let mut reflection = -autocorrelation[i + 1];
+
+    for j in 0..i {
+      reflection -= lpc[j] * autocorrelation[i - j];
+    }
+
+    reflection /= error;
+    lpc[i]      = reflection;
+
+    for j in 0..(i / 2) {
+      let temp = lpc[j];
+
+      lpc[j]         += reflection * lpc[i - 1 - j];
+      lpc[i - 1 - j] += reflection * temp;
+    }
+
+    if i % 2 == 1 {
+      lpc[i / 2] += lpc[i / 2] * reflection;
+    }
+
+    error *= 1.0 - reflection * reflection;
+
+    // The filter coefficients get negated into predictor coefficients.
+    for j in 0..(i + 1) {
+      coefficients[i][j] = -lpc[j];
+    }
+
+    errors[i] = error;
+
+    if error == 0.0 {
+      return i + 1;
+    }
+  }
+
+  max_order
+}
+
+// Estimate of the number of bits each residual sample takes, based on the
+// error of the prediction.
+fn expected_bits_per_sample(error: f64, error_scale: f64) -> f64 {
+  if error > 0.0 {
+    let bits = 0.5 * (error_scale * error).log2();
+
+    if bits >= 0.0 { bits } else { 0.0 }
+  } else if error < 0.0 {
+    1e32
+  } else {
+    0.0
+  }
+}
+
+// Order that is estimated to result in the smallest subframe, out of the
+// `errors` that `lp_coefficients` computed, where each order costs
+// `overhead_bits` for the warm up sample and coefficient.
+pub fn best_order(errors: &[f64], block_size: usize, overhead_bits: usize)
+                  -> usize {
+  let error_scale = 0.5 / block_size as f64;
+  let mut best    = (1, ::std::f64::MAX);
+
+  for (index, error) in errors.iter().enumerate() {
+    let order = index + 1;
+    let bits  = expected_bits_per_sample(*error, error_scale) *
+                (block_size - order) as f64 +
+                (order * overhead_bits) as f64;
+
+    if bits < best.1 {
+      best = (order, bits);
+    }
+  }
+
+  best.0
+}
+
+// Precision of the quantized coefficients when it isn't set explicitly,
+// which goes up with the bits per sample and block size.
+pub fn default_precision(bits_per_sample: usize, block_size: usize) -> u32 {
+  if bits_per_sample < 16 {
+    cmp::max(MIN_QLP_COEFF_PRECISION, 2 + bits_per_sample as u32 / 2)
+  } else if bits_per_sample == 16 {
+    match block_size {
+      0...192     => 7,
+      193...384   => 8,
+      385...576   => 9,
+      577...1152  => 10,
+      1153...2304 => 11,
+      2305...4608 => 12,
+      _           => 13,
+    }
+  } else {
+    match block_size {
+      0...384    => MAX_QLP_COEFF_PRECISION - 2,
+      385...1152 => MAX_QLP_COEFF_PRECISION - 1,
+      _          => MAX_QLP_COEFF_PRECISION,
+    }
+  }
+}
+
+// Precision to quantize the coefficients of an `order` predictor to, out
+// of the `requested` one.
+//
+// For smaller samples the precision gets lowered, when needed, so the sum
+// of the prediction fits inside of 32 bits. That way decoders can keep
+// using 32 bit math for these streams.
+pub fn precision(requested: u32, bits_per_sample: usize, order: usize)
+                 -> u32 {
+  let requested = cmp::min(requested, MAX_QLP_COEFF_PRECISION);
+
+  if bits_per_sample <= 17 {
+    let log2_order = 31 - (order as u32).leading_zeros();
+    let limit      = 32 - bits_per_sample as u32 - log2_order;
+
+    cmp::min(requested, cmp::max(limit, MIN_QLP_COEFF_PRECISION))
+  } else {
+    requested
+  }
+}
+
+// Quantize `coefficients` into integers of `precision` bits, returning the
+// shift needed to scale the prediction back down.
+//
+// The rounding error of each coefficient carries over to the next one. No
+// shift gets returned when the coefficients are all zero or are too large
+// to be quantized with a shift that isn't negative.
+pub fn quantize(coefficients: &[f64], precision: u32, output: &mut [i32])
+                -> Option<i8> {
+  debug_assert!(precision >= 1 && precision <= MAX_QLP_COEFF_PRECISION);
+
+  let precision = precision as i32 - 1;
+  let max_value = (1 << precision) - 1;
+  let min_value = -(1 << precision);
+
+  let max_coefficient = coefficients.iter().fold(0.0, |max, value: &f64| {
+    if value.abs() > max { value.abs() } else { max }
+  });
+
+  if max_coefficient <= 0.0 {
+    return None;
+  }
+
+  let log2_max = max_coefficient.log2().floor() as i32;
+  let shift    = cmp::min(precision - log2_max - 1, MAX_QUANTIZATION_LEVEL);
+
+  if shift < 0 {
+    return None;
+  }
+
+  let scale     = (1i64 << shift) as f64;
+  let mut error = 0.0;
+
+  for (coefficient, result) in coefficients.iter().zip(output.iter_mut()) {
+    error += coefficient * scale;
+
+    let value = cmp::max(min_value,
+                         cmp::min(max_value, error.round() as i32));
+
+    error  -= value as f64;
+    *result = value;
+  }
+
+  Some(shift as i8)
+}
+
+// Compute the residual of the linear predictor made up of `coefficients`
+// and `shift`.
+//
+// Like the fixed version, the residual gets computed with a wider integer
+// and false gets returned when any value doesn't fit inside of an `i32`.
+pub fn residual(samples: &[i32], coefficients: &[i32], shift: i8,
+                residual: &mut Vec<i32>)
+                -> bool {
+  let order = coefficients.len();
+
+  residual.clear();
+
+  for i in order..samples.len() {
+    let prediction = coefficients.iter()
+                       .zip(samples[(i - order)..i].iter().rev())
+                       .fold(0, |sum, (coefficient, sample)|
+                         sum + (*coefficient as i64) * (*sample as i64));
+    let value      = samples[i] as i64 - (prediction >> shift);
+
+    if value < i32::min_value() as i64 || value > i32::max_value() as i64 {
+      return false;
+    }
+
+    residual.push(value as i32);
+  }
+
+  true
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use subframe::MAX_LPC_ORDER;
+
+  fn assert_close(values: &[f64], expected: &[f64]) {
+    assert_eq!(values.len(), expected.len());
+
+    for (value, expected) in values.iter().zip(expected) {
+      assert!((value - expected).abs() < 1e-9, "{} != {}", value, expected);
+    }
+  }
+
+  #[test]
+  fn test_window() {
+    let mut output = [0.0; 9];
+
+    window(Apodization::Tukey(0.0), 0, &mut output);
+    assert_close(&output, &[1.0; 9]);
+
+    window(Apodization::Hann, 0, &mut output);
+    assert_close(&output[0..5], &[0.0, 0.1464466094067262, 0.5,
+                                  0.8535533905932737, 1.0]);
+    assert_close(&output[5..9], &[0.8535533905932737, 0.5,
+                                  0.1464466094067262, 0.0]);
+
+    window(Apodization::Tukey(1.0), 0, &mut output);
+    assert_close(&output[0..3], &[0.0, 0.1464466094067262, 0.5]);
+
+    window(Apodization::Welch, 0, &mut output);
+    assert_close(&output, &[0.0, 0.4375, 0.75, 0.9375, 1.0, 0.9375, 0.75,
+                            0.4375, 0.0]);
+
+    let mut output = [0.0; 20];
+
+    window(Apodization::Tukey(0.5), 0, &mut output);
+    assert_close(&output[0..5], &[0.0, 0.1464466094067262, 0.5,
+                                  0.8535533905932737, 1.0]);
+    assert_close(&output[5..15], &[1.0; 10]);
+    assert_eq!(output[19], 0.0);
+
+    assert_eq!(Apodization::PartialTukey(2).windows(), 2);
+    assert_eq!(Apodization::PunchoutTukey(3).windows(), 3);
+    assert_eq!(Apodization::Hann.windows(), 1);
+
+    window(Apodization::PartialTukey(2), 0, &mut output);
+    assert!(output[0..10].iter().all(|value| *value > 0.0));
+    assert!(output[11..20].iter().all(|value| *value == 0.0));
+
+    window(Apodization::PartialTukey(2), 1, &mut output);
+    assert!(output[0..9].iter().all(|value| *value == 0.0));
+    assert!(output[9..20].iter().all(|value| *value > 0.0));
+
+    window(Apodization::PunchoutTukey(3), 1, &mut output);
+    assert!(output[0..6].iter().all(|value| *value > 0.0));
+    assert!(output[6..13].iter().all(|value| *value == 0.0));
+    assert!(output[13..20].iter().all(|value| *value > 0.0));
+  }
+
+  #[test]
+  fn test_lp_coefficients() {
+    // First order process where each sample is half of the last one.
+    let autocorrelation  = [4.0, 2.0, 1.0, 0.5];
+    let mut coefficients = [[0.0; MAX_LPC_ORDER]; MAX_LPC_ORDER];
+    let mut errors       = [0.0; MAX_LPC_ORDER];
+
+    assert_eq!(lp_coefficients(&autocorrelation, 3, &mut coefficients,
+                               &mut errors), 3);
+
+    assert_close(&coefficients[0][0..1], &[0.5]);
+    assert_close(&coefficients[1][0..2], &[0.5, 0.0]);
+    assert_close(&coefficients[2][0..3], &[0.5, 0.0, 0.0]);
+    assert_close(&errors[0..3], &[3.0, 3.0, 3.0]);
+
+    assert_eq!(best_order(&errors[0..3], 100, 20), 1);
+
+    // Perfectly predictable signal stops at the first order.
+    let autocorrelation = [1.0, 1.0, 1.0];
+
+    assert_eq!(lp_coefficients(&autocorrelation, 2, &mut coefficients,
+                               &mut errors), 1);
+    assert_close(&coefficients[0][0..1], &[1.0]);
+  }
+
+  #[test]
+  fn test_autocorrelation() {
+    let mut windowed = Vec::new();
+    let mut result   = [0.0; 4];
+
+    autocorrelation(&[1, 2, 3], &[1.0, 1.0, 0.5], &mut windowed,
+                    &mut result);
+
+    assert_close(&result, &[7.25, 5.0, 1.5, 0.0]);
+  }
+
+  #[test]
+  fn test_quantize() {
+    let mut output = [0; 3];
+
+    assert_eq!(quantize(&[1.5, -0.75, 0.1], 5, &mut output), Some(3));
+    assert_eq!(&output, &[12, -6, 1]);
+
+    // The rounding error carries over to the next coefficient.
+    assert_eq!(quantize(&[0.3, 0.3, 0.3], 4, &mut output), Some(4));
+    assert_eq!(&output, &[5, 5, 4]);
+
+    // Clamped to the largest value that fits.
+    assert_eq!(quantize(&[1.99, 0.0], 3, &mut output[0..2]), Some(1));
+    assert_eq!(&output[0..2], &[3, 1]);
+
+    assert_eq!(quantize(&[0.0, 0.0], 12, &mut output[0..2]), None);
+    assert_eq!(quantize(&[1e6], 12, &mut output[0..1]), None);
+  }
+
+  #[test]
+  fn test_precision() {
+    assert_eq!(default_precision(8, 4096), 6);
+    assert_eq!(default_precision(16, 1152), 10);
+    assert_eq!(default_precision(16, 4096), 12);
+    assert_eq!(default_precision(24, 4096), 15);
+
+    assert_eq!(precision(12, 16, 8), 12);
+    assert_eq!(precision(15, 17, 32), 10);
+    assert_eq!(precision(20, 24, 32), 15);
+  }
+
+  #[test]
+  fn test_residual() {
+    let samples    = [10, 20, 30, 40, 50];
+    let mut output = Vec::new();
+
+    assert!(residual(&samples, &[4, -2], 1, &mut output));
+    assert_eq!(&output, &[0, 0, 0]);
+
+    assert!(!residual(&[i32::max_value(), i32::min_value()], &[1], 0,
+                      &mut output));
+  }
+}
@@ -1,6 +1,9 @@
 mod types;
 mod parser;
 mod decoder;
+mod encoder;
+mod kernel;
+mod lpc;
 
 pub use self::types::{
   MAX_FIXED_ORDER, MAX_LPC_ORDER,
@@ -10,5 +13,11 @@ pub use self::types::{
   EntropyCodingMethod, CodingMethod, PartitionedRice, PartitionedRiceContents,
 };
 
+pub(crate) use self::types::Buffers;
+
 pub use self::parser::subframe_parser;
-pub use self::decoder::decode;
+pub use self::encoder::Config;
+pub use self::lpc::Apodization;
+pub(crate) use self::parser::{adjust_bits_per_sample, subframe_parser_into};
+pub(crate) use self::decoder::decode;
+pub(crate) use self::encoder::{encode, estimate_bits, write};
@@ -1,53 +1,14 @@
-use nom::{self, IResult, Err, Needed};
+use nom::IResult;
 
-use frame::{self, ChannelAssignment};
-use subframe::{self, Subframe, CodingMethod, PartitionedRiceContents};
-use utility::{ErrorKind, Sample, power_of_two};
-
-// Parser used to parse unary notation. Naming the parser `leading_zeros`
-// was something that felt more clear in the code. It actually tells the
-// caller what the parser doing considering unary notation can -- and more
-// commonly -- be leading ones.
-pub fn leading_zeros(input: (&[u8], usize)) -> IResult<(&[u8], usize), u32> {
-  let (bytes, mut offset) = input;
-
-  let mut index     = 0;
-  let mut count     = 0;
-  let mut is_parsed = false;
-  let bytes_len     = bytes.len();
-
-  for (i, _byte) in bytes.iter().enumerate() {
-    // Clear the number of offset bits
-    let byte  = *_byte << offset;
-    let zeros = byte.leading_zeros() as usize;
-
-    index = i;
-
-    if byte > 0 {
-      is_parsed = true;
-      count    += zeros;
-      offset   += zeros + 1;
-
-      if offset >= 8 {
-        index  += 1;
-        offset -= 8;
-      }
-
-      break;
-    } else {
-      count += zeros - offset;
-      offset = 0;
-    }
-  }
+use std::cmp;
+use std::mem;
 
-  if is_parsed {
-    IResult::Done((&bytes[index..], offset), count as u32)
-  } else if index + 2 > bytes_len {
-    IResult::Incomplete(Needed::Size(index + 2))
-  } else {
-    IResult::Error(Err::Position(nom::ErrorKind::TakeUntil, (bytes, offset)))
-  }
-}
+use frame::{self, ChannelAssignment};
+use subframe::{
+  self,
+  Buffers, Subframe, CodingMethod, PartitionedRiceContents,
+};
+use utility::{BitReader, ErrorKind, Sample, power_of_two, to_iresult};
 
 // The channel's bits per sample that gets adjusted are the side channels
 // for `LeftSide`, `MidpointSide`, and `RightSide`. The `Independent`
@@ -84,218 +45,295 @@ pub fn subframe_parser<'a, S>(input: (&'a [u8], usize),
                               buffer: &mut [S])
                               -> IResult<(&'a [u8], usize), Subframe,
                                          ErrorKind>
+ where S: Sample {
+  let mut reader   = BitReader::with_offset(input.0, input.1);
+  let mut subframe = Subframe::default();
+  let mut buffers  = Buffers::default();
+
+  let result = subframe_parser_into(&mut reader, frame_header, *channel,
+                                    &mut subframe, &mut buffers, buffer);
+
+  // Iterate over the current channel being parsed. This probably should be
+  // abstracted away, but for now this is the solution.
+  if result.is_ok() {
+    *channel += 1;
+  }
+
+  to_iresult(input, reader.remaining(), result.map(|_| subframe))
+}
+
+// Parses a single channel of audio data into `subframe`, which first gives
+// back the buffers it holds to `buffers` so they get reused for the new
+// residual and Rice parameters.
+pub fn subframe_parser_into<S>(reader: &mut BitReader,
+                               frame_header: &frame::Header,
+                               channel: usize,
+                               subframe: &mut Subframe,
+                               buffers: &mut Buffers,
+                               buffer: &mut [S])
+                               -> Result<(), ErrorKind>
  where S: Sample {
   let block_size      = frame_header.block_size as usize;
-  let bits_per_sample = adjust_bits_per_sample(frame_header, *channel);
-  let start           = *channel * block_size;
-  let end             = (*channel + 1) * block_size;
+  let bits_per_sample = adjust_bits_per_sample(frame_header, channel);
+  let start           = channel * block_size;
+  let end             = (channel + 1) * block_size;
   let buffer_slice    = &mut buffer[start..end];
 
-  chain!(input,
-    subframe_header: header ~
-    wasted_bits: map!(
-      cond!(subframe_header.1,
-      to_custom_error!(leading_zeros, LeadingZerosParser)),
-      |option: Option<u32>| option.map_or(0, |zeros| zeros + 1)
-    ) ~
-    subframe_data: apply!(data,
-      bits_per_sample - (wasted_bits as usize),
-      block_size, subframe_header.0,
-      buffer_slice),
-    || {
-      // Iterate over the current channel being parsed. This probably should
-      // be abstracted away, but for now this is the solution.
-      *channel += 1;
-
-      Subframe {
-        data: subframe_data,
-        wasted_bits: wasted_bits,
-      }
-    }
-  )
+  buffers.recycle(&mut subframe.data);
+
+  let (subframe_type, has_wasted_bits) = try!(header(reader));
+
+  let wasted_bits = if has_wasted_bits {
+    try!(reader.read_unary()) + 1
+  } else {
+    0
+  };
+
+  if wasted_bits as usize >= bits_per_sample {
+    return Err(ErrorKind::InvalidSubframeHeader);
+  }
+
+  subframe.data        = try!(data(reader,
+                                   bits_per_sample - wasted_bits as usize,
+                                   block_size, subframe_type, buffers,
+                                   buffer_slice));
+  subframe.wasted_bits = wasted_bits;
+
+  Ok(())
+}
+
+// Replace any error, other than running out of input, with `kind`.
+fn or_error<T>(result: Result<T, ErrorKind>, kind: ErrorKind)
+               -> Result<T, ErrorKind> {
+  match result {
+    Err(ErrorKind::Incomplete(needed)) => Err(ErrorKind::Incomplete(needed)),
+    Err(_)                             => Err(kind),
+    Ok(value)                          => Ok(value),
+  }
 }
 
 // Parses the first byte of the subframe. The first bit must be zero to
 // prevent sync-fooling, next six bits determines the subframe data type.
 // Last bit is is there is wasted bits per sample, value one being true.
-pub fn header(input: (&[u8], usize))
-              -> IResult<(&[u8], usize), (usize, bool), ErrorKind> {
-  let (i, byte) = try_parser! {
-    to_custom_error!(input, take_bits!(u8, 8), SubframeHeaderParser)
-  };
+pub fn header(reader: &mut BitReader) -> Result<(usize, bool), ErrorKind> {
+  let byte = try!(reader.read_bits(8));
 
   let is_valid        = (byte >> 7) == 0;
   let subframe_type   = (byte >> 1) & 0b111111;
   let has_wasted_bits = (byte & 0b01) == 1;
 
   if is_valid {
-    IResult::Done(i, (subframe_type as usize, has_wasted_bits))
+    Ok((subframe_type as usize, has_wasted_bits))
   } else {
-    IResult::Error(Err::Position(
-      nom::ErrorKind::Custom(ErrorKind::InvalidSubframeHeader), input))
+    Err(ErrorKind::InvalidSubframeHeader)
   }
 }
 
-fn data<'a, S>(input: (&'a [u8], usize),
-               bits_per_sample: usize,
-               block_size: usize,
-               subframe_type: usize,
-               buffer: &mut [S])
-               -> IResult<(&'a [u8], usize), subframe::Data, ErrorKind>
+fn data<S>(reader: &mut BitReader,
+           bits_per_sample: usize,
+           block_size: usize,
+           subframe_type: usize,
+           buffers: &mut Buffers,
+           buffer: &mut [S])
+           -> Result<subframe::Data, ErrorKind>
  where S: Sample{
   match subframe_type {
-    0b000000            => constant(input, bits_per_sample),
-    0b000001            => verbatim(input, bits_per_sample, block_size)
-                             .map_err(to_custom_error!(VerbatimParser)),
-    0b001000...0b001100 => fixed(input, subframe_type & 0b0111,
-                                 bits_per_sample, block_size, buffer),
-    0b100000...0b111111 => lpc(input, (subframe_type & 0b011111) + 1,
-                               bits_per_sample, block_size, buffer),
-    _                   => IResult::Error(Err::Position(
-                             nom::ErrorKind::Custom(ErrorKind::Unknown),
-                             input))
+    0b000000            => {
+      or_error(constant(reader, bits_per_sample), ErrorKind::ConstantParser)
+    }
+    0b000001            => {
+      or_error(verbatim(reader, bits_per_sample, block_size, buffers),
+               ErrorKind::VerbatimParser)
+    }
+    0b001000...0b001100 => {
+      or_error(fixed(reader, subframe_type & 0b0111, bits_per_sample,
+                     block_size, buffers, buffer),
+               ErrorKind::FixedParser)
+    }
+    0b100000...0b111111 => {
+      or_error(lpc(reader, (subframe_type & 0b011111) + 1, bits_per_sample,
+                   block_size, buffers, buffer),
+               ErrorKind::LPCParser)
+    }
+    _                   => Err(ErrorKind::Unknown),
   }
 }
 
-pub fn constant(input: (&[u8], usize), bits_per_sample: usize)
-                -> IResult<(&[u8], usize), subframe::Data, ErrorKind> {
-  to_custom_error!(input,
-    map!(take_signed_bits!(bits_per_sample), subframe::Data::Constant),
-    ConstantParser)
+pub fn constant(reader: &mut BitReader, bits_per_sample: usize)
+                -> Result<subframe::Data, ErrorKind> {
+  reader.read_wide_signed_bits(bits_per_sample)
+        .map(subframe::Data::Constant)
 }
 
-pub fn fixed<'a, S>(input: (&'a [u8], usize),
-                    order: usize,
-                    bits_per_sample: usize,
-                    block_size: usize,
-                    buffer: &mut [S])
-                    -> IResult<(&'a [u8], usize), subframe::Data, ErrorKind>
+// Read a signed value of `bits_per_sample` into each value of `output`.
+fn signed_values(reader: &mut BitReader, bits_per_sample: usize,
+                 output: &mut [i32])
+                 -> Result<(), ErrorKind> {
+  for value in output {
+    *value = try!(reader.read_signed_bits(bits_per_sample));
+  }
+
+  Ok(())
+}
+
+// Same as `signed_values` for samples, which can be as large as 33 bits.
+fn sample_values(reader: &mut BitReader, bits_per_sample: usize,
+                 output: &mut [i64])
+                 -> Result<(), ErrorKind> {
+  for value in output {
+    *value = try!(reader.read_wide_signed_bits(bits_per_sample));
+  }
+
+  Ok(())
+}
+
+pub fn fixed<S>(reader: &mut BitReader,
+                order: usize,
+                bits_per_sample: usize,
+                block_size: usize,
+                buffers: &mut Buffers,
+                buffer: &mut [S])
+                -> Result<subframe::Data, ErrorKind>
  where S: Sample {
   let mut warmup = [0; subframe::MAX_FIXED_ORDER];
 
-  to_custom_error!(input,
-    chain!(
-      count_slice!(take_signed_bits!(bits_per_sample),
-                   &mut warmup[0..order]) ~
-      entropy_coding_method: apply!(residual, order, block_size, buffer),
-      || {
-        subframe::Data::Fixed(subframe::Fixed {
-          entropy_coding_method: entropy_coding_method,
-          order: order as u8,
-          warmup: warmup,
-          residual: Vec::new(),
-        })
-      }
-    ),
-    FixedParser)
+  try!(sample_values(reader, bits_per_sample, &mut warmup[0..order]));
+
+  let entropy_coding_method = try!(residual(reader, order, block_size,
+                                            buffers, buffer));
+
+  Ok(subframe::Data::Fixed(subframe::Fixed {
+    entropy_coding_method: entropy_coding_method,
+    order: order as u8,
+    warmup: warmup,
+    residual: mem::replace(&mut buffers.residual, Vec::new()),
+  }))
 }
 
 // This parser finds the bit length for each quantized linear predictor
 // coefficient. To preven sync fooling, four bit value cant be all onces.
-fn qlp_coefficient_precision(input: (&[u8], usize))
-                             -> IResult<(&[u8], usize), u8> {
-  let (i, precision) = try_parse!(input, take_bits!(u8, 4));
+fn qlp_coefficient_precision(reader: &mut BitReader)
+                             -> Result<u8, ErrorKind> {
+  let precision = try!(reader.read_bits(4)) as u8;
 
   if precision == 0b1111 {
-    IResult::Error(Err::Position(nom::ErrorKind::Digit, input))
+    Err(ErrorKind::Unknown)
   } else {
-    IResult::Done(i, precision + 1)
+    Ok(precision + 1)
   }
 }
 
-pub fn lpc<'a, S>(input: (&'a [u8], usize),
-                  order: usize,
-                  bits_per_sample: usize,
-                  block_size: usize,
-                  buffer: &mut [S])
-                  -> IResult<(&'a [u8], usize), subframe::Data, ErrorKind>
+pub fn lpc<S>(reader: &mut BitReader,
+              order: usize,
+              bits_per_sample: usize,
+              block_size: usize,
+              buffers: &mut Buffers,
+              buffer: &mut [S])
+              -> Result<subframe::Data, ErrorKind>
  where S: Sample {
   let mut warmup           = [0; subframe::MAX_LPC_ORDER];
   let mut qlp_coefficients = [0; subframe::MAX_LPC_ORDER];
 
-  to_custom_error!(input,
-    chain!(
-      count_slice!(take_signed_bits!(bits_per_sample),
-                   &mut warmup[0..order]) ~
-      qlp_coeff_precision: qlp_coefficient_precision ~
-      quantization_level: take_signed_bits!(i8, 5) ~
-      count_slice!(
-        take_signed_bits!(qlp_coeff_precision as usize),
-        &mut qlp_coefficients[0..order]
-      ) ~
-      entropy_coding_method: apply!(residual, order, block_size, buffer),
-      || {
-        subframe::Data::LPC(subframe::LPC {
-          entropy_coding_method: entropy_coding_method,
-          order: order as u8,
-          qlp_coeff_precision: qlp_coeff_precision,
-          quantization_level: quantization_level,
-          qlp_coefficients: qlp_coefficients,
-          warmup: warmup,
-          residual: Vec::new(),
-        })
-      }
-    ),
-    LPCParser)
+  try!(sample_values(reader, bits_per_sample, &mut warmup[0..order]));
+
+  let qlp_coeff_precision = try!(qlp_coefficient_precision(reader));
+  let quantization_level  = try!(reader.read_signed_bits(5)) as i8;
+
+  try!(signed_values(reader, qlp_coeff_precision as usize,
+                     &mut qlp_coefficients[0..order]));
+
+  let entropy_coding_method = try!(residual(reader, order, block_size,
+                                            buffers, buffer));
+
+  Ok(subframe::Data::LPC(subframe::LPC {
+    entropy_coding_method: entropy_coding_method,
+    order: order as u8,
+    qlp_coeff_precision: qlp_coeff_precision,
+    quantization_level: quantization_level,
+    qlp_coefficients: qlp_coefficients,
+    warmup: warmup,
+    residual: mem::replace(&mut buffers.residual, Vec::new()),
+  }))
 }
 
-pub fn verbatim(input: (&[u8], usize),
+pub fn verbatim(reader: &mut BitReader,
                 bits_per_sample: usize,
-                block_size: usize)
-                -> IResult<(&[u8], usize), subframe::Data> {
-  map!(input, count!(take_signed_bits!(bits_per_sample), block_size),
-       subframe::Data::Verbatim)
+                block_size: usize,
+                buffers: &mut Buffers)
+                -> Result<subframe::Data, ErrorKind> {
+  {
+    let samples = &mut buffers.verbatim;
+
+    samples.clear();
+    samples.resize(block_size, 0);
+
+    try!(sample_values(reader, bits_per_sample, samples));
+  }
+
+  let samples = mem::replace(&mut buffers.verbatim, Vec::new());
+
+  Ok(subframe::Data::Verbatim(samples))
 }
 
 // Parser for figuring out the partitioned Rice coding, which there are only
 // two, and the parser with fail when value is greater than one.
-fn coding_method(input: (&[u8], usize))
-                 -> IResult<(&[u8], usize), CodingMethod> {
-  let (i, method) = try_parse!(input, take_bits!(u8, 2));
-
-  match method {
-    0 => IResult::Done(i, CodingMethod::PartitionedRice),
-    1 => IResult::Done(i, CodingMethod::PartitionedRice2),
-    _ => IResult::Error(Err::Position(nom::ErrorKind::Alt, input)),
+fn coding_method(reader: &mut BitReader) -> Result<CodingMethod, ErrorKind> {
+  match try!(reader.read_bits(2)) {
+    0 => Ok(CodingMethod::PartitionedRice),
+    1 => Ok(CodingMethod::PartitionedRice2),
+    _ => Err(ErrorKind::Unknown),
   }
 }
 
-fn residual<'a, S>(input: (&'a [u8], usize),
-                   predictor_order: usize,
-                   block_size: usize,
-                   buffer: &mut [S])
-                   -> IResult<(&'a [u8], usize),
-                              subframe::EntropyCodingMethod>
+// Parses the residual into `buffer`, after the warm up samples, along with
+// keeping an unaltered copy of each value inside `buffers.residual`.
+fn residual<S>(reader: &mut BitReader,
+               predictor_order: usize,
+               block_size: usize,
+               buffers: &mut Buffers,
+               buffer: &mut [S])
+               -> Result<subframe::EntropyCodingMethod, ErrorKind>
  where S: Sample {
-  let (i, data) = try_parse!(input,
-                    pair!(coding_method, take_bits!(u32, 4)));
-
-  let (method, order) = data;
+  let method = try!(coding_method(reader));
+  let order  = try!(reader.read_bits(4));
 
-  rice_partition(i, order, predictor_order, block_size, method, buffer)
+  rice_partition(reader, order, predictor_order, block_size, method,
+                 buffers, buffer)
 }
 
-fn rice_partition<'a, S>(input: (&'a [u8], usize),
-                         partition_order: u32,
-                         predictor_order: usize,
-                         block_size: usize,
-                         method: CodingMethod,
-                         buffer: &mut [S])
-                         -> IResult<(&'a [u8], usize),
-                                    subframe::EntropyCodingMethod>
+fn rice_partition<S>(reader: &mut BitReader,
+                     partition_order: u32,
+                     predictor_order: usize,
+                     block_size: usize,
+                     method: CodingMethod,
+                     buffers: &mut Buffers,
+                     buffer: &mut [S])
+                     -> Result<subframe::EntropyCodingMethod, ErrorKind>
  where S: Sample {
   let (param_size, escape_code) = match method {
     CodingMethod::PartitionedRice  => (4, 0b1111),
     CodingMethod::PartitionedRice2 => (5, 0b11111),
   };
 
+  // The first partition has to fit the warm up samples, which also keeps
+  // the partitions from outnumbering the samples.
+  if (block_size >> partition_order) < cmp::max(predictor_order, 1) {
+    return Err(ErrorKind::Unknown);
+  }
+
   // Adjust block size to not include allocation for warm up samples
   let partitions = power_of_two(partition_order) as usize;
   let residual   = &mut buffer[predictor_order..];
 
-  let mut mut_input = input;
-  let mut sample    = 0;
-  let mut contents  = PartitionedRiceContents::new(partitions);
+  let mut sample = 0;
+  let residuals  = &mut buffers.residual;
+  let rice       = &mut buffers.rice;
+
+  residuals.clear();
+  residuals.resize(block_size - predictor_order, 0);
+
+  rice.clear();
+  rice.resize(partitions * 2, 0);
 
   for partition in 0..partitions {
     let offset = if partition_order == 0 {
@@ -308,192 +346,66 @@ fn rice_partition<'a, S>(input: (&'a [u8], usize),
     let start = sample;
     let end   = sample + offset;
 
-    let result = chain!(mut_input,
-      rice_parameter: take_bits!(u32, param_size) ~
-      size: cond!(rice_parameter == escape_code, take_bits!(usize, 5)) ~
-      apply!(residual_data,
-        size, rice_parameter,
-        &mut contents.raw_bits()[partition],
-        &mut residual[start..end]
-      ),
-      || { rice_parameter }
-    );
-
-    match result {
-      IResult::Done(i, parameter) => {
-        mut_input = i;
-        sample    = end;
-
-        contents.parameters()[partition] = parameter;
-      }
-      IResult::Error(error)       => return IResult::Error(error),
-      IResult::Incomplete(need)   => return IResult::Incomplete(need),
-    }
-  }
+    let rice_parameter = try!(reader.read_bits(param_size));
+    let values         = &mut residuals[start..end];
 
-  let entropy_coding_method = subframe::EntropyCodingMethod {
-    method_type: method,
-    data: subframe::PartitionedRice {
-      order: partition_order,
-      contents: contents,
-    },
-  };
-
-  IResult::Done(mut_input, entropy_coding_method)
-}
-
-fn residual_data<'a, S>(input: (&'a [u8], usize),
-                        option: Option<usize>,
-                        rice_parameter: u32,
-                        raw_bit: &mut u32,
-                        samples: &mut [S])
-                        -> IResult<(&'a [u8], usize), ()>
- where S: Sample {
-  if let Some(size) = option {
-    unencoded_residuals(input, size, raw_bit, samples)
-  } else {
-    encoded_residuals(input, rice_parameter, raw_bit, samples)
-  }
-}
-
-fn unencoded_residuals<'a, S>(input: (&'a [u8], usize),
-                              bits_per_sample: usize,
-                              raw_bit: &mut u32,
-                              samples: &mut [S])
-                              -> IResult<(&'a [u8], usize), ()>
- where S: Sample {
-  let length = samples.len();
-
-  let mut count     = 0;
-  let mut is_error  = false;
-  let mut mut_input = input;
+    if rice_parameter == escape_code {
+      let size = try!(reader.read_bits(5)) as usize;
 
-  *raw_bit = bits_per_sample as u32;
+      rice[partitions + partition] = size as u32;
 
-  for sample in samples {
-    match take_signed_bits!(mut_input, bits_per_sample) {
-      IResult::Done(i, value) => {
-        mut_input = i;
-        count    += 1;
-
-        *sample = S::from_i32_lossy(value)
-      }
-      IResult::Error(_)       => {
-        is_error = true;
+      try!(signed_values(reader, size, values));
+    } else {
+      try!(reader.read_rice(rice_parameter, values));
+    }
 
-        break;
-      }
-      IResult::Incomplete(_)  => break,
+    for (sample, value) in residual[start..end].iter_mut().zip(values) {
+      *sample = S::from_i32_lossy(*value);
     }
-  }
 
-  if is_error {
-    IResult::Error(Err::Position(nom::ErrorKind::Count, input))
-  } else if count == length {
-    IResult::Done(mut_input, ())
-  } else {
-    IResult::Incomplete(Needed::Unknown)
+    rice[partition] = rice_parameter;
+    sample          = end;
   }
-}
-
-fn encoded_residuals<'a, S>(input: (&'a [u8], usize),
-                            parameter: u32,
-                            raw_bit: &mut u32,
-                            samples: &mut [S])
-                            -> IResult<(&'a [u8], usize), ()>
- where S: Sample {
-  let length  = samples.len();
-  let modulus = power_of_two(parameter);
-
-  let mut count     = 0;
-  let mut is_error  = false;
-  let mut mut_input = input;
-
-  *raw_bit = 0;
-
-  for sample in samples {
-    let result = chain!(mut_input,
-      quotient: leading_zeros ~
-      // TODO: Figure out the varied remainder bit size
-      remainder: take_bits!(u32, parameter as usize),
-      || {
-        let value = quotient * modulus + remainder;
-
-        S::from_i32_lossy(((value as i32) >> 1) ^ -((value as i32) & 1))
-      });
-
-    match result {
-      IResult::Done(i, value) => {
-        mut_input = i;
-        count    += 1;
-
-        *sample = value
-      }
-      IResult::Error(_)       => {
-        is_error = true;
 
-        break;
-      }
-      IResult::Incomplete(_)  => break,
-    }
-  }
+  // Any samples after the last partition aren't part of the residual.
+  residuals.truncate(sample);
 
-  if is_error {
-    IResult::Error(Err::Position(nom::ErrorKind::Count, input))
-  } else if count == length {
-    IResult::Done(mut_input, ())
-  } else {
-    IResult::Incomplete(Needed::Unknown)
-  }
+  Ok(subframe::EntropyCodingMethod {
+    method_type: method,
+    data: subframe::PartitionedRice {
+      order: partition_order,
+      contents: PartitionedRiceContents {
+        capacity: partitions,
+        data: mem::replace(rice, Vec::new()),
+      },
+    },
+  })
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
-  use nom::{self, IResult, Err, Needed};
+  use nom::{self, IResult, Err};
 
   use frame::{self, ChannelAssignment, NumberType};
-  use utility::ErrorKind;
+  use utility::{BitReader, ErrorKind, to_iresult};
 
   use subframe::{
-    Data,
+    Buffers, Data,
     Fixed, LPC,
     EntropyCodingMethod, CodingMethod, PartitionedRice,
     PartitionedRiceContents,
   };
 
-  #[test]
-  fn test_leading_zeros() {
-    let inputs  = [ (&[0b10000000][..], 0)
-                  , (&[0b11000000][..], 1)
-                  , (&[0b00000001][..], 0)
-                  , (&[0b11111111][..], 7)
-                  , (&[0b00000000, 0b10000000][..], 0)
-                  , (&[0b10000000, 0b10000000][..], 1)
-                  , (&[0b00000000, 0b00000001][..], 0)
-                  , (&[0b11111110, 0b00000010][..], 7)
-                  , (&[0b10101010, 0b00000000][..], 7)
-                  ];
-    let results = [ IResult::Done((&inputs[0].0[..], 1), 0)
-                  , IResult::Done((&inputs[1].0[..], 2), 0)
-                  , IResult::Done((&[][..], 0), 7)
-                  , IResult::Done((&[][..], 0), 0)
-                  , IResult::Done((&inputs[4].0[1..], 1), 8)
-                  , IResult::Done((&inputs[5].0[1..], 1), 7)
-                  , IResult::Done((&[][..], 0), 15)
-                  , IResult::Done((&inputs[7].0[1..], 7), 7)
-                  , IResult::Incomplete(Needed::Size(3))
-                  ];
+  // Parse `input` the same way as a nom bit parser, turning the result back
+  // into an `IResult`.
+  fn parse<'a, T, F>(input: (&'a [u8], usize), parser: F)
+                     -> IResult<(&'a [u8], usize), T, ErrorKind>
+   where F: FnOnce(&mut BitReader<'a>) -> Result<T, ErrorKind> {
+    let mut reader = BitReader::with_offset(input.0, input.1);
+    let result     = parser(&mut reader);
 
-    assert_eq!(leading_zeros(inputs[0]), results[0]);
-    assert_eq!(leading_zeros(inputs[1]), results[1]);
-    assert_eq!(leading_zeros(inputs[2]), results[2]);
-    assert_eq!(leading_zeros(inputs[3]), results[3]);
-    assert_eq!(leading_zeros(inputs[4]), results[4]);
-    assert_eq!(leading_zeros(inputs[5]), results[5]);
-    assert_eq!(leading_zeros(inputs[6]), results[6]);
-    assert_eq!(leading_zeros(inputs[7]), results[7]);
-    assert_eq!(leading_zeros(inputs[8]), results[8]);
+    to_iresult(input, reader.remaining(), result)
   }
 
   #[test]
@@ -511,10 +423,10 @@ mod tests {
                         ErrorKind::InvalidSubframeHeader), inputs[3]))
                   ];
 
-    assert_eq!(header(inputs[0]), results[0]);
-    assert_eq!(header(inputs[1]), results[1]);
-    assert_eq!(header(inputs[2]), results[2]);
-    assert_eq!(header(inputs[3]), results[3]);
+    assert_eq!(parse(inputs[0], header), results[0]);
+    assert_eq!(parse(inputs[1], header), results[1]);
+    assert_eq!(parse(inputs[2], header), results[2]);
+    assert_eq!(parse(inputs[3], header), results[3]);
   }
 
   #[test]
@@ -557,8 +469,8 @@ mod tests {
                   , IResult::Done((&[][..], 0), Data::Constant(-8))
                   ];
 
-    assert_eq!(constant(inputs[0], 16), results[0]);
-    assert_eq!(constant(inputs[1], 5), results[1]);
+    assert_eq!(parse(inputs[0], |reader| constant(reader, 16)), results[0]);
+    assert_eq!(parse(inputs[1], |reader| constant(reader, 5)), results[1]);
   }
 
   #[test]
@@ -573,8 +485,14 @@ mod tests {
                                   -4, 10, 0, -16, 15, 0, 4, 9]))
                   ];
 
-    assert_eq!(verbatim(inputs[0], 16, 8), results[0]);
-    assert_eq!(verbatim(inputs[1], 5, 8), results[1]);
+    let mut buffers = Buffers::default();
+
+    assert_eq!(parse(inputs[0], |reader| {
+                 verbatim(reader, 16, 8, &mut buffers)
+               }), results[0]);
+    assert_eq!(parse(inputs[1], |reader| {
+                 verbatim(reader, 5, 8, &mut buffers)
+               }), results[1]);
   }
 
   #[test]
@@ -597,7 +515,7 @@ mod tests {
                       },
                       order: 4,
                       warmup: [-24, 0, 64, -81],
-                      residual: Vec::new(),
+                      residual: vec![642, 0, 5, 148, -141, 178],
                     }))
                   , IResult::Done((&[][..], 0), Data::Fixed(Fixed {
                       entropy_coding_method: EntropyCodingMethod {
@@ -612,19 +530,24 @@ mod tests {
                       },
                       order: 2,
                       warmup: [-1, 5, 0, 0],
-                      residual: Vec::new(),
+                      residual: vec![-36, 66, 142, -4, 2, 0, -32, 16],
                     }))
                   ];
 
-    let mut buffer = [0; 10];
-    let residuals  = [ &[642, 0, 5, 148, -141, 178][..]
-                     , &[-36, 66, 142, -4, 2, 0, -32, 16][..]
-                     ];
+    let mut buffer  = [0; 10];
+    let mut buffers = Buffers::default();
+    let residuals   = [ &[642, 0, 5, 148, -141, 178][..]
+                      , &[-36, 66, 142, -4, 2, 0, -32, 16][..]
+                      ];
 
-    assert_eq!(fixed(inputs[0], 4, 8, 10, &mut buffer), results[0]);
+    assert_eq!(parse(inputs[0], |reader| {
+                 fixed(reader, 4, 8, 10, &mut buffers, &mut buffer)
+               }), results[0]);
     assert_eq!(&buffer[4..10], residuals[0]);
 
-    assert_eq!(fixed(inputs[1], 2, 4, 10, &mut buffer), results[1]);
+    assert_eq!(parse(inputs[1], |reader| {
+                 fixed(reader, 2, 4, 10, &mut buffers, &mut buffer)
+               }), results[1]);
     assert_eq!(&buffer[2..10], residuals[1]);
   }
 
@@ -659,7 +582,7 @@ mod tests {
                                         , 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
                                         , 0, 0, 0, 0, 0, 0, 0, 0, 0
                                         ],
-                      residual: Vec::new(),
+                      residual: vec![22, 0, 5, 24, -17, 54],
                     }))
                   , IResult::Done(slice, Data::LPC(LPC {
                       entropy_coding_method: EntropyCodingMethod {
@@ -683,21 +606,28 @@ mod tests {
                                         , 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
                                         , 0, 0, 0, 0, 0, 0, 0, 0, 0
                                         ],
-                      residual: Vec::new(),
+                      residual: vec![ -2, 3, -1, -4, 2, 27, -28, 20, 11, 9, 12
+                                    , -22, -3, 1, 1, -25, -20, 26
+                                    ],
                     }))
                   ];
 
-    let mut buffer = [0; 26];
-    let residuals  = [ &[22, 0, 5, 24, -17, 54][..],
-                       &[ -2, 3, -1, -4, 2, 27, -28, 20, 11, 9, 12, -22, -3, 1
-                        , 1, -25, -20, 26
-                        ][..]
-                     ];
-
-    assert_eq!(lpc(inputs[0], 4, 8, 10, &mut buffer), results[0]);
+    let mut buffer  = [0; 26];
+    let mut buffers = Buffers::default();
+    let residuals   = [ &[22, 0, 5, 24, -17, 54][..],
+                        &[ -2, 3, -1, -4, 2, 27, -28, 20, 11, 9, 12, -22, -3, 1
+                         , 1, -25, -20, 26
+                         ][..]
+                      ];
+
+    assert_eq!(parse(inputs[0], |reader| {
+                 lpc(reader, 4, 8, 10, &mut buffers, &mut buffer)
+               }), results[0]);
     assert_eq!(&buffer[4..10], residuals[0]);
 
-    assert_eq!(lpc(inputs[1], 8, 4, 26, &mut buffer), results[1]);
+    assert_eq!(parse(inputs[1], |reader| {
+                 lpc(reader, 8, 4, 26, &mut buffers, &mut buffer)
+               }), results[1]);
     assert_eq!(&buffer[8..26], residuals[1]);
   }
 }
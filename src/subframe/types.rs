@@ -1,3 +1,9 @@
+use subframe::encoder;
+use utility::BitWriter;
+
+use std::io;
+use std::mem;
+
 /// Maximum order of the fixed predictors permitted by the format.
 pub const MAX_FIXED_ORDER: usize = 4;
 
@@ -12,13 +18,44 @@ pub struct Subframe {
   pub wasted_bits: u32,
 }
 
+impl Subframe {
+  /// Writes the subframe, padded with zero bits to the next byte boundary.
+  ///
+  /// `bits_per_sample` is the sample size of the channel that the subframe
+  /// belongs to, this is the same value the parser gets from
+  /// `adjust_bits_per_sample`.
+  pub fn to_bytes<Write: io::Write>(&self, bits_per_sample: usize,
+                                    buffer: &mut Write)
+                                    -> io::Result<()> {
+    let mut writer = BitWriter::new();
+
+    encoder::write(self, bits_per_sample, &mut writer);
+    writer.align();
+
+    buffer.write_all(writer.as_slice())
+  }
+}
+
+impl Default for Subframe {
+  /// Constructs a constant subframe of zero, which doesn't allocate.
+  fn default() -> Self {
+    Subframe {
+      data: Data::Constant(0),
+      wasted_bits: 0,
+    }
+  }
+}
+
 /// General enum that holds all the different subframe data types.
+///
+/// Samples are held as `i64` since the side channel of a 32 bit stream
+/// needs 33 bits, while the residual always fits within an `i32`.
 #[derive(Debug, PartialEq, Eq)]
 pub enum Data {
   /// A single value that represents a constant subframe.
-  Constant(i32),
+  Constant(i64),
   /// An uncompressed subframe.
-  Verbatim(Vec<i32>),
+  Verbatim(Vec<i64>),
   /// Fixed linear prediction subframe.
   Fixed(Fixed),
   /// FIR linear prediction subframe.
@@ -33,7 +70,7 @@ pub struct Fixed {
   /// Polynomial order.
   pub order: u8,
   /// Samples used to warm up, or prime, the predictor.
-  pub warmup: [i32; MAX_FIXED_ORDER],
+  pub warmup: [i64; MAX_FIXED_ORDER],
   /// Remaining samples after the warm up samples.
   pub residual: Vec<i32>,
 }
@@ -52,7 +89,7 @@ pub struct LPC {
   /// FIR filter coefficients.
   pub qlp_coefficients: [i32; MAX_LPC_ORDER],
   /// Samples used to warm up, or prime, the predictor.
-  pub warmup: [i32; MAX_LPC_ORDER],
+  pub warmup: [i64; MAX_LPC_ORDER],
   /// Remaining samples after the warm up samples.
   pub residual: Vec<i32>,
 }
@@ -95,14 +132,9 @@ pub struct PartitionedRiceContents {
 
 impl PartitionedRiceContents {
   pub fn new(capacity: usize) -> PartitionedRiceContents {
-    let full_capacity = capacity * 2;
-    let mut data      = Vec::with_capacity(full_capacity);
-
-    unsafe { data.set_len(full_capacity) }
-
     PartitionedRiceContents {
       capacity: capacity,
-      data: data,
+      data: vec![0; capacity * 2],
     }
   }
 
@@ -118,3 +150,43 @@ impl PartitionedRiceContents {
     &mut self.data[self.capacity..]
   }
 }
+
+// Buffers for the residual, verbatim samples and Rice parameters of a
+// single channel. They get moved into the subframe being parsed and taken
+// back out of it before parsing the next one, so the same memory gets used
+// for every frame.
+#[derive(Default)]
+pub struct Buffers {
+  pub residual: Vec<i32>,
+  pub verbatim: Vec<i64>,
+  pub rice: Vec<u32>,
+}
+
+impl Buffers {
+  // Reserve enough room for a residual, or verbatim samples, of
+  // `block_size` and the most Rice partitions it can be split into.
+  pub fn with_capacity(block_size: usize) -> Buffers {
+    Buffers {
+      residual: Vec::with_capacity(block_size),
+      verbatim: Vec::with_capacity(block_size),
+      rice: Vec::with_capacity(block_size * 2),
+    }
+  }
+
+  // Take back the buffers `data` holds, leaving a constant subframe in its
+  // place.
+  pub fn recycle(&mut self, data: &mut Data) {
+    match mem::replace(data, Data::Constant(0)) {
+      Data::Constant(_)       => (),
+      Data::Verbatim(samples) => self.verbatim = samples,
+      Data::Fixed(fixed)      => {
+        self.residual = fixed.residual;
+        self.rice     = fixed.entropy_coding_method.data.contents.data;
+      }
+      Data::LPC(lpc)          => {
+        self.residual = lpc.residual;
+        self.rice     = lpc.entropy_coding_method.data.contents.data;
+      }
+    }
+  }
+}
@@ -0,0 +1,209 @@
+// Per-round shift amounts, as defined in RFC 1321.
+const SHIFTS: [u32; 64] = [
+  7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22,
+  5,  9, 14, 20, 5,  9, 14, 20, 5,  9, 14, 20, 5,  9, 14, 20,
+  4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23, 4, 11, 16, 23,
+  6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10, 15, 21,
+];
+
+// Pre-generated round constants.
+//
+// Each entry is the integer part of `abs(sin(i + 1)) * 2^32`, where `i` is
+// the index into the table.
+const CONSTANTS: [u32; 64] = [
+  0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee,
+  0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+  0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be,
+  0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+  0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa,
+  0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+  0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed,
+  0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+  0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c,
+  0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+  0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05,
+  0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+  0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039,
+  0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+  0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1,
+  0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+// Incremental MD5 digest, used for the signature of the unencoded audio
+// data inside `StreamInfo`.
+#[derive(Clone)]
+pub struct MD5 {
+  state: [u32; 4],
+  buffer: [u8; 64],
+  filled: usize,
+  length: u64,
+}
+
+impl MD5 {
+  pub fn new() -> Self {
+    MD5 {
+      state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+      buffer: [0; 64],
+      filled: 0,
+      length: 0,
+    }
+  }
+
+  // Feed more bytes into the digest.
+  pub fn update(&mut self, bytes: &[u8]) {
+    let mut input = bytes;
+
+    self.length = self.length.wrapping_add(bytes.len() as u64);
+
+    if self.filled > 0 {
+      let needed = 64 - self.filled;
+
+      if input.len() < needed {
+        let end = self.filled + input.len();
+
+        self.buffer[self.filled..end].copy_from_slice(input);
+        self.filled = end;
+
+        return;
+      }
+
+      let block = {
+        let mut block = self.buffer;
+
+        block[self.filled..].copy_from_slice(&input[0..needed]);
+
+        block
+      };
+
+      self.process(&block);
+
+      self.filled = 0;
+      input       = &input[needed..];
+    }
+
+    while input.len() >= 64 {
+      let mut block = [0; 64];
+
+      block.copy_from_slice(&input[0..64]);
+      self.process(&block);
+
+      input = &input[64..];
+    }
+
+    self.buffer[0..input.len()].copy_from_slice(input);
+    self.filled = input.len();
+  }
+
+  // Pad the remaining bytes and return the final 128 bit signature.
+  pub fn finish(mut self) -> [u8; 16] {
+    let bit_length = self.length.wrapping_mul(8);
+    let mut length = [0; 8];
+    let padding    = if self.filled < 56 {
+      56 - self.filled
+    } else {
+      120 - self.filled
+    };
+
+    for i in 0..8 {
+      length[i] = (bit_length >> (i * 8)) as u8;
+    }
+
+    let mut tail = [0; 64];
+
+    tail[0] = 0x80;
+
+    self.update(&tail[0..padding]);
+    self.update(&length);
+
+    let mut result = [0; 16];
+
+    for (i, word) in self.state.iter().enumerate() {
+      result[i * 4]     = *word as u8;
+      result[i * 4 + 1] = (*word >> 8) as u8;
+      result[i * 4 + 2] = (*word >> 16) as u8;
+      result[i * 4 + 3] = (*word >> 24) as u8;
+    }
+
+    result
+  }
+
+  fn process(&mut self, block: &[u8; 64]) {
+    let mut words = [0u32; 16];
+
+    for (i, word) in words.iter_mut().enumerate() {
+      *word = (block[i * 4] as u32)              |
+              ((block[i * 4 + 1] as u32) << 8)   |
+              ((block[i * 4 + 2] as u32) << 16)  |
+              ((block[i * 4 + 3] as u32) << 24);
+    }
+
+    let mut a = self.state[0];
+    let mut b = self.state[1];
+    let mut c = self.state[2];
+    let mut d = self.state[3];
+
+    for i in 0..64 {
+      let (f, g) = match i / 16 {
+        0 => ((b & c) | (!b & d), i),
+        1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+        2 => (b ^ c ^ d, (3 * i + 5) % 16),
+        _ => (c ^ (b | !d), (7 * i) % 16),
+      };
+
+      let sum = a.wrapping_add(f)
+                 .wrapping_add(CONSTANTS[i])
+                 .wrapping_add(words[g]);
+
+      a = d;
+      d = c;
+      c = b;
+      b = b.wrapping_add(sum.rotate_left(SHIFTS[i]));
+    }
+
+    self.state[0] = self.state[0].wrapping_add(a);
+    self.state[1] = self.state[1].wrapping_add(b);
+    self.state[2] = self.state[2].wrapping_add(c);
+    self.state[3] = self.state[3].wrapping_add(d);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn digest(bytes: &[u8]) -> [u8; 16] {
+    let mut md5 = MD5::new();
+
+    md5.update(bytes);
+    md5.finish()
+  }
+
+  #[test]
+  fn test_md5() {
+    assert_eq!(digest(b""),
+               [ 0xd4, 0x1d, 0x8c, 0xd9, 0x8f, 0x00, 0xb2, 0x04, 0xe9, 0x80
+               , 0x09, 0x98, 0xec, 0xf8, 0x42, 0x7e
+               ]);
+    assert_eq!(digest(b"abc"),
+               [ 0x90, 0x01, 0x50, 0x98, 0x3c, 0xd2, 0x4f, 0xb0, 0xd6, 0x96
+               , 0x3f, 0x7d, 0x28, 0xe1, 0x7f, 0x72
+               ]);
+    assert_eq!(digest(b"12345678901234567890123456789012345678901234567890\
+                        123456789012345678901234567890"),
+               [ 0x57, 0xed, 0xf4, 0xa2, 0x2b, 0xe3, 0xc9, 0x55, 0xac, 0x49
+               , 0xda, 0x2e, 0x21, 0x07, 0xb6, 0x7a
+               ]);
+  }
+
+  #[test]
+  fn test_md5_update() {
+    let bytes   = [0x5a; 200];
+    let mut md5 = MD5::new();
+
+    md5.update(&bytes[0..3]);
+    md5.update(&bytes[3..70]);
+    md5.update(&bytes[70..]);
+
+    assert_eq!(md5.finish(), digest(&bytes));
+  }
+}
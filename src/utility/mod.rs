@@ -1,10 +1,15 @@
 mod crc;
 #[macro_use]
 mod macros;
+mod md5;
 mod types;
 
-pub use self::crc::{crc8, crc16};
-pub use self::types::{ErrorKind, ByteStream, ReadStream};
+pub use self::crc::{crc8, crc16, crc32, crc32_update};
+pub use self::md5::MD5;
+pub use self::types::{
+  ErrorKind, ByteStream, ReadStream, BitReader, BitWriter,
+};
+pub(crate) use self::types::{Buffer, from_iresult, to_iresult};
 
 use nom::{self, IResult};
 use metadata::{Metadata, metadata_parser};
@@ -21,6 +26,23 @@ pub trait StreamProducer {
    where F: FnOnce(&[u8]) -> IResult<&[u8], T, ErrorKind>;
 }
 
+/// A `StreamProducer` that can move to any position within its source of
+/// bytes.
+///
+/// Positions are in bytes and relative to where the producer started
+/// reading from.
+pub trait SeekableProducer: StreamProducer {
+  /// Move to `offset`, dropping any bytes that are buffered but haven't
+  /// been consumed.
+  fn seek(&mut self, offset: u64) -> Result<(), ErrorKind>;
+
+  /// Return the position of the next byte that gets parsed.
+  fn position(&self) -> u64;
+
+  /// Return the total length, in bytes, of the source.
+  fn stream_len(&mut self) -> Result<u64, ErrorKind>;
+}
+
 /// An abstraction trait for keeping different sized integers.
 pub trait Sample: PartialEq + Eq + Sized + Clone + Copy +
                   Add<Output = Self> + AddAssign +
@@ -57,6 +79,30 @@ pub trait Sample: PartialEq + Eq + Sized + Clone + Copy +
 
   /// Convert an i32 into a `Sample`.
   fn from_i32_lossy(sample: i32) -> Self;
+
+  /// Convert a `Sample` into an i64.
+  fn to_i64(sample: Self) -> i64;
+
+  /// Convert an i64 into a `Sample`.
+  ///
+  /// Much like `Sample::from_i32_lossy`, the number gets truncated when it
+  /// is larger or smaller than the current `Sample`.
+  fn from_i64_lossy(sample: i64) -> Self;
+
+  /// Returns the samples as `i32` when that is what `Sample` is, so the
+  /// signal restoration kernels for it can be used.
+  #[doc(hidden)]
+  #[inline]
+  fn as_i32_slice(_samples: &mut [Self]) -> Option<&mut [i32]> {
+    None
+  }
+
+  /// Returns the samples as `i64` when that is what `Sample` is.
+  #[doc(hidden)]
+  #[inline]
+  fn as_i64_slice(_samples: &mut [Self]) -> Option<&mut [i64]> {
+    None
+  }
 }
 
 /// A trait for defining the size of a sample.
@@ -215,7 +261,9 @@ pub fn to_u32(bytes: &[u8]) -> u32 {
 // NOTE: This assumes that the larger bit size will be 32 bit since that is
 // the largest sample size supported in FLAC.
 pub fn extend_sign(value: u32, bit_count: usize) -> i32 {
-  if bit_count >= 32 || value < (1 << (bit_count - 1)) {
+  if bit_count == 0 {
+    0
+  } else if bit_count >= 32 || value < (1 << (bit_count - 1)) {
     value as i32
   } else {
     (value as i32).wrapping_sub(1 << bit_count)
@@ -230,29 +278,26 @@ pub fn power_of_two(exponent: u32) -> u32 {
   1 << exponent
 }
 
-#[derive(PartialEq, Eq)]
-enum ParserState {
+// Where the parsing of the stream header is at, only moving forward once a
+// `parser` call completes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ParserState {
   Header,
-  StreamInfo,
   Metadata
 }
 
-fn parser<'a>(input: &'a [u8], state: &mut ParserState)
-              -> IResult<&'a [u8], Metadata, ErrorKind> {
-  let mut slice = input;
-  let error     = nom::Err::Code(nom::ErrorKind::Custom(ErrorKind::Unknown));
-
-  if *state == ParserState::Header {
-    let (i, _) = try_parser! {
-      to_custom_error!(slice, tag!("fLaC"), HeaderParser)
-    };
-
-    slice  = i;
-    *state = ParserState::StreamInfo;
-  }
+// Parses the next metadata block of the stream header, along with the
+// "fLaC" marker before the first block, which has to be `StreamInfo`.
+pub(crate) fn parser<'a>(input: &'a [u8], state: &mut ParserState)
+                         -> IResult<&'a [u8], Metadata, ErrorKind> {
+  let error = nom::Err::Code(nom::ErrorKind::Custom(ErrorKind::Unknown));
 
   match *state {
-    ParserState::StreamInfo => {
+    ParserState::Header   => {
+      let (slice, _) = try_parser! {
+        to_custom_error!(input, tag!("fLaC"), HeaderParser)
+      };
+
       let (i, block) = try_parse!(slice, metadata_parser);
 
       if block.is_stream_info() {
@@ -263,8 +308,7 @@ fn parser<'a>(input: &'a [u8], state: &mut ParserState)
         IResult::Error(error)
       }
     }
-    ParserState::Metadata   => metadata_parser(slice),
-    _                       => IResult::Error(error),
+    ParserState::Metadata => metadata_parser(input),
   }
 }
 
@@ -319,6 +363,7 @@ mod tests {
 
   #[test]
   fn test_extend_sign() {
+    assert_eq!(extend_sign(0, 0), 0);
     assert_eq!(extend_sign(32, 6), -32);
     assert_eq!(extend_sign(31, 6), 31);
     assert_eq!(extend_sign(128, 8), -128);
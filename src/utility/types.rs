@@ -1,10 +1,10 @@
 use nom::{self, IResult, Needed};
 
-use std::io::{self, Read};
+use std::io::{self, Read, Seek, SeekFrom};
 use std::ptr;
 use std::cmp;
 
-use super::{Sample, StreamProducer};
+use super::{Sample, StreamProducer, SeekableProducer};
 
 /// Represent the different kinds of errors.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -70,6 +70,11 @@ pub enum ErrorKind {
   FixedParser,
   /// Failed parsing a LPC subframe data.
   LPCParser,
+  /// Failed parsing the header of an Ogg page.
+  OggPageParser,
+  /// Failed parsing the first packet of an Ogg FLAC stream, which holds
+  /// the mapping version and `StreamInfo`.
+  OggMappingParser,
   // Invalid Error
   /// A block type, base on the number, that is outside the range (0-126).
   InvalidBlockType,
@@ -87,8 +92,38 @@ pub enum ErrorKind {
   /// The stored CRC-16 doesn't match the one generated from the bytes
   /// within the entire frame.
   InvalidCRC16,
+  /// The stored CRC-32 doesn't match the one generated from the bytes
+  /// within the entire Ogg page.
+  InvalidCRC32,
   /// A subframe header that could cause sync-fooling.
   InvalidSubframeHeader,
+  /// A frame header with more channels than `StreamInfo`, or with more
+  /// samples than the buffer being decoded into can hold.
+  InvalidFrameSize,
+  /// The `StreamInfo` given to the encoder describes a stream that can't be
+  /// encoded.
+  InvalidStreamInfo,
+  /// A seek to a sample that is past the end of the stream.
+  InvalidSeek,
+  /// The sample type used for decoding is too small for the bits per
+  /// sample of the stream, or a sample given to the encoder doesn't fit
+  /// within them.
+  InvalidSampleSize,
+  /// Samples given to a `StreamWriter` after it was finished.
+  InvalidWrite,
+  /// A metadata block that can't be written where it is, like a second
+  /// `StreamInfo` or a block too large for the 24 bit length.
+  InvalidMetadata,
+  /// A `VorbisComment` name that is empty or has a character outside of
+  /// 0x20 through 0x7D, which includes `=`.
+  InvalidCommentName,
+  /// A stream that has to stay within the streamable subset, but the
+  /// settings of the encoder are above its limits or the frame header can't
+  /// hold its sample rate or bits per sample.
+  InvalidSubset,
+  /// The stream no longer knows where it is, after frames read ahead by
+  /// `Stream::parallel_blocks` were dropped, until it seeks to a sample.
+  InvalidPosition,
   // Not Found
   /// Some metadata block was not found with a specific filter.
   NotFound,
@@ -162,6 +197,28 @@ impl<'a> StreamProducer for ByteStream<'a> {
   }
 }
 
+impl<'a> SeekableProducer for ByteStream<'a> {
+  fn seek(&mut self, offset: u64) -> Result<(), ErrorKind> {
+    if offset > self.bytes.len() as u64 {
+      return Err(ErrorKind::IO(io::ErrorKind::UnexpectedEof));
+    }
+
+    self.offset = offset as usize;
+
+    Ok(())
+  }
+
+  #[inline]
+  fn position(&self) -> u64 {
+    self.offset as u64
+  }
+
+  #[inline]
+  fn stream_len(&mut self) -> Result<u64, ErrorKind> {
+    Ok(self.bytes.len() as u64)
+  }
+}
+
 // Growable buffer of bytes.
 //
 // Mainly used to the `ReadStream` structure but can be used seperately for
@@ -260,6 +317,361 @@ impl Buffer {
   pub fn consume(&mut self, consumed: usize) {
     self.offset += consumed;
   }
+
+  // Drop every byte that has been read.
+  pub fn clear(&mut self) {
+    self.filled = 0;
+    self.offset = 0;
+  }
+}
+
+// Writer for values that aren't aligned to a byte boundary.
+//
+// Bits are packed most significant bit first, which is the order that the
+// FLAC format uses, and complete bytes are appended to the underlining
+// buffer as soon as they are available.
+pub struct BitWriter {
+  data: Vec<u8>,
+  cache: u64,
+  bits: usize,
+}
+
+impl BitWriter {
+  // Default constructor for `BitWriter`.
+  pub fn new() -> Self {
+    Self::with_capacity(1024)
+  }
+
+  // Explicitly set the capacity, in bytes, of the underlining buffer.
+  pub fn with_capacity(capacity: usize) -> Self {
+    BitWriter {
+      data: Vec::with_capacity(capacity),
+      cache: 0,
+      bits: 0,
+    }
+  }
+
+  // Return true when the bits written so far end on a byte boundary.
+  #[inline]
+  pub fn is_aligned(&self) -> bool {
+    self.bits == 0
+  }
+
+  // Return a reference to the slice of complete bytes.
+  #[inline]
+  pub fn as_slice(&self) -> &[u8] {
+    &self.data
+  }
+
+  // Return the number of bits written, including the ones that don't make
+  // up a complete byte yet.
+  #[inline]
+  pub fn bits_len(&self) -> usize {
+    self.data.len() * 8 + self.bits
+  }
+
+  // Remove everything that has been written.
+  pub fn clear(&mut self) {
+    self.data.clear();
+
+    self.cache = 0;
+    self.bits  = 0;
+  }
+
+  // Write the lower `count` bits of `value`.
+  #[inline]
+  pub fn write_bits(&mut self, value: u32, count: usize) {
+    debug_assert!(count <= 32);
+
+    if count == 0 {
+      return;
+    }
+
+    let mask = u64::max_value() >> (64 - count);
+
+    self.cache  = (self.cache << count) | (value as u64 & mask);
+    self.bits  += count;
+
+    while self.bits >= 8 {
+      self.bits -= 8;
+
+      self.data.push((self.cache >> self.bits) as u8);
+    }
+  }
+
+  // Write a signed value as a two's complement number of `count` bits.
+  #[inline]
+  pub fn write_signed_bits(&mut self, value: i32, count: usize) {
+    self.write_bits(value as u32, count)
+  }
+
+  // Write a signed value as a two's complement number of `count` bits,
+  // for the samples of a 32 bit side channel that need up to 33 bits.
+  pub fn write_wide_signed_bits(&mut self, value: i64, count: usize) {
+    debug_assert!(count <= 64);
+
+    if count > 32 {
+      self.write_bits((value >> 32) as u32, count - 32);
+      self.write_bits(value as u32, 32);
+    } else {
+      self.write_bits(value as u32, count);
+    }
+  }
+
+  // Write a number in unary notation, `zeros` amount of zero bits followed
+  // by a single one bit.
+  pub fn write_unary(&mut self, zeros: u32) {
+    let mut remaining = zeros as usize;
+
+    while remaining >= 32 {
+      self.write_bits(0, 32);
+
+      remaining -= 32;
+    }
+
+    self.write_bits(1, remaining + 1);
+  }
+
+  // Write a signed value as a Rice code with the given parameter.
+  //
+  // The value gets folded into an unsigned number first, with the sign
+  // being the least significant bit.
+  #[inline]
+  pub fn write_rice(&mut self, value: i32, parameter: u32) {
+    let folded = ((value << 1) ^ (value >> 31)) as u32;
+
+    self.write_unary(folded >> parameter);
+    self.write_bits(folded, parameter as usize);
+  }
+
+  // Write zero bits until the next byte boundary.
+  #[inline]
+  pub fn align(&mut self) {
+    if !self.is_aligned() {
+      let padding = 8 - self.bits;
+
+      self.write_bits(0, padding);
+    }
+  }
+}
+
+// Reader for values that aren't aligned to a byte boundary, the
+// counterpart of `BitWriter`.
+//
+// Up to 64 bits of the input are cached at a time, starting from the most
+// significant bit, so most reads only shift the cache. Every bit after the
+// ones that have been cached is kept at zero, which lets unary numbers get
+// read with a single `leading_zeros`.
+pub struct BitReader<'a> {
+  data: &'a [u8],
+  // Index of the next byte that gets moved into the cache.
+  position: usize,
+  cache: u64,
+  bits: usize,
+}
+
+impl<'a> BitReader<'a> {
+  pub fn new(data: &'a [u8]) -> Self {
+    BitReader {
+      data: data,
+      position: 0,
+      cache: 0,
+      bits: 0,
+    }
+  }
+
+  // Start reading `offset` bits into the first byte of `data`, the same
+  // input that nom's bit parsers take.
+  pub fn with_offset(data: &'a [u8], offset: usize) -> Self {
+    let mut reader = BitReader::new(data);
+
+    reader.refill();
+    reader.skip(cmp::min(offset, reader.bits));
+
+    reader
+  }
+
+  // Return the bytes that haven't been completely read, along with the
+  // number of bits already read from the first one.
+  pub fn remaining(&self) -> (&'a [u8], usize) {
+    let consumed = self.position * 8 - self.bits;
+
+    (&self.data[(consumed / 8)..], consumed % 8)
+  }
+
+  // Skip the rest of a partially read byte.
+  #[inline]
+  pub fn align(&mut self) {
+    let padding = self.bits % 8;
+
+    self.skip(padding);
+  }
+
+  #[inline]
+  fn skip(&mut self, count: usize) {
+    debug_assert!(count <= self.bits && count < 64);
+
+    self.cache <<= count;
+    self.bits   -= count;
+  }
+
+  // Move as many whole bytes into the cache as there is room for, eight at
+  // a time when there are enough bytes left.
+  #[inline]
+  fn refill(&mut self) {
+    let room = (64 - self.bits) / 8;
+
+    if room == 0 {
+      return;
+    }
+
+    if self.position + 8 <= self.data.len() {
+      let mut bytes = [0; 8];
+      let fill      = room * 8;
+
+      bytes.copy_from_slice(&self.data[self.position..(self.position + 8)]);
+
+      let word = u64::from_be_bytes(bytes) >> (64 - fill);
+
+      self.cache    |= word << (64 - self.bits - fill);
+      self.position += room;
+      self.bits     += fill;
+    } else {
+      while self.bits <= 56 && self.position < self.data.len() {
+        let byte = self.data[self.position] as u64;
+
+        self.cache    |= byte << (56 - self.bits);
+        self.position += 1;
+        self.bits     += 8;
+      }
+    }
+  }
+
+  // Error for when the input runs out with `count` bits left to read,
+  // holding the least amount of bytes needed to read them.
+  fn incomplete(&self, count: usize) -> ErrorKind {
+    let missing = count - self.bits;
+
+    ErrorKind::Incomplete(self.data.len() + (missing + 7) / 8)
+  }
+
+  // Read `count` bits as an unsigned number.
+  #[inline]
+  pub fn read_bits(&mut self, count: usize) -> Result<u32, ErrorKind> {
+    debug_assert!(count <= 32);
+
+    if self.bits < count {
+      self.refill();
+
+      if self.bits < count {
+        return Err(self.incomplete(count));
+      }
+    }
+
+    if count == 0 {
+      return Ok(0);
+    }
+
+    let value = (self.cache >> (64 - count)) as u32;
+
+    self.skip(count);
+
+    Ok(value)
+  }
+
+  // Read `count` bits as a two's complement number.
+  #[inline]
+  pub fn read_signed_bits(&mut self, count: usize) -> Result<i32, ErrorKind> {
+    self.read_bits(count).map(|value| super::extend_sign(value, count))
+  }
+
+  // Read `count` bits as a two's complement number that can be wider than
+  // 32 bits, like the samples of a 32 bit side channel.
+  pub fn read_wide_signed_bits(&mut self, count: usize)
+                               -> Result<i64, ErrorKind> {
+    debug_assert!(count <= 64);
+
+    if count <= 32 {
+      return self.read_signed_bits(count).map(|value| value as i64);
+    }
+
+    let high  = try!(self.read_bits(count - 32)) as u64;
+    let low   = try!(self.read_bits(32)) as u64;
+    let shift = 64 - count;
+
+    Ok((((high << 32) | low) << shift) as i64 >> shift)
+  }
+
+  // Read a number in unary notation, the amount of zero bits before the
+  // next one bit.
+  #[inline]
+  pub fn read_unary(&mut self) -> Result<u32, ErrorKind> {
+    let mut zeros = 0;
+
+    loop {
+      if self.cache != 0 {
+        let count = self.cache.leading_zeros() as usize;
+
+        self.skip(count);
+        self.skip(1);
+
+        return Ok(zeros + count as u32);
+      }
+
+      zeros     += self.bits as u32;
+      self.bits  = 0;
+
+      self.refill();
+
+      if self.bits == 0 {
+        return Err(self.incomplete(1));
+      }
+    }
+  }
+
+  // Read a Rice code with `parameter` into each value of `output`.
+  //
+  // Codes that are entirely within the cache get read with a single shift
+  // of it, the rest go through `read_unary` and `read_bits`.
+  pub fn read_rice(&mut self, parameter: u32, output: &mut [i32])
+                   -> Result<(), ErrorKind> {
+    let parameter = parameter as usize;
+
+    debug_assert!(parameter <= 32);
+
+    for value in output.iter_mut() {
+      if self.bits < 32 {
+        self.refill();
+      }
+
+      let zeros = self.cache.leading_zeros() as usize;
+      let (quotient, remainder);
+
+      if zeros + 1 + parameter <= self.bits {
+        let shifted = self.cache << zeros << 1;
+
+        quotient  = zeros as u32;
+        remainder = if parameter == 0 {
+          0
+        } else {
+          (shifted >> (64 - parameter)) as u32
+        };
+
+        self.cache  = shifted << parameter;
+        self.bits  -= zeros + 1 + parameter;
+      } else {
+        quotient  = try!(self.read_unary());
+        remainder = try!(self.read_bits(parameter));
+      }
+
+      // Wide enough that a corrupt quotient can't overflow.
+      let folded = ((quotient as u64) << parameter) | remainder as u64;
+
+      *value = (((folded >> 1) as i64) ^ -((folded & 1) as i64)) as i32;
+    }
+
+    Ok(())
+  }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -294,6 +706,7 @@ pub struct ReadStream<R: Read> {
   reader: R,
   buffer: Buffer,
   needed: usize,
+  consumed: u64,
   state: ParserState,
 }
 
@@ -304,6 +717,7 @@ impl<R> ReadStream<R> where R: Read {
       reader: reader,
       buffer: Buffer::new(),
       needed: 0,
+      consumed: 0,
       state: ParserState::Incomplete,
     }
   }
@@ -322,8 +736,27 @@ impl<R> ReadStream<R> where R: Read {
   }
 }
 
-fn from_iresult<T>(buffer: &Buffer, result: IResult<&[u8], T, ErrorKind>)
-                   -> Result<(usize, T), ErrorKind> {
+// Turn the result of parsing with a `BitReader` into an `IResult`, where
+// `rest` is what's left of `input` once it succeeds.
+//
+// Running out of input only tells how many bytes the reader needs at the
+// very least, so it's left unknown for the producer to read ahead by more
+// than a few bytes at a time.
+pub(crate) fn to_iresult<I, T>(input: I, rest: I,
+                               result: Result<T, ErrorKind>)
+                               -> IResult<I, T, ErrorKind> {
+  match result {
+    Ok(o)                         => IResult::Done(rest, o),
+    Err(ErrorKind::Incomplete(_)) => IResult::Incomplete(Needed::Unknown),
+    Err(kind)                     => {
+      IResult::Error(nom::Err::Position(nom::ErrorKind::Custom(kind), input))
+    }
+  }
+}
+
+pub(crate) fn from_iresult<T>(buffer: &Buffer,
+                              result: IResult<&[u8], T, ErrorKind>)
+                              -> Result<(usize, T), ErrorKind> {
   match result {
     IResult::Done(i, o)    => Ok((buffer.len() - i.len(), o)),
     IResult::Incomplete(n) => {
@@ -377,11 +810,21 @@ impl<R> StreamProducer for ReadStream<R> where R: Read {
       Ok((consumed, o)) => {
         buffer.consume(consumed);
 
+        self.consumed += consumed as u64;
+
         Ok(o)
       }
       Err(kind)         => {
         if let ErrorKind::Incomplete(needed) = kind {
-          self.needed = needed;
+          // There are no more bytes to read, so asking to continue would
+          // end up parsing the same bytes forever.
+          if self.state == ParserState::EndOfInput {
+            return Err(kind);
+          }
+
+          // A parser can ask for fewer bytes than it was already given,
+          // so at least one more byte is needed to make any progress.
+          self.needed = cmp::max(needed, buffer.len() + 1);
 
           Err(ErrorKind::Continue)
         } else {
@@ -392,6 +835,64 @@ impl<R> StreamProducer for ReadStream<R> where R: Read {
   }
 }
 
+impl<R> SeekableProducer for ReadStream<R> where R: Read + Seek {
+  fn seek(&mut self, offset: u64) -> Result<(), ErrorKind> {
+    // The reader is ahead of the consumed bytes by everything that is
+    // still left inside of the buffer.
+    let current  = self.consumed + self.buffer.len() as u64;
+    let distance = offset as i64 - current as i64;
+
+    try!(self.reader.seek(SeekFrom::Current(distance))
+                    .map_err(|e| ErrorKind::IO(e.kind())));
+
+    self.buffer.clear();
+
+    self.consumed = offset;
+    self.needed   = 0;
+    self.state    = ParserState::Incomplete;
+
+    Ok(())
+  }
+
+  #[inline]
+  fn position(&self) -> u64 {
+    self.consumed
+  }
+
+  fn stream_len(&mut self) -> Result<u64, ErrorKind> {
+    let to_error = |e: io::Error| ErrorKind::IO(e.kind());
+
+    let current = try!(self.reader.seek(SeekFrom::Current(0))
+                                  .map_err(to_error));
+    let end     = try!(self.reader.seek(SeekFrom::End(0)).map_err(to_error));
+
+    try!(self.reader.seek(SeekFrom::Start(current)).map_err(to_error));
+
+    // Where the reader started, since positions are relative to it.
+    let start = current - (self.consumed + self.buffer.len() as u64);
+
+    Ok(end - start)
+  }
+}
+
+// Give access to the samples as the concrete type, for the types that have
+// signal restoration kernels.
+macro_rules! sample_slice (
+  (i32) => (
+    #[inline]
+    fn as_i32_slice(samples: &mut [Self]) -> Option<&mut [i32]> {
+      Some(samples)
+    }
+  );
+  (i64) => (
+    #[inline]
+    fn as_i64_slice(samples: &mut [Self]) -> Option<&mut [i64]> {
+      Some(samples)
+    }
+  );
+  ($extended: ident) => ();
+);
+
 macro_rules! sample (
   ($normal: ident, $extended: ident, $bits_per_sample: expr) => (
     impl Sample for $extended {
@@ -446,6 +947,18 @@ macro_rules! sample (
       fn from_i32_lossy(sample: i32) -> Self {
         sample as Self
       }
+
+      #[inline]
+      fn to_i64(sample: Self) -> i64 {
+        sample as i64
+      }
+
+      #[inline]
+      fn from_i64_lossy(sample: i64) -> Self {
+        sample as Self
+      }
+
+      sample_slice!($extended);
     }
   )
 );
@@ -487,6 +1000,165 @@ mod tests {
     assert_eq!(buffer.capacity(), 1024);
   }
 
+  #[test]
+  fn test_bit_writer() {
+    let mut writer = BitWriter::new();
+
+    writer.write_bits(0b101, 3);
+    writer.write_signed_bits(-2, 4);
+    writer.write_bits(0x1ff, 9);
+
+    assert!(writer.is_aligned());
+    assert_eq!(writer.bits_len(), 16);
+    assert_eq!(writer.as_slice(), &[0b10111101, 0xff]);
+
+    writer.write_unary(3);
+    assert_eq!(writer.bits_len(), 20);
+    writer.align();
+
+    assert_eq!(writer.as_slice(), &[0b10111101, 0xff, 0b00010000]);
+
+    writer.clear();
+    writer.write_unary(40);
+    writer.align();
+    assert_eq!(writer.as_slice(), &[0, 0, 0, 0, 0, 0b10000000]);
+  }
+
+  #[test]
+  fn test_bit_writer_rice() {
+    let mut writer = BitWriter::new();
+
+    writer.write_rice(0, 2);
+    writer.write_rice(-1, 2);
+    writer.write_rice(3, 1);
+    writer.align();
+
+    assert_eq!(writer.as_slice(), &[0b10010100, 0b01000000]);
+  }
+
+  #[test]
+  fn test_wide_signed_bits() {
+    let values     = [-(1 << 32), (1 << 32) - 1, -3, 0x7fffffff];
+    let mut writer = BitWriter::new();
+
+    writer.write_wide_signed_bits(values[0], 33);
+    writer.write_wide_signed_bits(values[1], 33);
+    writer.write_wide_signed_bits(values[2], 5);
+    writer.write_wide_signed_bits(values[3], 32);
+    writer.align();
+
+    assert_eq!(&writer.as_slice()[0..5], &[0x80, 0, 0, 0, 0x3f]);
+
+    let mut reader = BitReader::new(writer.as_slice());
+
+    assert_eq!(reader.read_wide_signed_bits(33), Ok(values[0]));
+    assert_eq!(reader.read_wide_signed_bits(33), Ok(values[1]));
+    assert_eq!(reader.read_wide_signed_bits(5), Ok(values[2]));
+    assert_eq!(reader.read_wide_signed_bits(32), Ok(values[3]));
+  }
+
+  #[test]
+  fn test_bit_reader() {
+    let bytes      = [0b10111101, 0xff, 0b00010000, 0x80];
+    let mut reader = BitReader::new(&bytes);
+
+    assert_eq!(reader.read_bits(3), Ok(0b101));
+    assert_eq!(reader.read_signed_bits(4), Ok(-2));
+    assert_eq!(reader.read_bits(0), Ok(0));
+    assert_eq!(reader.remaining(), (&bytes[0..], 7));
+    assert_eq!(reader.read_bits(9), Ok(0x1ff));
+    assert_eq!(reader.read_unary(), Ok(3));
+    assert_eq!(reader.remaining(), (&bytes[2..], 4));
+
+    reader.align();
+
+    assert_eq!(reader.remaining(), (&bytes[3..], 0));
+    assert_eq!(reader.read_bits(9), Err(ErrorKind::Incomplete(5)));
+    assert_eq!(reader.read_bits(8), Ok(0x80));
+    assert_eq!(reader.read_unary(), Err(ErrorKind::Incomplete(5)));
+
+    let bytes      = [0xff; 20];
+    let mut reader = BitReader::with_offset(&bytes, 3);
+
+    for _ in 0..4 {
+      assert_eq!(reader.read_bits(32), Ok(0xffffffff));
+    }
+
+    assert_eq!(reader.remaining(), (&bytes[16..], 3));
+    assert_eq!(reader.read_bits(29), Ok(0x1fffffff));
+    assert_eq!(reader.read_bits(1), Err(ErrorKind::Incomplete(21)));
+  }
+
+  #[test]
+  fn test_bit_reader_unary() {
+    let inputs  = [ (&[0b10000000][..], 0)
+                  , (&[0b11000000][..], 1)
+                  , (&[0b00000001][..], 0)
+                  , (&[0b11111111][..], 7)
+                  , (&[0b00000000, 0b10000000][..], 0)
+                  , (&[0b10000000, 0b10000000][..], 1)
+                  , (&[0b00000000, 0b00000001][..], 0)
+                  , (&[0b11111110, 0b00000010][..], 7)
+                  ];
+    let results = [ ((&inputs[0].0[..], 1), 0)
+                  , ((&inputs[1].0[..], 2), 0)
+                  , ((&[][..], 0), 7)
+                  , ((&[][..], 0), 0)
+                  , ((&inputs[4].0[1..], 1), 8)
+                  , ((&inputs[5].0[1..], 1), 7)
+                  , ((&[][..], 0), 15)
+                  , ((&inputs[7].0[1..], 7), 7)
+                  ];
+
+    for (input, result) in inputs.iter().zip(results.iter()) {
+      let mut reader = BitReader::with_offset(input.0, input.1);
+
+      assert_eq!(reader.read_unary(), Ok(result.1));
+      assert_eq!(reader.remaining(), result.0);
+    }
+
+    let bytes      = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01];
+    let mut reader = BitReader::with_offset(&[0b10101010, 0], 7);
+
+    assert_eq!(reader.read_unary(), Err(ErrorKind::Incomplete(3)));
+    assert_eq!(BitReader::new(&bytes).read_unary(), Ok(95));
+  }
+
+  #[test]
+  fn test_bit_reader_rice() {
+    let mut writer = BitWriter::new();
+    let values     = [0, -1, 3, 1000, -70000, 5, i32::max_value(),
+                      i32::min_value()];
+
+    for parameter in 0..31 {
+      writer.clear();
+
+      for value in &values[0..6] {
+        writer.write_rice(*value, parameter);
+      }
+
+      writer.align();
+
+      let mut output = [0; 6];
+      let mut reader = BitReader::new(writer.as_slice());
+
+      assert_eq!(reader.read_rice(parameter, &mut output), Ok(()));
+      assert_eq!(&output, &values[0..6]);
+    }
+
+    writer.clear();
+    writer.write_rice(values[6], 30);
+    writer.write_rice(values[7], 30);
+    writer.write_rice(7, 2);
+
+    let mut output = [0; 3];
+    let mut reader = BitReader::new(writer.as_slice());
+
+    assert_eq!(reader.read_rice(30, &mut output[0..2]), Ok(()));
+    assert_eq!(&output[0..2], &values[6..8]);
+    assert!(reader.read_rice(2, &mut output[2..]).is_err());
+  }
+
   #[test]
   fn test_byte_stream() {
     let bytes      = b"Hello World";
@@ -0,0 +1,774 @@
+use metadata::{self, Metadata, StreamInfo};
+use frame::{self, ChannelAssignment, NumberType, Header, StereoMode};
+use subframe::{self, Apodization};
+use utility::{BitWriter, ErrorKind, MD5};
+
+use std::cmp;
+use std::io;
+
+/// Settings for encoding a stream.
+///
+/// `EncoderConfig::level` gives the same settings as the compression
+/// levels of the reference encoder, `flac -0` through `flac -8`, and every
+/// field can be changed afterwards to override a single setting:
+///
+/// | Level | Block size | LPC order | Partition orders | Stereo         |
+/// |-------|------------|-----------|------------------|----------------|
+/// | 0     | 1152       | 0         | 0 to 3           | `Independent`  |
+/// | 1     | 1152       | 0         | 0 to 3           | `LooseMidSide` |
+/// | 2     | 1152       | 0         | 0 to 3           | `MidSide`      |
+/// | 3     | 4096       | 6         | 0 to 4           | `Independent`  |
+/// | 4     | 4096       | 8         | 0 to 4           | `LooseMidSide` |
+/// | 5     | 4096       | 8         | 0 to 5           | `MidSide`      |
+/// | 6     | 4096       | 8         | 0 to 6           | `MidSide`      |
+/// | 7     | 4096       | 12        | 0 to 6           | `MidSide`      |
+/// | 8     | 4096       | 12        | 0 to 6           | `MidSide`      |
+///
+/// Levels up to five use a `Tukey(0.5)` window, six and seven add
+/// `PartialTukey(2)`, and eight adds `PunchoutTukey(3)` on top of that.
+/// None of them use an exhaustive model search and the precision of the
+/// coefficients is picked from the bits per sample and block size.
+///
+/// Parity with the reference encoder is only in these settings. The frames
+/// don't come out as the same bytes, and their sizes aren't measured
+/// against the ones of the reference encoder. There are also a few
+/// differences in the details: escaped partitions are always tried, which
+/// the reference encoder leaves out, and `LooseMidSide` estimates the
+/// channel assignment for every frame instead of only every so often.
+///
+/// The settings of every level are within the limits of the streamable
+/// subset, though they only get checked against them once
+/// `streamable_subset` is set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncoderConfig {
+  /// Number of samples, per channel, within each frame.
+  pub block_size: u16,
+  /// How the channels of stereo streams get decorrelated.
+  pub stereo: StereoMode,
+  /// Settings for the search of the smallest subframe.
+  pub subframe: subframe::Config,
+  /// Keep the stream within the streamable subset, which lets a decoder
+  /// start from any frame without `StreamInfo`. Off by default.
+  ///
+  /// The block size, `LPC` order and Rice partition order can't be above
+  /// the limits of the subset, which are a block size of 4608 and an order
+  /// of 12 for sample rates up to 48kHz, or 16384 and 32 above that, and a
+  /// partition order of eight. The frame header also has to hold the sample
+  /// rate and bits per sample without referring to `StreamInfo`.
+  pub streamable_subset: bool,
+}
+
+impl EncoderConfig {
+  /// Constructs the settings of a compression level from zero, the fastest,
+  /// to eight, the smallest. Levels above eight are the same as eight.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use flac::EncoderConfig;
+  ///
+  /// let mut config = EncoderConfig::level(8);
+  ///
+  /// config.block_size = 2048;
+  ///
+  /// assert_eq!(config.subframe.max_lpc_order, 12);
+  /// ```
+  pub fn level(level: u32) -> Self {
+    let level = cmp::min(level, 8);
+
+    let (block_size, max_lpc_order, max_partition_order) = match level {
+      0...2 => (1152, 0, 3),
+      3     => (4096, 6, 4),
+      4     => (4096, 8, 4),
+      5     => (4096, 8, 5),
+      6     => (4096, 8, 6),
+      _     => (4096, 12, 6),
+    };
+
+    let stereo = match level {
+      0 | 3 => StereoMode::Independent,
+      1 | 4 => StereoMode::LooseMidSide,
+      _     => StereoMode::MidSide,
+    };
+
+    let mut apodizations = vec![Apodization::Tukey(0.5)];
+
+    if level >= 6 {
+      apodizations.push(Apodization::PartialTukey(2));
+    }
+
+    if level == 8 {
+      apodizations.push(Apodization::PunchoutTukey(3));
+    }
+
+    EncoderConfig {
+      block_size: block_size,
+      stereo: stereo,
+      subframe: subframe::Config {
+        max_lpc_order: max_lpc_order,
+        qlp_coeff_precision: 0,
+        apodizations: apodizations,
+        exhaustive_model_search: false,
+        min_partition_order: 0,
+        max_partition_order: max_partition_order,
+      },
+      streamable_subset: false,
+    }
+  }
+
+  // Whether the settings are within the limits of the streamable subset
+  // for a stream at `sample_rate`.
+  fn is_within_subset(&self, sample_rate: u32) -> bool {
+    let (max_block_size, max_lpc_order, max_partition_order) =
+      frame::subset_limits(sample_rate);
+
+    self.block_size as u32 <= max_block_size &&
+      self.subframe.max_lpc_order <= max_lpc_order &&
+      self.subframe.max_partition_order <= max_partition_order
+  }
+}
+
+impl Default for EncoderConfig {
+  /// Constructs the settings of compression level five, the default of the
+  /// reference encoder.
+  fn default() -> Self {
+    EncoderConfig::level(5)
+  }
+}
+
+/// FLAC stream that encodes samples and writes them to an `io::Write`.
+///
+/// The stream header, `StreamInfo` and any other metadata blocks, is
+/// written when the `StreamWriter` gets constructed. Samples are buffered
+/// until a whole block is available and then get encoded as a frame.
+pub struct StreamWriter<W: io::Write> {
+  writer: W,
+  info: StreamInfo,
+  md5: MD5,
+  output: BitWriter,
+  config: EncoderConfig,
+  buffer: Vec<i32>,
+  block_size: usize,
+  channel: usize,
+  sample_index: usize,
+  frame_number: u32,
+  bytes_written: u64,
+  is_finished: bool,
+}
+
+fn to_io_error(error: io::Error) -> ErrorKind {
+  ErrorKind::IO(error.kind())
+}
+
+// Check that the stream can be described with a FLAC frame and the
+// `StreamInfo` metadata block.
+fn is_valid_stream_info(info: &StreamInfo) -> bool {
+  info.channels >= 1 && info.channels as usize <= frame::MAX_CHANNELS &&
+  info.bits_per_sample >= 4 && info.bits_per_sample <= 32 &&
+  info.sample_rate > 0 && info.sample_rate <= 0xfffff &&
+  info.max_block_size >= 16
+}
+
+// Encoder settings for `StreamWriter::with_metadata`, the default ones
+// with the block size of `info`.
+fn info_config(info: &StreamInfo) -> EncoderConfig {
+  EncoderConfig {
+    block_size: info.max_block_size,
+    ..Default::default()
+  }
+}
+
+impl<W> StreamWriter<W> where W: io::Write {
+  /// Constructs a `StreamWriter` that only has the `StreamInfo` metadata
+  /// block.
+  ///
+  /// See `StreamWriter::with_metadata` for how `info` is used.
+  #[inline]
+  pub fn new(writer: W, info: StreamInfo) -> Result<Self, ErrorKind> {
+    StreamWriter::with_metadata(writer, info, Vec::new())
+  }
+
+  /// Constructs a `StreamWriter` and writes the stream header along with
+  /// the given metadata blocks.
+  ///
+  /// Only `sample_rate`, `channels`, `bits_per_sample`, and
+  /// `max_block_size` are used from `info`, where `max_block_size` is the
+  /// number of samples, per channel, within each frame. Everything else gets
+  /// filled in while encoding. Any `StreamInfo` blocks inside of `metadata`
+  /// are skipped.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::InvalidStreamInfo` is returned when `info` describes a
+  ///   stream FLAC doesn't support.
+  /// * `ErrorKind::IO(_)` is returned when writing the header fails.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use flac::StreamWriter;
+  /// use flac::metadata::StreamInfo;
+  ///
+  /// let mut info: StreamInfo = Default::default();
+  ///
+  /// info.sample_rate     = 44100;
+  /// info.channels        = 2;
+  /// info.bits_per_sample = 16;
+  /// info.max_block_size  = 4096;
+  ///
+  /// let mut stream = StreamWriter::new(Vec::new(), info).unwrap();
+  ///
+  /// stream.write_samples(&[0i16, 0, 100, -100, 200, -200]).unwrap();
+  /// stream.finish().unwrap();
+  ///
+  /// let bytes = stream.into_inner();
+  /// ```
+  #[inline]
+  pub fn with_metadata(writer: W, info: StreamInfo, metadata: Vec<Metadata>)
+                       -> Result<Self, ErrorKind> {
+    let config = info_config(&info);
+
+    StreamWriter::with_config(writer, info, metadata, config)
+  }
+
+  /// Constructs a `StreamWriter` the same way as
+  /// `StreamWriter::with_metadata`, except with the encoder settings of
+  /// `config`.
+  ///
+  /// The block size of `config` gets used instead of the `max_block_size`
+  /// of `info`.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::InvalidStreamInfo` is returned when `info` describes a
+  ///   stream FLAC doesn't support.
+  /// * `ErrorKind::InvalidSubset` is returned when the stream has to stay
+  ///   within the streamable subset, but the settings of `config` are above
+  ///   its limits or the frame header can't hold the sample rate or bits
+  ///   per sample of `info`.
+  /// * `ErrorKind::IO(_)` is returned when writing the header fails.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// use flac::{EncoderConfig, StreamWriter};
+  /// use flac::metadata::StreamInfo;
+  ///
+  /// let mut info: StreamInfo = Default::default();
+  ///
+  /// info.sample_rate     = 44100;
+  /// info.channels        = 2;
+  /// info.bits_per_sample = 16;
+  ///
+  /// let config     = EncoderConfig::level(8);
+  /// let mut stream = StreamWriter::with_config(Vec::new(), info, Vec::new(),
+  ///                                            config).unwrap();
+  ///
+  /// stream.write_samples(&[0i16, 0, 100, -100, 200, -200]).unwrap();
+  /// stream.finish().unwrap();
+  ///
+  /// assert_eq!(stream.info().max_block_size, 4096);
+  /// ```
+  pub fn with_config(writer: W, info: StreamInfo, metadata: Vec<Metadata>,
+                     config: EncoderConfig)
+                     -> Result<Self, ErrorKind> {
+    let mut info = info;
+
+    info.max_block_size = config.block_size;
+
+    if !is_valid_stream_info(&info) {
+      return Err(ErrorKind::InvalidStreamInfo);
+    }
+
+    let bits_per_sample = info.bits_per_sample as usize;
+
+    if config.streamable_subset &&
+       (!config.is_within_subset(info.sample_rate) ||
+        !frame::is_header_complete(info.sample_rate, bits_per_sample)) {
+      return Err(ErrorKind::InvalidSubset);
+    }
+
+    let block_size = info.max_block_size as usize;
+    let channels   = info.channels as usize;
+
+    let mut stream_info = info;
+
+    stream_info.min_block_size = info.max_block_size;
+    stream_info.min_frame_size = 0;
+    stream_info.max_frame_size = 0;
+    stream_info.total_samples  = 0;
+    stream_info.md5_sum        = [0; 16];
+
+    let mut stream = StreamWriter {
+      writer: writer,
+      info: stream_info,
+      md5: MD5::new(),
+      output: BitWriter::new(),
+      config: config,
+      buffer: vec![0; block_size * channels],
+      block_size: block_size,
+      channel: 0,
+      sample_index: 0,
+      frame_number: 0,
+      bytes_written: 0,
+      is_finished: false,
+    };
+
+    try!(stream.write_header(metadata));
+
+    Ok(stream)
+  }
+
+  fn write_header(&mut self, metadata: Vec<Metadata>)
+                  -> Result<(), ErrorKind> {
+    let blocks: Vec<Metadata> = metadata.into_iter()
+                                        .filter(|b| !b.is_stream_info())
+                                        .collect();
+    let blocks_len = blocks.len();
+    let mut header = Vec::new();
+
+    header.extend_from_slice(b"fLaC");
+
+    let stream_info = metadata::Data::StreamInfo(self.info);
+    let info_block  = Metadata::new(blocks_len == 0, 34, stream_info);
+
+    try!(info_block.to_bytes(&mut header).map_err(to_io_error));
+
+    for (i, block) in blocks.into_iter().enumerate() {
+      let length = (block.bytes_len() - 4) as u32;
+      let block  = Metadata::new(i + 1 == blocks_len, length, block.data);
+
+      try!(block.to_bytes(&mut header).map_err(to_io_error));
+    }
+
+    try!(self.writer.write_all(&header).map_err(to_io_error));
+
+    self.bytes_written += header.len() as u64;
+
+    Ok(())
+  }
+
+  /// Returns information for the current stream.
+  ///
+  /// The total samples, frame sizes, and MD5 signature are only complete
+  /// after `StreamWriter::finish` has been called.
+  #[inline]
+  pub fn info(&self) -> StreamInfo {
+    self.info
+  }
+
+  /// Encode interleaved samples.
+  ///
+  /// Samples are expected to fit within `bits_per_sample` and be
+  /// interleaved by channel, the same order `Stream::iter` returns them
+  /// in. A slice doesn't need to end on a channel boundary, the next call
+  /// continues where the previous one ended.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::InvalidSampleSize` is returned when one of the samples
+  ///   doesn't fit within `bits_per_sample`, without encoding any of them.
+  /// * `ErrorKind::InvalidWrite` is returned after the stream is finished.
+  /// * `ErrorKind::IO(_)` is returned when writing a frame fails.
+  pub fn write_samples<S>(&mut self, samples: &[S]) -> Result<(), ErrorKind>
+   where S: Copy + Into<i32> {
+    let channels      = self.info.channels as usize;
+    let sample_bytes  = (self.info.bits_per_sample as usize + 7) / 8;
+    let shift         = 32 - self.info.bits_per_sample as u32;
+    let mut bytes     = [0; 4];
+
+    if self.is_finished {
+      return Err(ErrorKind::InvalidWrite);
+    }
+
+    // Shifting the value up and back down again only keeps it the same
+    // when it fits within the bits per sample.
+    let is_valid = samples.iter().all(|sample| {
+      let value = (*sample).into();
+
+      (value << shift) >> shift == value
+    });
+
+    if !is_valid {
+      return Err(ErrorKind::InvalidSampleSize);
+    }
+
+    for sample in samples {
+      let value = (*sample).into();
+
+      bytes[0] = value as u8;
+      bytes[1] = (value >> 8) as u8;
+      bytes[2] = (value >> 16) as u8;
+      bytes[3] = (value >> 24) as u8;
+
+      self.md5.update(&bytes[0..sample_bytes]);
+
+      let index = self.channel * self.block_size + self.sample_index;
+
+      self.buffer[index] = value;
+      self.channel      += 1;
+
+      if self.channel == channels {
+        self.channel       = 0;
+        self.sample_index += 1;
+
+        if self.sample_index == self.block_size {
+          try!(self.write_frame());
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  fn write_frame(&mut self) -> Result<(), ErrorKind> {
+    let block_size = self.sample_index;
+    let channels   = self.info.channels as usize;
+
+    // A short final block still needs the channels to be next to each
+    // other inside the buffer.
+    if block_size < self.block_size {
+      for channel in 1..channels {
+        let start = channel * self.block_size;
+
+        for i in 0..block_size {
+          self.buffer[channel * block_size + i] = self.buffer[start + i];
+        }
+      }
+    }
+
+    let header = Header {
+      block_size: block_size as u32,
+      sample_rate: self.info.sample_rate,
+      channels: self.info.channels,
+      channel_assignment: ChannelAssignment::Independent,
+      bits_per_sample: self.info.bits_per_sample as usize,
+      number: NumberType::Frame(self.frame_number),
+      crc: 0,
+    };
+
+    self.output.clear();
+
+    frame::encode(&header, &self.buffer[0..(block_size * channels)],
+                  self.config.stereo, &self.config.subframe,
+                  &mut self.output);
+
+    try!(self.writer.write_all(self.output.as_slice())
+                    .map_err(to_io_error));
+
+    let frame_size = self.output.as_slice().len() as u32;
+
+    if self.frame_number == 0 || frame_size < self.info.min_frame_size {
+      self.info.min_frame_size = frame_size;
+    }
+
+    if frame_size > self.info.max_frame_size {
+      self.info.max_frame_size = frame_size;
+    }
+
+    self.info.total_samples += block_size as u64;
+    self.bytes_written      += frame_size as u64;
+    self.frame_number       += 1;
+    self.sample_index        = 0;
+
+    Ok(())
+  }
+
+  /// Encode any remaining samples and flush the underlining writer.
+  ///
+  /// After this call `StreamWriter::info` holds the complete information
+  /// of the stream, calling it more than once doesn't do anything.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::IO(_)` is returned when writing the last frame, or
+  ///   flushing the writer, fails.
+  pub fn finish(&mut self) -> Result<(), ErrorKind> {
+    if self.is_finished {
+      return Ok(());
+    }
+
+    if self.sample_index > 0 {
+      try!(self.write_frame());
+    }
+
+    self.info.md5_sum = self.md5.clone().finish();
+    self.is_finished  = true;
+
+    self.writer.flush().map_err(to_io_error)
+  }
+
+  /// Returns the underlining writer.
+  #[inline]
+  pub fn into_inner(self) -> W {
+    self.writer
+  }
+}
+
+impl<W> StreamWriter<W> where W: io::Write + io::Seek {
+  /// Finish encoding and rewrite the `StreamInfo` block with the final
+  /// information of the stream.
+  ///
+  /// This does the same as `StreamWriter::finish` but also fills in the
+  /// total samples, minimum and maximum frame size, and the MD5 signature
+  /// of the stream header, which is only possible when the writer is
+  /// seekable.
+  ///
+  /// # Failures
+  ///
+  /// * `ErrorKind::IO(_)` is returned when writing, or seeking within, the
+  ///   writer fails.
+  pub fn finalize(&mut self) -> Result<(), ErrorKind> {
+    try!(self.finish());
+
+    // The `StreamInfo` data is right after the "fLaC" marker and its
+    // block header.
+    let offset     = 8 - (self.bytes_written as i64);
+    let mut buffer = Vec::with_capacity(self.info.bytes_len());
+
+    try!(self.info.to_bytes(&mut buffer).map_err(to_io_error));
+
+    try!(self.writer.seek(io::SeekFrom::Current(offset))
+                    .map_err(to_io_error));
+    try!(self.writer.write_all(&buffer).map_err(to_io_error));
+
+    let remaining = (self.bytes_written as i64) - 8 - (buffer.len() as i64);
+
+    try!(self.writer.seek(io::SeekFrom::Current(remaining))
+                    .map_err(to_io_error));
+
+    self.writer.flush().map_err(to_io_error)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use frame::StereoMode;
+  use metadata::StreamInfo;
+  use subframe::Apodization;
+  use utility::ErrorKind;
+
+  use std::io::Cursor;
+
+  fn stream_info() -> StreamInfo {
+    let mut info: StreamInfo = Default::default();
+
+    info.sample_rate     = 44100;
+    info.channels        = 2;
+    info.bits_per_sample = 16;
+    info.max_block_size  = 16;
+
+    info
+  }
+
+  #[test]
+  fn test_invalid_stream_info() {
+    let mut info = stream_info();
+
+    info.channels = 9;
+
+    let result = StreamWriter::new(Vec::new(), info);
+
+    assert_eq!(result.err().map(|e| e), Some(ErrorKind::InvalidStreamInfo));
+  }
+
+  #[test]
+  fn test_level() {
+    let fastest = EncoderConfig::level(0);
+
+    assert_eq!(fastest.block_size, 1152);
+    assert_eq!(fastest.stereo, StereoMode::Independent);
+    assert_eq!(fastest.subframe.max_lpc_order, 0);
+    assert_eq!(fastest.subframe.max_partition_order, 3);
+
+    let loose = EncoderConfig::level(4);
+
+    assert_eq!(loose.stereo, StereoMode::LooseMidSide);
+    assert_eq!(loose.subframe.max_lpc_order, 8);
+
+    assert_eq!(EncoderConfig::default(), EncoderConfig::level(5));
+    assert_eq!(EncoderConfig::level(9), EncoderConfig::level(8));
+
+    let smallest = EncoderConfig::level(8);
+
+    assert_eq!(smallest.block_size, 4096);
+    assert_eq!(smallest.stereo, StereoMode::MidSide);
+    assert_eq!(smallest.subframe.max_lpc_order, 12);
+    assert_eq!(smallest.subframe.max_partition_order, 6);
+    assert_eq!(smallest.subframe.apodizations, vec![
+      Apodization::Tukey(0.5), Apodization::PartialTukey(2),
+      Apodization::PunchoutTukey(3),
+    ]);
+    assert!(!smallest.subframe.exhaustive_model_search);
+  }
+
+  #[test]
+  fn test_with_config() {
+    let mut config = EncoderConfig::level(3);
+
+    config.block_size = 8;
+
+    let result = StreamWriter::with_config(Vec::new(), stream_info(),
+                                           Vec::new(), config.clone());
+
+    assert_eq!(result.err(), Some(ErrorKind::InvalidStreamInfo));
+
+    config.block_size = 32;
+
+    let stream = StreamWriter::with_config(Vec::new(), stream_info(),
+                                           Vec::new(), config).unwrap();
+    let info   = stream.info();
+
+    assert_eq!(info.min_block_size, 32);
+    assert_eq!(info.max_block_size, 32);
+  }
+
+  #[test]
+  fn test_streamable_subset() {
+    let mut config = EncoderConfig::level(8);
+
+    config.streamable_subset = true;
+
+    assert!(StreamWriter::with_config(Vec::new(), stream_info(), Vec::new(),
+                                      config.clone()).is_ok());
+
+    // Each setting above the limits at 48kHz.
+    let mut configs = vec![config.clone(); 3];
+
+    configs[0].block_size                   = 8192;
+    configs[1].subframe.max_lpc_order       = 32;
+    configs[2].subframe.max_partition_order = 12;
+
+    for config in configs.iter() {
+      let result = StreamWriter::with_config(Vec::new(), stream_info(),
+                                             Vec::new(), config.clone());
+
+      assert_eq!(result.err(), Some(ErrorKind::InvalidSubset));
+    }
+
+    let mut info = stream_info();
+
+    info.sample_rate = 96000;
+
+    // Only the partition order has the same limit above 48kHz.
+    for config in configs[0..2].iter() {
+      let stream = StreamWriter::with_config(Vec::new(), info, Vec::new(),
+                                             config.clone()).unwrap();
+
+      assert_eq!(stream.config, *config);
+    }
+
+    let result = StreamWriter::with_config(Vec::new(), info, Vec::new(),
+                                           configs[2].clone());
+
+    assert_eq!(result.err(), Some(ErrorKind::InvalidSubset));
+
+    // The frame header can't hold 17 bits per sample.
+    info.bits_per_sample = 17;
+
+    let result = StreamWriter::with_config(Vec::new(), info, Vec::new(),
+                                           config.clone());
+
+    assert_eq!(result.err(), Some(ErrorKind::InvalidSubset));
+
+    config.streamable_subset = false;
+
+    assert!(StreamWriter::with_config(Vec::new(), info, Vec::new(),
+                                      config).is_ok());
+
+    // Without a config the stream isn't kept within the subset.
+    info.sample_rate    = 44100;
+    info.max_block_size = 8192;
+
+    let stream = StreamWriter::new(Vec::new(), info).unwrap();
+
+    assert_eq!(stream.info().max_block_size, 8192);
+    assert_eq!(stream.info().bits_per_sample, 17);
+  }
+
+  #[test]
+  fn test_header() {
+    let stream = StreamWriter::new(Vec::new(), stream_info()).unwrap();
+    let bytes  = stream.into_inner();
+
+    assert_eq!(&bytes[0..8], b"fLaC\x80\0\0\x22");
+    assert_eq!(bytes.len(), 42);
+  }
+
+  #[test]
+  fn test_finish() {
+    let samples: Vec<i16> = (0..40).map(|i| (i * 37 % 23) as i16).collect();
+    let mut stream = StreamWriter::new(Vec::new(), stream_info()).unwrap();
+
+    assert!(stream.write_samples(&samples).is_ok());
+    assert!(stream.finish().is_ok());
+
+    let info = stream.info();
+
+    assert_eq!(info.total_samples, 20);
+    assert_eq!(info.min_block_size, 16);
+    assert!(info.min_frame_size > 0);
+    assert!(info.max_frame_size >= info.min_frame_size);
+
+    assert_eq!(stream.write_samples(&samples), Err(ErrorKind::InvalidWrite));
+    assert_eq!(stream.info(), info);
+
+    let mut stream = StreamWriter::new(Cursor::new(Vec::new()),
+                                       stream_info()).unwrap();
+
+    assert!(stream.finalize().is_ok());
+    assert_eq!(stream.write_samples(&samples), Err(ErrorKind::InvalidWrite));
+  }
+
+  #[test]
+  fn test_sample_range() {
+    let mut info = stream_info();
+
+    info.bits_per_sample = 8;
+
+    let mut stream = StreamWriter::new(Vec::new(), info).unwrap();
+
+    assert!(stream.write_samples(&[-128, 127]).is_ok());
+    assert_eq!(stream.write_samples(&[0, 128]),
+               Err(ErrorKind::InvalidSampleSize));
+    assert_eq!(stream.write_samples(&[-129, 0]),
+               Err(ErrorKind::InvalidSampleSize));
+
+    let samples = (0..5000).map(|i| i * 1000).collect::<Vec<i32>>();
+
+    assert_eq!(stream.write_samples(&samples),
+               Err(ErrorKind::InvalidSampleSize));
+    assert!(stream.finish().is_ok());
+
+    // Nothing from the rejected calls gets encoded.
+    assert_eq!(stream.info().total_samples, 1);
+
+    info.bits_per_sample = 32;
+
+    let mut stream = StreamWriter::new(Vec::new(), info).unwrap();
+
+    assert!(stream.write_samples(&[i32::min_value(),
+                                   i32::max_value()]).is_ok());
+  }
+
+  #[test]
+  fn test_finalize() {
+    let samples: Vec<i16> = (0..40).map(|i| (i * 37 % 23) as i16).collect();
+    let cursor     = Cursor::new(Vec::new());
+    let mut stream = StreamWriter::new(cursor, stream_info()).unwrap();
+
+    assert!(stream.write_samples(&samples).is_ok());
+    assert!(stream.finalize().is_ok());
+
+    let info   = stream.info();
+    let length = stream.bytes_written as usize;
+    let bytes  = stream.into_inner().into_inner();
+
+    let mut expected = Vec::new();
+
+    assert!(info.to_bytes(&mut expected).is_ok());
+    assert_eq!(bytes.len(), length);
+    assert_eq!(&bytes[8..42], &expected[..]);
+  }
+}
@@ -0,0 +1,81 @@
+extern crate flac;
+
+use flac::{Block, StreamBuffer};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs::File;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// Allocator that counts every allocation and deallocation made while
+// `IS_COUNTING` is set. There is only a single test in this file, so
+// nothing else runs while it's counting.
+struct Counter;
+
+static IS_COUNTING: AtomicBool = AtomicBool::new(false);
+static CALLS: AtomicUsize      = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counter {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    if IS_COUNTING.load(Ordering::SeqCst) {
+      CALLS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    if IS_COUNTING.load(Ordering::SeqCst) {
+      CALLS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    System.dealloc(ptr, layout)
+  }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counter = Counter;
+
+fn read_file(filename: &str) -> Vec<u8> {
+  let mut bytes = Vec::new();
+
+  File::open(filename).and_then(|mut file| file.read_to_end(&mut bytes))
+                      .unwrap();
+
+  bytes
+}
+
+#[test]
+fn test_steady_state() {
+  let filenames = [
+    "tests/assets/input-SCPAP.flac",
+    "tests/assets/input-SVAUP.flac",
+    "tests/assets/input-24bit.flac",
+    "tests/assets/input-32bit.flac",
+    "tests/assets/input-32bit-size-code.flac",
+    "tests/assets/input-32bit-side.flac",
+  ];
+
+  for filename in filenames.iter() {
+    for is_md5_enabled in [false, true].iter() {
+      let bytes      = read_file(filename);
+      let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+      let mut block  = Block::<i64>::new();
+      let mut blocks = 1;
+
+      stream.compute_md5(*is_md5_enabled);
+
+      assert!(stream.next_block(&mut block).unwrap());
+
+      IS_COUNTING.store(true, Ordering::SeqCst);
+
+      while stream.next_block(&mut block).unwrap() {
+        blocks += 1;
+      }
+
+      IS_COUNTING.store(false, Ordering::SeqCst);
+
+      assert!(blocks > 1, "{}", filename);
+      assert_eq!(CALLS.swap(0, Ordering::SeqCst), 0, "{}", filename);
+    }
+  }
+}
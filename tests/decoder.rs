@@ -0,0 +1,128 @@
+extern crate flac;
+
+use flac::{Block, Decoder, ErrorKind, StreamReader};
+use flac::decoder::Status;
+use std::fs::File;
+use std::io::Read;
+
+fn read_file(filename: &str) -> Vec<u8> {
+  let mut bytes = Vec::new();
+
+  File::open(filename).and_then(|mut file| file.read_to_end(&mut bytes))
+                      .unwrap();
+
+  bytes
+}
+
+fn interleave(block: &Block<i64>, samples: &mut Vec<i64>) {
+  for i in 0..block.len() {
+    for channel in 0..block.channels() {
+      samples.push(block.channel(channel)[i]);
+    }
+  }
+}
+
+#[test]
+fn test_feed() {
+  let filenames = [
+    "tests/assets/input-pictures.flac",
+    "tests/assets/input-SCPAP.flac",
+    "tests/assets/input-24bit.flac",
+    "tests/assets/input-32bit.flac",
+    "tests/assets/input-32bit-size-code.flac",
+    "tests/assets/input-32bit-side.flac",
+  ];
+
+  for filename in filenames.iter() {
+    let bytes      = read_file(filename);
+    let mut stream = StreamReader::<File>::from_file(filename).unwrap();
+    let expected   = stream.iter::<i32>().map(|sample| sample as i64)
+                                         .collect::<Vec<_>>();
+
+    for chunk_size in [1, 7, 100, 4096, bytes.len()].iter() {
+      let mut decoder = Decoder::new();
+      let mut samples = Vec::new();
+
+      for chunk in bytes.chunks(*chunk_size) {
+        for block in decoder.feed(chunk) {
+          interleave(&block.unwrap(), &mut samples);
+        }
+      }
+
+      assert_eq!(decoder.info(), Some(stream.info()));
+      assert_eq!(decoder.metadata().len(), stream.metadata().len());
+
+      for (block, expected) in decoder.metadata().iter()
+                                     .zip(stream.metadata()) {
+        assert_eq!(block.data, expected.data);
+      }
+
+      assert!(decoder.is_metadata_done());
+      assert_eq!(samples, expected);
+    }
+  }
+}
+
+#[test]
+fn test_next_block() {
+  let bytes       = read_file("tests/assets/input-24bit.flac");
+  let mut decoder = Decoder::new();
+  let mut block   = Block::<i32>::new();
+
+  // Only the "fLaC" marker and part of `StreamInfo`.
+  assert!(decoder.feed::<i32>(&bytes[0..20]).next().is_none());
+
+  assert_eq!(decoder.info(), None);
+  assert_eq!(decoder.next_block(&mut block), Ok(Status::NeedMoreData));
+
+  assert!(decoder.feed::<i32>(&bytes[20..50]).next().is_none());
+
+  assert!(decoder.info().is_some());
+  assert_eq!(decoder.next_block(&mut block), Ok(Status::NeedMoreData));
+
+  decoder.feed::<i32>(&bytes[50..]);
+
+  let mut blocks = 0;
+
+  while decoder.next_block(&mut block) == Ok(Status::Block) {
+    blocks += 1;
+  }
+
+  assert_eq!(blocks, 6);
+  assert_eq!(block.len(), 200);
+  assert_eq!(decoder.next_block(&mut block), Ok(Status::NeedMoreData));
+}
+
+#[test]
+fn test_feed_error() {
+  let mut bytes = read_file("tests/assets/input-24bit.flac");
+
+  // Flip a bit inside of the first frame.
+  bytes[100] ^= 0x01;
+
+  let mut decoder = Decoder::new();
+  let results     = decoder.feed::<i32>(&bytes).collect::<Vec<_>>();
+
+  assert_eq!(results.len(), 1);
+  assert_eq!(results[0].as_ref().err().map(|error| error.kind),
+             Some(ErrorKind::InvalidCRC16));
+
+  // A maximum block size of 16 in `StreamInfo`, smaller than the frames.
+  let mut bytes = read_file("tests/assets/input-24bit.flac");
+
+  bytes[8..12].copy_from_slice(&[0, 16, 0, 16]);
+
+  let mut decoder = Decoder::new();
+
+  match decoder.feed::<i32>(&bytes).next() {
+    Some(Err(error)) => assert_eq!(error.kind, ErrorKind::InvalidFrameSize),
+    _                => panic!("Expected an error"),
+  }
+
+  let mut decoder = Decoder::new();
+
+  match decoder.feed::<i32>(b"OggS").next() {
+    Some(Err(error)) => assert_eq!(error.kind, ErrorKind::HeaderParser),
+    _                => panic!("Expected an error"),
+  }
+}
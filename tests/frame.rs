@@ -0,0 +1,77 @@
+extern crate flac;
+
+use flac::{Stream, ReadStream};
+use flac::frame::frame_parser;
+use std::fs::File;
+use std::io::Read;
+
+// Offset of the first frame, right after the last metadata block.
+fn metadata_end(bytes: &[u8]) -> usize {
+  let mut offset = 4;
+
+  loop {
+    let is_last = (bytes[offset] >> 7) == 1;
+    let length  = ((bytes[offset + 1] as usize) << 16) |
+                  ((bytes[offset + 2] as usize) << 8)  |
+                  (bytes[offset + 3] as usize);
+
+    offset += 4 + length;
+
+    if is_last {
+      return offset;
+    }
+  }
+}
+
+fn frame_round_trip(filename: &str) {
+  let info = Stream::<ReadStream<File>>::from_file(filename).unwrap().info();
+
+  let mut bytes = Vec::new();
+
+  File::open(filename).unwrap().read_to_end(&mut bytes).unwrap();
+
+  let channels   = info.channels as usize;
+  let block_size = info.max_block_size as usize;
+  let mut buffer = vec![0i64; channels * block_size];
+  let mut input  = &bytes[metadata_end(&bytes)..];
+
+  while !input.is_empty() {
+    let (i, frame) = frame_parser(input, &info, &mut buffer).unwrap();
+    let length     = input.len() - i.len();
+    let mut output = Vec::new();
+
+    frame.to_bytes(&mut output).unwrap();
+
+    // A 32 bit sample size taken from `StreamInfo` gets written with its
+    // own code, which changes both CRCs along with it.
+    if (input[3] >> 1) & 0b111 == 0b000 && info.bits_per_sample == 32 {
+      let mut header = Vec::new();
+
+      frame.header.to_bytes(&mut header).unwrap();
+
+      let header_len = header.len();
+
+      assert_eq!((output[3] >> 1) & 0b111, 0b111);
+      assert_eq!(output[3] & 0b11110001, input[3] & 0b11110001);
+      assert_eq!(&output[0..3], &input[0..3]);
+      assert_eq!(&output[4..(header_len - 1)], &input[4..(header_len - 1)]);
+      assert_eq!(&output[header_len..(length - 2)],
+                 &input[header_len..(length - 2)]);
+    } else {
+      assert_eq!(&output[..], &input[0..length]);
+    }
+
+    input = i;
+  }
+}
+
+#[test]
+fn test_frame_round_trip() {
+  frame_round_trip("tests/assets/input-pictures.flac");
+  frame_round_trip("tests/assets/input-SCPAP.flac");
+  frame_round_trip("tests/assets/input-SVAUP.flac");
+  frame_round_trip("tests/assets/input-24bit.flac");
+  frame_round_trip("tests/assets/input-32bit.flac");
+  frame_round_trip("tests/assets/input-32bit-size-code.flac");
+  frame_round_trip("tests/assets/input-32bit-side.flac");
+}
@@ -1,7 +1,12 @@
 extern crate flac;
 
-use flac::{metadata, ErrorKind};
-use flac::metadata::{Picture, PictureType};
+use flac::{metadata, ErrorKind, StreamReader, Verification};
+use flac::metadata::{
+  Application, Data, Editor, Picture, PictureType, StreamInfo,
+};
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
 
 fn compare_all_but_data(picture: &Picture, other_picture: &Picture) -> bool {
   (picture.picture_type == other_picture.picture_type) &&
@@ -27,6 +32,13 @@ fn test_get_vorbis_comment() {
 
   assert!(tags.is_ok(), "Should have vorbis comments");
   assert_eq!(no_tags.unwrap_err(), ErrorKind::NotFound);
+
+  let tags = tags.unwrap();
+
+  assert_eq!(tags.comments.len(), 6);
+  assert_eq!(tags.comments[0].0, "REPLAYGAIN_TRACK_PEAK");
+  assert_eq!(tags.get("ARTIST"), Some("1"));
+  assert_eq!(tags.get_all("Title"), vec!["2"]);
 }
 
 #[test]
@@ -98,3 +110,268 @@ fn test_get_picture() {
           "No constraint option");
   assert_eq!(no_picture.unwrap_err(), ErrorKind::NotFound);
 }
+
+#[test]
+fn test_metadata_round_trip() {
+  let filenames = [
+    "tests/assets/input-pictures.flac",
+    "tests/assets/input-SCPAP.flac",
+    "tests/assets/input-SVAUP.flac",
+  ];
+
+  for filename in filenames.iter() {
+    let mut bytes = Vec::new();
+
+    File::open(filename).unwrap().read_to_end(&mut bytes).unwrap();
+
+    // Skip over the "fLaC" marker.
+    let mut input   = &bytes[4..];
+    let mut is_last = false;
+
+    while !is_last {
+      let (i, block) = metadata::metadata_parser(input).unwrap();
+      let length     = input.len() - i.len();
+      let mut output = Vec::new();
+
+      block.to_bytes(&mut output).unwrap();
+
+      assert_eq!(block.bytes_len(), length);
+
+      assert_eq!(&output[..], &input[0..length]);
+
+      is_last = block.is_last();
+      input   = i;
+    }
+  }
+}
+
+// Copy of `filename` inside the temporary directory that tests can freely
+// change.
+fn copy_asset(filename: &str, name: &str) -> String {
+  let path = env::temp_dir().join(format!("flac-editor-{}.flac", name));
+  let copy = path.to_str().unwrap().to_owned();
+
+  fs::copy(filename, &copy).unwrap();
+
+  copy
+}
+
+fn file_len(filename: &str) -> u64 {
+  fs::metadata(filename).unwrap().len()
+}
+
+fn assert_audio(filename: &str) {
+  let mut stream = StreamReader::<File>::from_file(filename).unwrap();
+
+  stream.compute_md5(true);
+  stream.iter::<i16>().count();
+
+  assert_eq!(stream.verify(), Verification::Match);
+}
+
+// First byte of every metadata block header in `filename`, holding the last
+// block flag and the block type.
+fn header_bytes(filename: &str) -> Vec<u8> {
+  let mut bytes   = Vec::new();
+  let mut headers = Vec::new();
+  let mut offset  = 4;
+
+  File::open(filename).unwrap().read_to_end(&mut bytes).unwrap();
+
+  loop {
+    let header = bytes[offset];
+    let length = (bytes[offset + 1] as usize) << 16 |
+                 (bytes[offset + 2] as usize) << 8 |
+                 bytes[offset + 3] as usize;
+
+    headers.push(header);
+
+    if header >> 7 == 1 {
+      return headers;
+    }
+
+    offset += 4 + length;
+  }
+}
+
+fn application(length: usize) -> Data {
+  Data::Application(Application {
+    id: "test".to_owned(),
+    data: vec![0xaa; length],
+  })
+}
+
+#[test]
+fn test_editor_in_place() {
+  let filename = copy_asset("tests/assets/input-pictures.flac", "in-place");
+  let length   = file_len(&filename);
+
+  let metadata_len = {
+    let mut editor = Editor::from_file(&filename).unwrap();
+    let blocks_len = editor.blocks().iter().fold(4 + 34, |len, block| {
+                       len + 4 + block.length()
+                     });
+
+    assert_eq!(editor.blocks().len(), 15);
+    assert_eq!(editor.blocks()[14].data, Data::Padding(9753));
+
+    editor.retain(|block| !block.is_picture());
+    editor.push(application(100)).unwrap();
+    editor.save().unwrap();
+
+    let blocks = editor.blocks();
+
+    assert_eq!(blocks.len(), 2);
+    assert!(blocks[0].is_application() && !blocks[0].is_last());
+    assert!(blocks[1].is_padding() && blocks[1].is_last());
+
+    blocks_len
+  };
+
+  assert_eq!(file_len(&filename), length);
+
+  let editor = Editor::from_file(&filename).unwrap();
+  let blocks = editor.blocks();
+
+  // Stream info and the application block with their headers, along with
+  // the header of the padding.
+  let padding = metadata_len - 38 - 108 - 4;
+
+  assert_eq!(blocks.len(), 2);
+  assert_eq!(blocks[0].data, application(100));
+  assert_eq!(blocks[1].data, Data::Padding(padding));
+  assert_audio(&filename);
+
+  fs::remove_file(&filename).unwrap();
+}
+
+#[test]
+fn test_editor_rewrite() {
+  let filename = copy_asset("tests/assets/input-SVAUP.flac", "rewrite");
+  let length   = file_len(&filename);
+
+  assert_eq!(header_bytes(&filename), [0x00, 0x03, 0x04, 0x02, 0x7e, 0x81]);
+
+  {
+    let mut editor = Editor::from_file(&filename).unwrap();
+
+    // Seek table, vorbis comment, application, unknown, and padding.
+    assert_eq!(editor.blocks().len(), 5);
+    assert!(editor.blocks()[1].is_vorbis_comment());
+    assert!(editor.blocks()[3].is_unknown());
+
+    editor.move_block(1, 0);
+    editor.insert(1, application(20000)).unwrap();
+    editor.save().unwrap();
+  }
+
+  assert!(file_len(&filename) > length);
+
+  // The unknown block keeps its block type of 126.
+  assert_eq!(header_bytes(&filename),
+             [0x00, 0x04, 0x02, 0x03, 0x02, 0x7e, 0x81]);
+
+  let editor = Editor::from_file(&filename).unwrap();
+  let blocks = editor.blocks();
+
+  assert_eq!(blocks.len(), 6);
+  assert!(blocks[0].is_vorbis_comment());
+  assert_eq!(blocks[1].data, application(20000));
+  assert!(blocks[2].is_seek_table());
+  assert!(blocks[3].is_application());
+  assert!(blocks[4].is_unknown());
+  assert_eq!(blocks[5].data, Data::Padding(3201));
+  assert!(blocks[5].is_last());
+  assert_audio(&filename);
+
+  // Shrinking back down fits inside of the space the rewrite left.
+  {
+    let mut editor = Editor::from_file(&filename).unwrap();
+    let old        = editor.replace(1, application(10)).unwrap();
+
+    assert_eq!(old.data, application(20000));
+
+    editor.save().unwrap();
+  }
+
+  let editor = Editor::from_file(&filename).unwrap();
+  let blocks = editor.blocks();
+
+  assert_eq!(blocks.len(), 6);
+  assert_eq!(blocks[1].data, application(10));
+  assert_eq!(blocks[5].data, Data::Padding(3201 + 19990));
+  assert_audio(&filename);
+
+  fs::remove_file(&filename).unwrap();
+}
+
+#[test]
+fn test_editor_rewrite_file() {
+  let filename  = copy_asset("tests/assets/input-SCPAP.flac", "rewrite-file");
+  let temporary = format!("{}.tmp", filename);
+
+  fs::write(&temporary, b"not ours").unwrap();
+  set_mode(&filename, 0o640);
+
+  {
+    let mut editor = Editor::from_file(&filename).unwrap();
+
+    editor.push(application(20000)).unwrap();
+    editor.save().unwrap();
+  }
+
+  assert_eq!(fs::read(&temporary).unwrap(), b"not ours");
+  assert_eq!(mode(&filename), 0o640);
+  assert_audio(&filename);
+
+  fs::remove_file(&temporary).unwrap();
+  fs::remove_file(&filename).unwrap();
+}
+
+#[cfg(unix)]
+fn set_mode(filename: &str, mode: u32) {
+  use std::os::unix::fs::PermissionsExt;
+
+  let permissions = fs::Permissions::from_mode(mode);
+
+  fs::set_permissions(filename, permissions).unwrap();
+}
+
+#[cfg(unix)]
+fn mode(filename: &str) -> u32 {
+  use std::os::unix::fs::PermissionsExt;
+
+  fs::metadata(filename).unwrap().permissions().mode() & 0o777
+}
+
+#[cfg(not(unix))]
+fn set_mode(_: &str, _: u32) {}
+
+#[cfg(not(unix))]
+fn mode(_: &str) -> u32 {
+  0o640
+}
+
+#[test]
+fn test_editor_invalid() {
+  let filename = copy_asset("tests/assets/input-SCPAP.flac", "invalid");
+
+  let mut editor  = Editor::from_file(&filename).unwrap();
+  let stream_info = *editor.stream_info();
+  let blocks_len  = editor.blocks().len();
+
+  let default_info: StreamInfo = Default::default();
+
+  assert_eq!(editor.push(Data::StreamInfo(default_info)),
+             Err(ErrorKind::InvalidMetadata));
+  assert_eq!(editor.blocks().len(), blocks_len);
+
+  editor.block_mut(0).unwrap().data = Data::StreamInfo(stream_info);
+
+  assert_eq!(editor.save(), Err(ErrorKind::InvalidMetadata));
+  assert_eq!(editor.blocks().len(), blocks_len);
+  assert_audio(&filename);
+
+  fs::remove_file(&filename).unwrap();
+  assert!(Editor::from_file(&filename).is_err());
+}
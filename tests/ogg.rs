@@ -0,0 +1,178 @@
+extern crate flac;
+
+use flac::{
+  StreamBuffer, StreamReader, OggStreamReader, OggWriter, ErrorKind,
+  Verification,
+};
+use flac::metadata::Data;
+use flac::ogg::DEFAULT_PAGE_SIZE;
+use std::fs::File;
+use std::io::{Cursor, Read};
+
+const NATIVE_FILE: &'static str = "tests/assets/input-24bit.flac";
+// Synthetic, written by `OggWriter` rather than the reference encoder, with
+// a serial number of 0x1234abcd and pages of 700 bytes. The frames are the
+// ones of `NATIVE_FILE`, but the pages come from this crate, so reading Ogg
+// FLAC from other encoders isn't covered by it.
+//
+// TODO: add a fixture from the reference encoder, made with
+// `flac --ogg --serial-number=1 -o input-24bit-ref.oga input-24bit.flac`,
+// and decode and seek it in `test_ogg_decode` and `test_ogg_seek` too.
+const OGG_FILE: &'static str    = "tests/assets/input-24bit.oga";
+
+fn read_file(filename: &str) -> Vec<u8> {
+  let mut bytes = Vec::new();
+
+  File::open(filename).and_then(|mut file| file.read_to_end(&mut bytes))
+                      .unwrap();
+
+  bytes
+}
+
+fn native_samples() -> Vec<i32> {
+  let mut stream = StreamReader::<File>::from_file(NATIVE_FILE).unwrap();
+
+  stream.iter::<i32>().collect()
+}
+
+#[test]
+fn test_ogg_metadata() {
+  let native = StreamReader::<File>::from_file(NATIVE_FILE).unwrap();
+  let stream = OggStreamReader::<File>::from_ogg_file(OGG_FILE).unwrap();
+
+  assert_eq!(stream.info(), native.info());
+  assert_eq!(stream.metadata().len(), 2);
+
+  match stream.metadata()[0].data {
+    Data::VorbisComment(ref vorbis_comment) => {
+      assert_eq!(vorbis_comment.vendor_string, "OggWriter test fixture");
+      assert_eq!(vorbis_comment.get("TITLE"), Some("Ogg test"));
+      assert_eq!(vorbis_comment.get("ARTIST"), Some("flac"));
+    }
+    _                                       => panic!("Unexpected block"),
+  }
+
+  assert_eq!(stream.metadata()[1].data, Data::Padding(600));
+}
+
+#[test]
+fn test_ogg_decode() {
+  let samples    = native_samples();
+  let mut stream = OggStreamReader::<File>::from_ogg_file(OGG_FILE).unwrap();
+
+  stream.compute_md5(true);
+
+  assert_eq!(stream.iter::<i32>().collect::<Vec<_>>(), samples);
+  assert_eq!(stream.last_error(), None);
+  assert_eq!(stream.verify(), Verification::Match);
+}
+
+#[test]
+fn test_ogg_seek() {
+  let samples    = native_samples();
+  let bytes      = read_file(OGG_FILE);
+  let mut stream = OggStreamReader::<Cursor<&[u8]>>::from_ogg(
+                     Cursor::new(&bytes[..])).unwrap();
+  let targets    = [0, 1, 255, 256, 700, 1024, 1300, 1479, 767, 10];
+
+  for target in targets.iter() {
+    let start = *target as usize * 2;
+    let end   = start + 200;
+
+    assert!(stream.seek(*target).is_ok());
+
+    let result = stream.iter::<i32>().take(200).collect::<Vec<_>>();
+
+    assert_eq!(&result[..], &samples[start..end.min(samples.len())]);
+  }
+
+  assert!(stream.seek(1480).is_err());
+}
+
+#[test]
+fn test_ogg_invalid() {
+  let mut bytes = read_file(OGG_FILE);
+  let length    = bytes.len();
+
+  // Corrupt the middle of the last page.
+  bytes[length - 100] ^= 0x10;
+
+  let mut stream = OggStreamReader::<&[u8]>::from_ogg(&bytes[..]).unwrap();
+  let samples    = stream.iter::<i32>().count();
+
+  assert!(samples < native_samples().len());
+  assert_eq!(stream.last_error().map(|error| error.kind),
+             Some(ErrorKind::InvalidCRC32));
+
+  let native = read_file(NATIVE_FILE);
+
+  match OggStreamReader::<&[u8]>::from_ogg(&native[..]) {
+    Err(error) => assert_eq!(error, ErrorKind::OggPageParser),
+    Ok(_)      => panic!("Expected an error"),
+  }
+}
+
+#[test]
+fn test_ogg_round_trip() {
+  let native = read_file(NATIVE_FILE);
+  let ogg    = read_file(OGG_FILE);
+
+  // Native to Ogg and back, with the default page size.
+  let mut stream = StreamBuffer::from_buffer(&native).unwrap();
+  let mut writer = OggWriter::new(Vec::new(), 1);
+
+  writer.write_stream(&mut stream).unwrap();
+
+  let bytes = writer.into_inner();
+
+  assert!(bytes.len() < native.len() + DEFAULT_PAGE_SIZE);
+
+  let mut stream = OggStreamReader::<&[u8]>::from_ogg(&bytes[..]).unwrap();
+  let mut result = Vec::new();
+
+  stream.write_native(&mut result).unwrap();
+
+  assert_eq!(result, native);
+
+  // Ogg to native and back, with the same page size and serial number the
+  // asset was written with.
+  let mut stream = OggStreamReader::<&[u8]>::from_ogg(&ogg[..]).unwrap();
+  let mut bytes  = Vec::new();
+
+  stream.write_native(&mut bytes).unwrap();
+
+  let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+  let mut writer = OggWriter::with_page_size(Vec::new(), 0x1234abcd, 700);
+
+  assert_eq!(stream.metadata().len(), 2);
+
+  writer.write_stream(&mut stream).unwrap();
+
+  assert_eq!(writer.into_inner(), ogg);
+}
+
+#[test]
+fn test_ogg_page_size() {
+  let samples = native_samples();
+  let native  = read_file(NATIVE_FILE);
+
+  for page_size in [0, 255, 1000, 100000].iter() {
+    let mut stream = StreamBuffer::from_buffer(&native).unwrap();
+    let mut writer = OggWriter::with_page_size(Vec::new(), 7, *page_size);
+
+    writer.write_stream(&mut stream).unwrap();
+
+    let bytes      = writer.into_inner();
+    let mut stream = OggStreamReader::<Cursor<&[u8]>>::from_ogg(
+                       Cursor::new(&bytes[..])).unwrap();
+
+    stream.compute_md5(true);
+
+    assert_eq!(stream.iter::<i32>().collect::<Vec<_>>(), samples);
+    assert_eq!(stream.verify(), Verification::Match);
+
+    stream.seek(1000).unwrap();
+
+    assert_eq!(stream.iter::<i32>().next(), Some(samples[2000]));
+  }
+}
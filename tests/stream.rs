@@ -3,9 +3,16 @@ extern crate crypto;
 
 use crypto::digest::Digest;
 use crypto::md5::Md5;
-use flac::{Stream, ReadStream};
+use flac::{
+  Stream, StreamBuffer, StreamReader, StreamWriter, ReadStream, Block,
+  Concealment, EncoderConfig, ErrorKind, SkippedRegion, SubsetViolation,
+  Verification,
+};
+use flac::frame::{frame_parser, ChannelAssignment, NumberType, SubsetRule};
+use flac::metadata::{self, Metadata, SeekPoint, StreamInfo};
 use std::cmp;
-use std::fs::File;
+use std::fs::{self, File};
+use std::io::{self, Cursor};
 
 fn to_bytes(value: i32, buffer: &mut [u8]) {
   buffer[0] = value as u8;
@@ -90,3 +97,841 @@ fn test_decoded_md5_sum() {
     assert_eq!(md5_sum, info.md5_sum);
   }
 }
+
+fn md5_sum<I>(samples: I, bits_per_sample: usize) -> [u8; 16]
+ where I: Iterator<Item = i64> {
+  let mut buffer  = [0; 4];
+  let mut md5     = Md5::new();
+  let mut md5_sum = [0; 16];
+  let offset      = get_offset(bits_per_sample);
+
+  for sample in samples {
+    to_bytes(sample as i32, &mut buffer);
+
+    md5.input(&buffer[0..offset]);
+  }
+
+  md5.result(&mut md5_sum);
+
+  md5_sum
+}
+
+#[test]
+fn test_decoded_md5_sum_wide() {
+  let filenames = [
+    "tests/assets/input-24bit.flac",
+    "tests/assets/input-32bit.flac",
+    "tests/assets/input-32bit-size-code.flac",
+    "tests/assets/input-32bit-side.flac",
+  ];
+
+  for filename in filenames.iter() {
+    let mut stream = Stream::<ReadStream<File>>::from_file(filename).unwrap();
+
+    let info    = stream.info();
+    let samples = stream.iter::<i32>().map(|sample| sample as i64);
+
+    assert_eq!(md5_sum(samples, info.bits_per_sample as usize),
+               info.md5_sum);
+
+    let mut stream = Stream::<ReadStream<File>>::from_file(filename).unwrap();
+    let mut block  = Block::<i64>::new();
+    let mut blocks = Vec::new();
+
+    while stream.next_block(&mut block).unwrap() {
+      let channels = (0..block.channels()).map(|channel| {
+        block.channel(channel).to_vec()
+      }).collect::<Vec<_>>();
+
+      blocks.push(channels);
+    }
+
+    let samples = blocks.iter().flat_map(|channels| {
+      (0..channels[0].len()).flat_map(move |i| {
+        channels.iter().map(move |channel| channel[i])
+      })
+    });
+
+    assert_eq!(md5_sum(samples, info.bits_per_sample as usize),
+               info.md5_sum);
+  }
+}
+
+#[test]
+fn test_sample_size() {
+  let mut stream =
+    StreamReader::<File>::from_file("tests/assets/input-24bit.flac").unwrap();
+  let mut block  = Block::<i32>::new();
+  let mut count  = 0;
+
+  // Predictions are wider than an `i32` even though the samples aren't.
+  while stream.next_block(&mut block).unwrap() {
+    count += block.len();
+  }
+
+  assert_eq!(count as u64, stream.info().total_samples);
+
+  let mut stream =
+    StreamReader::<File>::from_file("tests/assets/input-32bit.flac").unwrap();
+  let error      = stream.next_block(&mut block).unwrap_err();
+
+  assert_eq!(error.kind, ErrorKind::InvalidSampleSize);
+  assert_eq!(stream.last_error(), Some(error));
+}
+
+#[test]
+fn test_frame_size() {
+  let bytes = fs::read("tests/assets/input-24bit.flac").unwrap();
+
+  // `StreamInfo` with a maximum block size of 16, and with one channel,
+  // while the frames have 256 samples for each of two channels.
+  let mut small = bytes.clone();
+  let mut mono  = bytes.clone();
+
+  small[8..12].copy_from_slice(&[0, 16, 0, 16]);
+  mono[20] &= !0b00001110;
+
+  for bytes in [small, mono].iter() {
+    let mut stream = StreamBuffer::from_buffer(bytes).unwrap();
+    let results    = stream.try_iter::<i32>().collect::<Vec<_>>();
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].unwrap_err().kind, ErrorKind::InvalidFrameSize);
+
+    let mut stream = StreamBuffer::from_buffer(bytes).unwrap();
+    let mut block  = Block::<i32>::new();
+
+    assert_eq!(stream.next_block(&mut block).unwrap_err().kind,
+               ErrorKind::InvalidFrameSize);
+  }
+}
+
+#[test]
+fn test_try_iter_sample_size() {
+  let filename = "tests/assets/input-24bit.flac";
+  let length   = fs::metadata(filename).unwrap().len();
+
+  let mut stream  = StreamReader::<File>::from_file(filename).unwrap();
+  let block_size  = stream.info().max_block_size as u64;
+  let mut bytes   = Vec::new();
+  let mut offsets = Vec::new();
+  let mut offset  = 0;
+
+  while stream.next_frame_bytes(&mut bytes).unwrap() {
+    offsets.push(offset);
+
+    offset += bytes.len() as u64;
+  }
+
+  let frames_offset = length - offset;
+
+  // Starting from the first frame and from the second one.
+  for &start in [0, block_size].iter() {
+    let mut stream = StreamReader::<File>::from_file(filename).unwrap();
+    let mut block  = Block::<i32>::new();
+    let mut frames = start / block_size;
+    let mut index  = 0;
+
+    stream.seek(start).unwrap();
+
+    // Frame, and index from the start, of the first sample too large for
+    // an `i16`.
+    'frames: while stream.next_block(&mut block).unwrap() {
+      for i in 0..block.len() {
+        for channel in 0..block.channels() {
+          let sample = block.channel(channel)[i];
+
+          if sample < i16::min_value() as i32 ||
+             sample > i16::max_value() as i32 {
+            break 'frames;
+          }
+
+          index += 1;
+        }
+      }
+
+      frames += 1;
+    }
+
+    let mut stream = StreamReader::<File>::from_file(filename).unwrap();
+
+    stream.seek(start).unwrap();
+
+    let results = stream.try_iter::<i16>().collect::<Vec<_>>();
+    let error   = results.last().unwrap().unwrap_err();
+
+    assert_eq!(frames, start / block_size);
+    assert_eq!(results.len(), index + 1);
+    assert!(results[0..index].iter().all(|result| result.is_ok()));
+    assert_eq!(error.kind, ErrorKind::InvalidSampleSize);
+    assert_eq!(error.frame_number, frames);
+    assert_eq!(error.byte_offset, frames_offset + offsets[frames as usize]);
+    assert_eq!(stream.last_error(), Some(error));
+  }
+}
+
+fn test_info() -> StreamInfo {
+  let mut info: StreamInfo = Default::default();
+
+  info.sample_rate     = 44100;
+  info.channels        = 2;
+  info.bits_per_sample = 16;
+  info.max_block_size  = 192;
+
+  info
+}
+
+fn test_samples() -> Vec<i16> {
+  (0..(2 * 5000)).map(|i| {
+    let phase = (i / 2) as f64 * 0.01 + (i % 2) as f64;
+
+    (phase.sin() * 8000.0) as i16
+  }).collect()
+}
+
+fn encode(samples: &[i16], metadata: Vec<Metadata>) -> Vec<u8> {
+  let cursor     = Cursor::new(Vec::new());
+  let mut writer = StreamWriter::with_metadata(cursor, test_info(),
+                                               metadata).unwrap();
+
+  writer.write_samples(samples).unwrap();
+  writer.finalize().unwrap();
+
+  writer.into_inner().into_inner()
+}
+
+fn assert_seek<P>(stream: &mut Stream<P>, samples: &[i16])
+ where P: flac::SeekableProducer {
+  let targets = [0, 1, 191, 192, 193, 2500, 3071, 4999, 1000, 10];
+
+  for target in targets.iter() {
+    let start = *target as usize * 2;
+
+    assert!(stream.seek(*target).is_ok());
+
+    let result = stream.iter::<i16>().take(100).collect::<Vec<i16>>();
+    let end    = cmp::min(start + 100, samples.len());
+
+    assert_eq!(&result[..], &samples[start..end]);
+  }
+
+  assert!(stream.seek(5000).is_err());
+}
+
+#[test]
+fn test_seek() {
+  let samples = test_samples();
+  let bytes   = encode(&samples, vec![]);
+
+  let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+
+  assert_seek(&mut stream, &samples);
+
+  let mut stream = Stream::<ReadStream<Cursor<&[u8]>>>::new(
+                     Cursor::new(&bytes[..])).unwrap();
+
+  assert_seek(&mut stream, &samples);
+}
+
+#[test]
+fn test_seek_table() {
+  let samples = test_samples();
+  let bytes   = encode(&samples, vec![]);
+  let info    = StreamBuffer::from_buffer(&bytes).unwrap().info();
+
+  // Stream info block along with the "fLaC" marker.
+  let frame_offset    = 42;
+  let mut buffer      = vec![0i32; 2 * 192];
+  let mut input       = &bytes[frame_offset..];
+  let mut seek_points = Vec::new();
+  let mut sample      = 0;
+  let mut frames      = 0;
+
+  while !input.is_empty() {
+    let (i, frame) = frame_parser(input, &info, &mut buffer).unwrap();
+
+    if frames % 4 == 0 {
+      seek_points.push(SeekPoint {
+        sample_number: sample,
+        stream_offset: (bytes.len() - input.len() - frame_offset) as u64,
+        frame_samples: frame.header.block_size as u16,
+      });
+    }
+
+    sample += frame.header.block_size as u64;
+    frames += 1;
+    input   = i;
+  }
+
+  let seek_table = Metadata::new(true, 0,
+                                 metadata::Data::SeekTable(seek_points));
+  let bytes      = encode(&samples, vec![seek_table]);
+
+  let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+
+  assert!(stream.metadata()[0].is_seek_table());
+  assert!(frames > 4);
+  assert_seek(&mut stream, &samples);
+}
+
+#[test]
+fn test_decode_errors() {
+  let samples = test_samples();
+  let bytes   = encode(&samples, vec![]);
+
+  let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+
+  assert_eq!(stream.iter::<i16>().count(), samples.len());
+  assert_eq!(stream.last_error(), None);
+
+  // Offset of the fourth frame, after the "fLaC" marker and stream info.
+  let info       = stream.info();
+  let mut buffer = vec![0i32; 2 * 192];
+  let mut input  = &bytes[42..];
+
+  for _ in 0..3 {
+    input = frame_parser(input, &info, &mut buffer).unwrap().0;
+  }
+
+  let offset        = bytes.len() - input.len();
+  let mut corrupted = bytes.clone();
+
+  corrupted[offset + 20] ^= 0b00100000;
+
+  let mut stream = StreamBuffer::from_buffer(&corrupted).unwrap();
+  let results    = stream.try_iter::<i16>().collect::<Vec<_>>();
+  let error      = results.last().unwrap().unwrap_err();
+
+  assert_eq!(results.len(), 3 * 192 * 2 + 1);
+  assert_eq!(error.frame_number, 3);
+  assert_eq!(error.byte_offset, offset as u64);
+  assert_eq!(stream.last_error(), Some(error));
+
+  let truncated  = &bytes[0..(bytes.len() - 10)];
+  let mut stream = Stream::<ReadStream<Cursor<&[u8]>>>::new(
+                     Cursor::new(truncated)).unwrap();
+
+  assert!(stream.iter::<i16>().count() < samples.len());
+
+  let error = stream.last_error().unwrap();
+
+  assert!(error.byte_offset < truncated.len() as u64);
+
+  match error.kind {
+    ErrorKind::Incomplete(_) => (),
+    kind                     => panic!("unexpected error {:?}", kind),
+  }
+}
+
+// Byte offsets of every frame, from the first one to the end of `bytes`.
+fn frame_offsets(bytes: &[u8]) -> Vec<usize> {
+  let info        = StreamBuffer::from_buffer(bytes).unwrap().info();
+  let mut buffer  = vec![0i32; 2 * 192];
+  let mut input   = &bytes[42..];
+  let mut offsets = vec![42];
+
+  while !input.is_empty() {
+    input = frame_parser(input, &info, &mut buffer).unwrap().0;
+
+    offsets.push(bytes.len() - input.len());
+  }
+
+  offsets
+}
+
+#[test]
+fn test_skip_corrupt_frames() {
+  let samples = test_samples();
+  let bytes   = encode(&samples, vec![]);
+  let offsets = frame_offsets(&bytes);
+
+  let mut corrupted = bytes.clone();
+
+  // Break the CRC-16 of the fourth frame and the sync code of the sixth.
+  corrupted[offsets[4] - 1] ^= 0b00100000;
+  corrupted[offsets[5]]      = 0;
+
+  let mut stream = StreamBuffer::from_buffer(&corrupted).unwrap();
+
+  stream.skip_corrupt_frames(true);
+  stream.compute_md5(true);
+
+  let result   = stream.iter::<i16>().collect::<Vec<_>>();
+  let mut kept = samples.clone();
+
+  kept.drain((5 * 192 * 2)..(6 * 192 * 2));
+  kept.drain((3 * 192 * 2)..(4 * 192 * 2));
+
+  assert_eq!(result, kept);
+  assert_eq!(stream.last_error(), None);
+  assert_eq!(stream.verify(), Verification::Unverified);
+  assert_eq!(stream.skipped_regions(), &[
+    SkippedRegion {
+      kind: ErrorKind::InvalidCRC16,
+      byte_offset: offsets[3] as u64,
+      byte_length: (offsets[4] - offsets[3]) as u64,
+      sample_number: 3 * 192,
+      samples: 192,
+    },
+    SkippedRegion {
+      kind: ErrorKind::InvalidSyncCode,
+      byte_offset: offsets[5] as u64,
+      byte_length: (offsets[6] - offsets[5]) as u64,
+      sample_number: 5 * 192,
+      samples: 192,
+    },
+  ]);
+
+  // Seeking into a corrupt frame starts at the frame after it.
+  let mut stream = StreamBuffer::from_buffer(&corrupted).unwrap();
+
+  stream.skip_corrupt_frames(true);
+  stream.seek(600).unwrap();
+
+  assert_eq!(stream.iter::<i16>().next(), Some(samples[4 * 192 * 2]));
+  assert_eq!(stream.skipped_regions().len(), 1);
+
+  // A truncated frame at the end loses every sample after the last one.
+  let last       = offsets.len() - 2;
+  let truncated  = &bytes[0..(bytes.len() - 10)];
+  let mut stream = Stream::<ReadStream<Cursor<&[u8]>>>::new(
+                     Cursor::new(truncated)).unwrap();
+
+  stream.skip_corrupt_frames(true);
+
+  assert_eq!(stream.iter::<i16>().count(), last * 192 * 2);
+  assert_eq!(stream.last_error(), None);
+
+  let region = stream.skipped_regions()[0];
+
+  assert_eq!(stream.skipped_regions().len(), 1);
+  assert_eq!(region.byte_offset, offsets[last] as u64);
+  assert_eq!(region.byte_length, (truncated.len() - offsets[last]) as u64);
+  assert_eq!(region.samples, 5000 - last as u64 * 192);
+}
+
+#[test]
+fn test_conceal_lost_frames() {
+  let samples = test_samples();
+  let bytes   = encode(&samples, vec![]);
+  let offsets = frame_offsets(&bytes);
+
+  // Break the CRC-16 of the fourth frame and the sync code of the sixth,
+  // then cut the last frame short.
+  let mut corrupted = bytes.clone();
+  let length        = corrupted.len();
+
+  corrupted[offsets[4] - 1] ^= 0b00100000;
+  corrupted[offsets[5]]      = 0;
+  corrupted.truncate(length - 10);
+
+  let last       = offsets.len() - 2;
+  let mut silent = samples.clone();
+
+  for sample in &mut silent[(last * 192 * 2)..] {
+    *sample = 0;
+  }
+
+  for sample in &mut silent[(5 * 192 * 2)..(6 * 192 * 2)] {
+    *sample = 0;
+  }
+
+  let mut stream = StreamBuffer::from_buffer(&corrupted).unwrap();
+
+  stream.conceal_lost_frames(Concealment::IgnoreCRC);
+
+  assert_eq!(stream.iter::<i16>().collect::<Vec<_>>(), silent);
+  assert_eq!(stream.skipped_regions().len(), 2);
+
+  for sample in &mut silent[(3 * 192 * 2)..(4 * 192 * 2)] {
+    *sample = 0;
+  }
+
+  let mut stream = StreamBuffer::from_buffer(&corrupted).unwrap();
+
+  stream.conceal_lost_frames(Concealment::Silence);
+
+  assert_eq!(stream.iter::<i16>().collect::<Vec<_>>(), silent);
+  assert_eq!(stream.skipped_regions().len(), 3);
+  assert_eq!(stream.last_error(), None);
+
+  // Seeking into a corrupt frame lands within the silence.
+  let mut stream = Stream::<ReadStream<Cursor<&[u8]>>>::new(
+                     Cursor::new(&corrupted[..])).unwrap();
+
+  stream.conceal_lost_frames(Concealment::Silence);
+  stream.seek(1000).unwrap();
+
+  let result = stream.iter::<i16>().collect::<Vec<_>>();
+
+  assert_eq!(&result[..], &silent[2000..]);
+}
+
+fn parallel_samples<P>(stream: &mut Stream<P>, threads: usize) -> Vec<i32>
+ where P: flac::StreamProducer {
+  let mut samples = Vec::new();
+
+  for block in stream.parallel_blocks::<i32>(threads) {
+    let block = block.unwrap();
+
+    for i in 0..block.len() {
+      for channel in 0..block.channels() {
+        samples.push(block.channel(channel)[i]);
+      }
+    }
+  }
+
+  samples
+}
+
+#[test]
+fn test_parallel_blocks() {
+  let filenames = [
+    "tests/assets/input-pictures.flac",
+    "tests/assets/input-SCPAP.flac",
+    "tests/assets/input-SVAUP.flac",
+    "tests/assets/input-24bit.flac",
+  ];
+
+  for filename in filenames.iter() {
+    let mut stream = StreamReader::<File>::from_file(filename).unwrap();
+    let samples    = stream.iter::<i32>().collect::<Vec<_>>();
+
+    for threads in [0, 1, 3].iter() {
+      let mut stream = StreamReader::<File>::from_file(filename).unwrap();
+
+      stream.compute_md5(true);
+
+      assert_eq!(parallel_samples(&mut stream, *threads), samples);
+      assert_eq!(stream.verify(), Verification::Match);
+    }
+  }
+
+  let samples = test_samples();
+  let bytes   = encode(&samples, vec![]);
+  let offsets = frame_offsets(&bytes);
+
+  let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+
+  stream.seek(1000).unwrap();
+
+  let result = parallel_samples(&mut stream, 2);
+
+  assert_eq!(result.len(), samples.len() - 2000);
+  assert!(result.iter().zip(&samples[2000..]).all(|(a, b)| *a == *b as i32));
+
+  let mut corrupted = bytes.clone();
+
+  corrupted[offsets[4] - 1] ^= 0b00100000;
+
+  let mut stream = StreamBuffer::from_buffer(&corrupted).unwrap();
+  let results    = stream.parallel_blocks::<i32>(4).collect::<Vec<_>>();
+  let error      = match results.last() {
+    Some(&Err(error)) => error,
+    _                 => panic!("Expected an error"),
+  };
+
+  assert_eq!(results.len(), 4);
+  assert_eq!(error.kind, ErrorKind::InvalidCRC16);
+  assert_eq!(error.frame_number, 3);
+  assert_eq!(error.byte_offset, offsets[3] as u64);
+  assert_eq!(stream.last_error(), Some(error));
+}
+
+#[test]
+fn test_parallel_blocks_frame_size() {
+  let mut bytes = fs::read("tests/assets/input-24bit.flac").unwrap();
+
+  // A maximum block size of 16 in `StreamInfo`, smaller than the frames.
+  bytes[8..12].copy_from_slice(&[0, 16, 0, 16]);
+
+  for threads in [1, 3].iter() {
+    let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+    let results    = stream.parallel_blocks::<i32>(*threads)
+                           .collect::<Vec<_>>();
+    let error      = match results.last() {
+      Some(&Err(error)) => error,
+      _                 => panic!("Expected an error"),
+    };
+
+    assert_eq!(results.len(), 1);
+    assert_eq!(error.kind, ErrorKind::InvalidFrameSize);
+    assert_eq!(error.frame_number, 0);
+    assert_eq!(stream.last_error(), Some(error));
+  }
+}
+
+#[test]
+fn test_parallel_blocks_dropped() {
+  let samples = test_samples();
+  let bytes   = encode(&samples, vec![]);
+
+  let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+  let mut block  = Block::<i32>::new();
+  let mut frame  = Vec::new();
+
+  assert_eq!(stream.parallel_blocks::<i32>(2).take(2).count(), 2);
+
+  // Frames after the second one were read ahead and lost.
+  let error = stream.next_block(&mut block).unwrap_err();
+
+  assert_eq!(error.kind, ErrorKind::InvalidPosition);
+  assert_eq!(stream.last_error(), Some(error));
+  assert_eq!(stream.next_frame_bytes(&mut frame).unwrap_err().kind,
+             ErrorKind::InvalidPosition);
+
+  match stream.parallel_blocks::<i32>(2).next() {
+    Some(Err(error)) => assert_eq!(error.kind, ErrorKind::InvalidPosition),
+    _                => panic!("Expected an error"),
+  }
+
+  stream.seek(2 * 192).unwrap();
+
+  assert!(stream.next_block(&mut block).unwrap());
+  assert_eq!(block.channel(0)[0], samples[2 * 2 * 192] as i32);
+  assert_eq!(block.channel(1)[0], samples[2 * 2 * 192 + 1] as i32);
+
+  // Nothing gets lost once every block is handed out.
+  let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+
+  assert_eq!(stream.parallel_blocks::<i32>(2).count(), 27);
+  assert!(!stream.next_block(&mut block).unwrap());
+  assert_eq!(stream.last_error(), None);
+}
+
+#[test]
+fn test_next_block() {
+  let samples = test_samples();
+  let bytes   = encode(&samples, vec![]);
+
+  let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+  let mut block  = Block::<i32>::new();
+  let mut frames = 0;
+  let mut index  = 0;
+
+  while stream.next_block(&mut block).unwrap() {
+    {
+      let header = block.header();
+
+      assert_eq!(header.sample_rate, 44100);
+      assert_eq!(header.number, NumberType::Frame(frames));
+
+      match header.channel_assignment {
+        ChannelAssignment::Independent => assert_eq!(header.channels, 2),
+        _                              => (),
+      }
+    }
+
+    assert_eq!(block.channels(), 2);
+
+    let left  = block.channel(0);
+    let right = block.channel(1);
+
+    assert_eq!(left.len(), block.len());
+    assert_eq!(right.len(), block.len());
+
+    for (l, r) in left.iter().zip(right) {
+      assert_eq!(*l, samples[index] as i32);
+      assert_eq!(*r, samples[index + 1] as i32);
+
+      index += 2;
+    }
+
+    frames += 1;
+  }
+
+  assert_eq!(index, samples.len());
+  assert_eq!(stream.last_error(), None);
+
+  // The first block after a seek starts at the requested sample.
+  assert!(stream.seek(200).is_ok());
+  assert!(stream.next_block(&mut block).unwrap());
+
+  assert_eq!(block.header().number, NumberType::Frame(1));
+  assert_eq!(block.len(), 184);
+  assert_eq!(block.channel(0)[0], samples[400] as i32);
+  assert_eq!(block.channel(1)[0], samples[401] as i32);
+}
+
+#[test]
+fn test_verify() {
+  let filenames = [
+    "tests/assets/input-pictures.flac",
+    "tests/assets/input-SCPAP.flac",
+    "tests/assets/input-SVAUP.flac",
+    "tests/assets/input-24bit.flac",
+    "tests/assets/input-32bit.flac",
+    "tests/assets/input-32bit-size-code.flac",
+    "tests/assets/input-32bit-side.flac",
+  ];
+
+  for filename in filenames.iter() {
+    let mut stream = StreamReader::<File>::from_file(filename).unwrap();
+
+    stream.compute_md5(true);
+
+    assert_eq!(stream.verify(), Verification::Unverified);
+    assert!(stream.iter::<i32>().count() > 0);
+    assert_eq!(stream.verify(), Verification::Match);
+    assert!(stream.md5_matches());
+  }
+
+  let samples = test_samples();
+  let bytes   = encode(&samples, vec![]);
+
+  // Without computing the signature there is nothing to compare.
+  let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+
+  stream.iter::<i16>().count();
+  assert_eq!(stream.verify(), Verification::Unverified);
+
+  // Skipping samples leaves the signature incomplete, until seeking back to
+  // the start.
+  let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+
+  stream.compute_md5(true);
+  stream.seek(100).unwrap();
+  stream.iter::<i16>().count();
+  assert_eq!(stream.verify(), Verification::Unverified);
+
+  stream.seek(0).unwrap();
+  stream.iter::<i16>().count();
+  assert_eq!(stream.verify(), Verification::Match);
+
+  // The signature starts after the "fLaC" marker, metadata header and the
+  // first 18 bytes of stream info.
+  let md5_offset = 4 + 4 + 18;
+  let mut copy   = bytes.clone();
+
+  copy[md5_offset] ^= 0xff;
+
+  let mut stream = StreamBuffer::from_buffer(&copy).unwrap();
+
+  stream.compute_md5(true);
+  stream.iter::<i16>().count();
+  assert_eq!(stream.verify(), Verification::Mismatch);
+  assert!(!stream.md5_matches());
+
+  for byte in &mut copy[md5_offset..(md5_offset + 16)] {
+    *byte = 0;
+  }
+
+  let mut stream = StreamBuffer::from_buffer(&copy).unwrap();
+
+  stream.compute_md5(true);
+  stream.iter::<i16>().count();
+  assert_eq!(stream.verify(), Verification::Unset);
+}
+
+#[test]
+fn test_check_subset() {
+  let filenames = [
+    "tests/assets/input-pictures.flac",
+    "tests/assets/input-SCPAP.flac",
+    "tests/assets/input-SVAUP.flac",
+    "tests/assets/input-24bit.flac",
+    "tests/assets/input-32bit-size-code.flac",
+    "tests/assets/input-32bit-side.flac",
+  ];
+
+  for filename in filenames.iter() {
+    let mut stream = StreamReader::<File>::from_file(filename).unwrap();
+
+    stream.check_subset(true);
+    stream.iter::<i32>().count();
+
+    assert_eq!(stream.subset_violations(), &[], "{}", filename);
+  }
+
+  // Every frame header refers to `StreamInfo` for the bits per sample.
+  let mut stream =
+    StreamReader::<File>::from_file("tests/assets/input-32bit.flac").unwrap();
+
+  stream.check_subset(true);
+  stream.iter::<i32>().count();
+
+  let violations = stream.subset_violations();
+
+  assert_eq!(violations.len(), 4);
+  assert!(violations.iter().enumerate().all(|(i, violation)| {
+    violation.rule == SubsetRule::BitsPerSample &&
+      violation.frame_number == i as u64
+  }));
+
+  let samples: Vec<i16> = (0..(2 * 16384)).map(|i| {
+    let phase = (i / 2) as f64 * 0.01 + (i % 2) as f64;
+
+    (phase.sin() * 8000.0) as i16
+  }).collect();
+
+  let mut config = EncoderConfig::level(5);
+
+  config.block_size                   = 8192;
+  config.subframe.min_partition_order = 9;
+  config.subframe.max_partition_order = 9;
+
+  let mut writer = StreamWriter::with_config(Vec::new(), test_info(),
+                                             Vec::new(), config).unwrap();
+
+  writer.write_samples(&samples).unwrap();
+  writer.finish().unwrap();
+
+  let bytes      = writer.into_inner();
+  let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+  let mut frame  = Vec::new();
+
+  stream.check_subset(true);
+
+  assert!(stream.next_frame_bytes(&mut frame).unwrap());
+
+  let second = 42 + frame.len() as u64;
+
+  assert_eq!(stream.iter::<i16>().count(), 8192 * 2);
+
+  let violations = |frame_number: u64, byte_offset: u64| {
+    let rules = [
+      SubsetRule::BlockSize(8192),
+      SubsetRule::PartitionOrder { channel: 0, order: 9 },
+      SubsetRule::PartitionOrder { channel: 1, order: 9 },
+    ];
+
+    rules.iter().map(|rule| SubsetViolation {
+      rule: *rule,
+      frame_number: frame_number,
+      byte_offset: byte_offset,
+    }).collect::<Vec<_>>()
+  };
+
+  let mut expected = violations(0, 42);
+
+  expected.extend(violations(1, second));
+
+  assert_eq!(stream.subset_violations(), &expected[..]);
+}
+
+// Reader that only hands out a single byte at a time.
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> io::Read for ByteReader<'a> {
+  fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+    if self.0.is_empty() || buffer.is_empty() {
+      return Ok(0);
+    }
+
+    buffer[0] = self.0[0];
+    self.0    = &self.0[1..];
+
+    Ok(1)
+  }
+}
+
+#[test]
+fn test_slow_reader() {
+  let samples = test_samples();
+  let bytes   = encode(&samples, vec![]);
+
+  let mut stream = Stream::<ReadStream<ByteReader>>::new(
+                     ByteReader(&bytes)).unwrap();
+
+  assert_eq!(stream.iter::<i16>().collect::<Vec<_>>(), samples);
+}
@@ -0,0 +1,83 @@
+extern crate flac;
+
+use flac::{EncoderConfig, Stream, StreamBuffer, StreamWriter, ReadStream};
+use flac::metadata::{self, StreamInfo};
+use std::fs::File;
+use std::io::Cursor;
+
+fn encode_decode(filename: &str) {
+  let mut stream = Stream::<ReadStream<File>>::from_file(filename).unwrap();
+  let info       = stream.info();
+  let samples    = stream.iter::<i32>().collect::<Vec<i32>>();
+
+  let mut stream_info: StreamInfo = Default::default();
+
+  stream_info.sample_rate     = info.sample_rate;
+  stream_info.channels        = info.channels;
+  stream_info.bits_per_sample = info.bits_per_sample;
+  stream_info.max_block_size  = info.max_block_size;
+
+  let cursor     = Cursor::new(Vec::new());
+  let mut writer = StreamWriter::with_metadata(cursor, stream_info, vec![
+    metadata::Metadata::new(false, 10, metadata::Data::Padding(10)),
+  ]).unwrap();
+
+  writer.write_samples(&samples).unwrap();
+  writer.finalize().unwrap();
+
+  let result = writer.info();
+  let bytes  = writer.into_inner().into_inner();
+
+  assert_eq!(result.total_samples, info.total_samples);
+  assert_eq!(result.md5_sum, info.md5_sum);
+
+  let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+
+  assert_eq!(stream.info(), result);
+  assert_eq!(stream.metadata().len(), 1);
+  assert_eq!(stream.iter::<i32>().collect::<Vec<i32>>(), samples);
+}
+
+#[test]
+fn test_encode_round_trip() {
+  encode_decode("tests/assets/input-pictures.flac");
+  encode_decode("tests/assets/input-SCPAP.flac");
+  encode_decode("tests/assets/input-SVAUP.flac");
+  encode_decode("tests/assets/input-24bit.flac");
+  encode_decode("tests/assets/input-32bit.flac");
+  encode_decode("tests/assets/input-32bit-size-code.flac");
+  encode_decode("tests/assets/input-32bit-side.flac");
+}
+
+#[test]
+fn test_encode_levels() {
+  let filename   = "tests/assets/input-24bit.flac";
+  let mut stream = Stream::<ReadStream<File>>::from_file(filename).unwrap();
+  let info       = stream.info();
+  let samples    = stream.iter::<i32>().collect::<Vec<i32>>();
+
+  let mut sizes = Vec::new();
+
+  for level in 0..9 {
+    let config     = EncoderConfig::level(level);
+    let mut writer = StreamWriter::with_config(Vec::new(), info, Vec::new(),
+                                               config).unwrap();
+
+    writer.write_samples(&samples).unwrap();
+    writer.finish().unwrap();
+
+    assert_eq!(writer.info().md5_sum, info.md5_sum);
+
+    let bytes      = writer.into_inner();
+    let mut stream = StreamBuffer::from_buffer(&bytes).unwrap();
+
+    assert_eq!(stream.iter::<i32>().collect::<Vec<i32>>(), samples, "{}",
+               level);
+
+    sizes.push(bytes.len());
+  }
+
+  // Higher levels never lose to the lowest one.
+  assert!(sizes[1..].iter().all(|size| *size <= sizes[0]), "{:?}", sizes);
+  assert!(sizes[8] <= sizes[5], "{:?}", sizes);
+}